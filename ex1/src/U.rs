@@ -5,137 +5,702 @@
 // ******************************************************************
 #![allow(non_snake_case)]
 
-use std::fs::File;
-use std::io::{BufRead, BufReader};
-
 // ========================================
 //  CVSデータ
 // ========================================
+#[allow(clippy::upper_case_acronyms)]
 pub struct CSV {
     pub ncols: u32, // 列数
     pub nrows: u32, // 行数
-    pub cols: Vec<Vec<f64>>,    // 列ベクトルの集合
-    pub colNames: Vec<String>   // 列名
+    pub cols: Vec<Vec<f64>>,    // 列ベクトルの集合（カテゴリ列は水準の番号、テキスト列は全てNaN）
+    pub colNames: Vec<String>,  // 列名
+    pub kinds: Vec<ColKind>,    // 列の種類
+    pub levels: Vec<Vec<String>>,   // カテゴリ列の水準（cols[j]の値がlevels[j]の位置）．他の列は空
+    pub texts: Vec<Vec<String>>,    // テキスト列のセルの内容．他の列は空
+
+    // 読み込みの設定（readの前にdelimiter()などで指定する）
+    delimiter: Option<char>,    // 区切り文字．Noneなら1行目から推定する
+    hasHeader: bool,            // 1行目が列名か
+    commentChar: Option<char>,  // この文字で始まる行は読み飛ばす
+    selection: Option<Vec<Col>>,    // 読み込む列（Noneなら全ての列）
+    naValues: Vec<String>,      // 欠損値(NaNとして読む)とみなすセルの内容
+    kindOverrides: Vec<(Col, ColKind)>  // 種類を指定された列（指定のない列は推定する）
+}
+
+// ========================================
+//  列の種類
+// ========================================
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColKind {
+    Numeric,        // 数値
+    Categorical,    // カテゴリ（水準の辞書を作り、値は水準の番号にする）
+    Text            // テキスト（そのまま保持する．数値の行列には含めない）
 }
 
 // カスタムエラー識別子
+// 行番号はファイルの1行目を1とする（クォート内の改行も1行に数える）
+#[derive(Debug)]
 pub enum CSVError {
-    IoErr,
-    ParseErr
+    Io(std::io::Error),     // ファイルを読めない
+    Parse {                 // 数値として読めないセルがある
+        line: usize,
+        column: String,     // 列名
+        text: String        // セルの内容
+    },
+    Ragged {                // 列名の行（ヘッダがなければ最初の行）と列数が違う行がある
+        line: usize,
+        expected: usize,
+        found: usize
+    },
+    UnknownColumn(String)   // columns(), columnType(), splitXY()で指定した列が見つからない
+}
+
+impl std::fmt::Display for CSVError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CSVError::Io(e) => write!(f, "io error: {}", e),
+            CSVError::Parse { line, column, text } =>
+                write!(f, "line {}, column \"{}\": cannot parse \"{}\" as a number", line, column, text),
+            CSVError::Ragged { line, expected, found } =>
+                write!(f, "line {}: expected {} fields but found {}", line, expected, found),
+            CSVError::UnknownColumn(c) => write!(f, "unknown column {}", c)
+        }
+    }
+}
+
+impl std::error::Error for CSVError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CSVError::Io(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+// ========================================
+//  列の指定（名前、または0から始まる列番号）
+// ========================================
+#[derive(Clone, Debug)]
+pub enum Col {
+    Name(String),
+    Index(usize)
 }
 
+// 文字列から列の指定を作る（数字なら列番号、それ以外は列名）
+// 数字だけの列名は先頭に=を付けて指定する (ex) "=2020" は列名"2020"、"2020"は2020番目の列
+impl std::str::FromStr for Col {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(name) = s.trim().strip_prefix('=') {
+            return Ok(Col::Name(name.to_string()));
+        }
+        match s.trim().parse::<usize>() {
+            Ok(i) => Ok(Col::Index(i)),
+            Err(_) => Ok(Col::Name(s.trim().to_string()))
+        }
+    }
+}
+
+// 欠損値とみなすセルの内容（デフォルト）
+const NA_VALUES: [&str; 6] = ["", "NA", "N/A", "NaN", "null", "?"];
+
+// 区切り文字の推定候補
+const DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+
 impl CSV {
     // static method（C++/C#などのclass methodに相当）
     pub fn new() -> CSV {
-        return CSV {
+        CSV {
             ncols: 0,
             nrows: 0,
             cols: vec![],
-            colNames: vec![]
-        };
+            colNames: vec![],
+            kinds: vec![],
+            levels: vec![],
+            texts: vec![],
+            delimiter: None,
+            hasHeader: true,
+            commentChar: None,
+            selection: None,
+            naValues: NA_VALUES.iter().map(|s| s.to_string()).collect(),
+            kindOverrides: vec![]
+        }
+    }
+
+    // 区切り文字を指定する（指定しなければ最初の行で最も多く現れる , ; タブ | のどれか）
+    // (ex) let mut csv = U::CSV::new().delimiter(',');
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = Some(delimiter);
+        self
     }
 
+    // 1行目が列名かどうかを指定する（デフォルトはtrue）
+    // falseの場合、列名は"col0", "col1", ...とする
+    pub fn header(mut self, hasHeader: bool) -> Self {
+        self.hasHeader = hasHeader;
+        self
+    }
+
+    // コメント行の先頭文字を指定する（(ex) '#'）
+    pub fn comment(mut self, commentChar: char) -> Self {
+        self.commentChar = Some(commentChar);
+        self
+    }
+
+    // 欠損値とみなすセルの内容を指定する（デフォルトは 空, NA, N/A, NaN, null, ?）
+    // 欠損値はf64::NANとして読む
+    pub fn naValues(mut self, naValues: &[&str]) -> Self {
+        self.naValues = naValues.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    // 列の種類を指定する
+    // 指定しない列は、欠損値以外のセルの過半数が数値として読めなければCategorical、そうでなければNumericとする
+    // （Numericの列に数値として読めないセルがあればreadはCSVError::Parseを返す）
+    // (ex) let mut csv = U::CSV::new().columnType(U::Col::Name("region".to_string()), U::ColKind::Categorical);
+    pub fn columnType(mut self, col: Col, kind: ColKind) -> Self {
+        self.kindOverrides.push((col, kind));
+        self
+    }
+
+    // 読み込む列を指定する（指定した順に並ぶ）
+    // (ex) let mut csv = U::CSV::new().columns(&[U::Col::Name("alcohol".to_string()), U::Col::Index(11)]);
+    pub fn columns(mut self, selection: &[Col]) -> Self {
+        self.selection = Some(selection.to_vec());
+        self
+    }
+
+    // ============================================================
+    //  CSVファイルを読み込む
+    //  ダブルクォートで囲まれたフィールドはRFC 4180に従って読む
+    //  （区切り文字、改行を含められる．""は"1文字になる．列名の"も取り除く）
+    //  空行、コメント行は読み飛ばす．欠損値(naValues参照)はf64::NANにする
+    //
+    //  @param fpath ファイル名
+    // ============================================================
     pub fn read(&mut self, fpath: &str) -> Result<(), CSVError> {
-        let resFile = File::open(fpath);
-        if resFile.is_err() {
-            // ファイル読み込み失敗
-            return Err(CSVError::IoErr);
-        }
-        let file = resFile.unwrap();
-        let reader = BufReader::new(file);
-    
-        self.cols = vec![];
-        self.colNames = vec![];
-    
-        let mut nCols = 0;
-        let mut nRows = 0;
-
-        let mut bFirstLine: bool = true;
-        // std::io::BufReadのlines()イテレータで行ごとに読む
-        for (index, line) in reader.lines().enumerate() {
-            if line.is_err() {
-                return Err(CSVError::IoErr);
-            }
-            let line = line.unwrap();
-            // println!("[{}] {}", index+1, line);
-            // let elms = line.split(",");
-            let elms = line.split(";");
-            if bFirstLine {
-                // 1行目（=列名の行）
-                bFirstLine = false; // もうここは通らない
-                for (_, elm) in elms.enumerate() {
-                    self.colNames.push(elm.trim().to_string());
-                    // println!("{}", elm.trim());
-                }
-                nCols = self.colNames.len() as u32;  // 列数
-                for _ in 0..nCols {
-                    let col: Vec<f64> = vec![];
-                    self.cols.push(col); // 空の列を追加
-                }
-            } else {
-                // 2行目以降
-                for (ind, elm) in elms.enumerate() {
-                    // colNames.push(elm.trim().to_string());
-                    // println!("ind={}, elm={}", ind, elm);
-                    let resVal = elm.trim().parse::<f64>();
-                    if resVal.is_err() {
-                        return Err(CSVError::ParseErr);
-                    }
-                    self.cols[ind].push(resVal.unwrap());
+        let text = match std::fs::read_to_string(fpath) {
+            Ok(t) => t,
+            Err(e) => { return Err(CSVError::Io(e)); }   // ファイル読み込み失敗
+        };
+
+        let delimiter = self.delimiter.unwrap_or_else(|| detectDelimiter(&text, self.commentChar));
+        let mut records = parseRecords(&text, delimiter, self.commentChar);
+
+        // 列名
+        let allNames: Vec<String> = if !self.hasHeader {
+            let n = records.first().map_or(0, |(_, r)| r.len());
+            (0..n).map(|j| format!("col{}", j)).collect()
+        } else if records.is_empty() {
+            vec![]
+        } else {
+            records.remove(0).1
+        };
+
+        // 読み込む列の番号
+        let indices: Vec<usize> = match self.selection {
+            Some(ref sel) => {
+                let mut v = vec![];
+                for c in sel {
+                    v.push(resolveColumn(c, &allNames)?);
                 }
-                nRows = nRows + 1;
-            }
+                v
+            },
+            None => (0..allNames.len()).collect()
+        };
+
+        // 種類を指定された列
+        let mut forced: Vec<(usize, ColKind)> = vec![];
+        for (c, kind) in &self.kindOverrides {
+            forced.push((resolveColumn(c, &allNames)?, *kind));
         }
 
-        self.nrows = nRows;
-        self.ncols = nCols;
-    
-        Ok(())
-    }
+        // 列数が合わない行
+        checkRagged(&records, allNames.len())?;
 
-    // CSV構造体を表示
-    pub fn print(&self) {
-        let nrows = self.nrows;
-        let ncols = self.ncols;
-        println!("({}, {})", nrows, ncols);
+        self.colNames = indices.iter().map(|j| allNames[*j].clone()).collect();
+        self.cols = vec![];
+        self.kinds = vec![];
+        self.levels = vec![];
+        self.texts = vec![];
 
-        for colName in &self.colNames {
-            print!("{} ", colName);
-        }
-        println!();
+        for j in indices.iter().cloned() {
+            let cells: Vec<&str> = records.iter().map(|(_, r)| r[j].trim()).collect();
+            let isNa = |cell: &str| self.naValues.iter().any(|na| na == cell);
 
-        for irow in 0..nrows {
-            for jcol in 0..ncols {
-                let col: &Vec<f64> = &self.cols[jcol as usize];
-                let val = col[irow as usize];
-                print!("{} ", val);
+            // 列の種類（指定がなければ推定）
+            // 数値の列に紛れ込んだ誤り（(ex) 1,2,abc,4）をカテゴリ列にしないように、
+            // 欠損値以外のセルの過半数が数値として読めない列だけをカテゴリ列とみなす
+            let kind = match forced.iter().rev().find(|(k, _)| *k == j) {
+                Some((_, kind)) => *kind,
+                None => {
+                    let present = cells.iter().filter(|c| !isNa(c)).count();
+                    let nonNumeric = cells.iter().filter(|c| !isNa(c) && c.parse::<f64>().is_err()).count();
+                    if nonNumeric * 2 > present {
+                        ColKind::Categorical
+                    } else {
+                        ColKind::Numeric
+                    }
+                }
+            };
+
+            let mut col: Vec<f64> = vec![];
+            let mut levels: Vec<String> = vec![];
+            let mut texts: Vec<String> = vec![];
+            match kind {
+                ColKind::Numeric => {
+                    col = numericColumn(&records, j, &allNames[j], &self.naValues)?;
+                },
+                ColKind::Categorical => {
+                    // 水準は昇順（全て有限の数値なら数値の順、そうでなければ文字列の順）
+                    levels = cells.iter().filter(|c| !isNa(c)).map(|c| c.to_string()).collect();
+                    levels.sort();
+                    levels.dedup();
+                    let numbers: Option<Vec<f64>> = levels.iter()
+                        .map(|l| l.parse::<f64>().ok().filter(|v| v.is_finite()))
+                        .collect();
+                    if let Some(numbers) = numbers {
+                        let mut order: Vec<usize> = (0..levels.len()).collect();
+                        order.sort_by(|a, b| numbers[*a].total_cmp(&numbers[*b]));
+                        levels = order.iter().map(|k| levels[*k].clone()).collect();
+                    }
+                    col = cells.iter().map(|c| {
+                        if isNa(c) { f64::NAN } else { levels.iter().position(|l| l == c).unwrap() as f64 }
+                    }).collect();
+                },
+                ColKind::Text => {
+                    texts = cells.iter().map(|c| c.to_string()).collect();
+                    col = vec![f64::NAN; cells.len()];
+                }
             }
-            println!();
+            self.cols.push(col);
+            self.kinds.push(kind);
+            self.levels.push(levels);
+            self.texts.push(texts);
         }
+
+        self.nrows = records.len() as u32;
+        self.ncols = indices.len() as u32;
+
+        Ok(())
     }
 
-    // このCSVオブジェクトの一部の列からなる別のCSVオブジェクトを生成して返す
+    // このCSVオブジェクトの指定の列（列番号の順）からなる別のCSVオブジェクトを生成して返す
     //
     // (ex)
     // let mut csv = U::CSV::new();
     // csv.read(filePath);
-    // let csv2 = csv.clonePartial(3, 5);  // 3列目、4列目をクローンして返す
-    pub fn clonePartial(&self, startCol: usize, endCol: usize) -> Self {
+    // let csv2 = csv.selectColumns(&[4, 0]);  // 4列目、0列目をクローンして返す
+    pub fn selectColumns(&self, indices: &[usize]) -> Self {
         let mut newCols = vec![];
         let mut newColNames = vec![];
+        let mut newKinds = vec![];
+        let mut newLevels = vec![];
+        let mut newTexts = vec![];
 
-        for i in startCol..endCol {
+        for i in indices.iter().cloned() {
             newCols.push(self.cols[i].clone());
             newColNames.push(self.colNames[i].clone());
+            // read()を使わずに作ったCSVは種類の情報がないので数値列とする
+            newKinds.push(self.kinds.get(i).cloned().unwrap_or(ColKind::Numeric));
+            newLevels.push(self.levels.get(i).cloned().unwrap_or_default());
+            newTexts.push(self.texts.get(i).cloned().unwrap_or_default());
         }
 
-        return CSV {
-            ncols: (endCol-startCol) as u32,
+        CSV {
+            ncols: indices.len() as u32,
             nrows: self.nrows,
             cols: newCols,
-            colNames: newColNames
+            colNames: newColNames,
+            kinds: newKinds,
+            levels: newLevels,
+            texts: newTexts,
+            ..CSV::new()
+        }
+    }
+
+    // ==========================================================
+    //  列名（か列番号）で説明変数と目的変数の列を選ぶ
+    //
+    //  @param target 目的変数の列（複数なら多出力回帰）
+    //  @param features 説明変数の列（Noneなら目的変数以外の全ての列．目的変数の列は指定しても除く）
+    //  @param drop 説明変数から除く列
+    //
+    //  @return (説明変数のCSV, 目的変数のCSV)．見つからない列があればCSVError::UnknownColumn
+    //
+    //  (ex)
+    //  let (x, y) = csv.splitXY(&[U::Col::Name("quality".to_string())], None, &[U::Col::Name("density".to_string())])?;
+    // ==========================================================
+    pub fn splitXY(&self, target: &[Col], features: Option<&[Col]>, drop: &[Col]) -> Result<(CSV, CSV), CSVError> {
+        let mut yIndices = vec![];
+        for c in target {
+            yIndices.push(resolveColumn(c, &self.colNames)?);
+        }
+        let mut dropIndices = vec![];
+        for c in drop {
+            dropIndices.push(resolveColumn(c, &self.colNames)?);
+        }
+        let mut xIndices: Vec<usize> = match features {
+            Some(cols) => {
+                let mut v = vec![];
+                for c in cols {
+                    v.push(resolveColumn(c, &self.colNames)?);
+                }
+                v
+            },
+            None => (0..self.colNames.len()).collect()
         };
+        xIndices.retain(|j| !yIndices.contains(j) && !dropIndices.contains(j));
+
+        Ok((self.selectColumns(&xIndices), self.selectColumns(&yIndices)))
     }
+
+    // ============================================================
+    //  CSVファイルをchunkRows行ずつ読むリーダーを作る（メモリに載らない大きなファイル用）
+    //  区切り文字、列名、コメント、欠損値、読み込む列はreadと同じ設定を使う
+    //  全ての列を数値として読む（columnTypeの指定は使わない）
+    //
+    //  @param fpath ファイル名
+    //  @param chunkRows 1回に読む行数
+    //
+    //  (ex)
+    //  for chunk in U::CSV::new().chunks("big.csv", 10000)? {
+    //      let chunk = chunk?;     // chunkRows行分のCSV
+    //  }
+    // ============================================================
+    pub fn chunks(&self, fpath: &str, chunkRows: usize) -> Result<CSVChunks, CSVError> {
+        let file = std::fs::File::open(fpath).map_err(CSVError::Io)?;
+        let mut chunks = CSVChunks {
+            reader: std::io::BufReader::new(file),
+            delimiter: self.delimiter.unwrap_or(','),
+            commentChar: self.commentChar,
+            naValues: self.naValues.clone(),
+            colNames: vec![],
+            indices: vec![],
+            chunkRows: chunkRows.max(1),
+            line: 1,
+            pending: vec![],
+            done: false
+        };
+
+        // 最初のレコード（列名、またはデータの1行目）で区切り文字と列を決める
+        let mut first = vec![];
+        while first.is_empty() {
+            let (line, text) = match chunks.readRecordText()? {
+                Some(t) => t,
+                None => break
+            };
+            if self.delimiter.is_none() {
+                chunks.delimiter = detectDelimiter(&text, self.commentChar);
+            }
+            first = parseRecords(&text, chunks.delimiter, self.commentChar).into_iter()
+                .map(|(l, r)| (line + l - 1, r))
+                .collect();
+        }
+        let allNames: Vec<String> = match first.pop() {
+            None => vec![],
+            Some((_, names)) if self.hasHeader => names,
+            Some(record) => {
+                let names = (0..record.1.len()).map(|j| format!("col{}", j)).collect();
+                chunks.pending.push(record);
+                names
+            }
+        };
+
+        chunks.indices = match self.selection {
+            Some(ref sel) => {
+                let mut v = vec![];
+                for c in sel {
+                    v.push(resolveColumn(c, &allNames)?);
+                }
+                v
+            },
+            None => (0..allNames.len()).collect()
+        };
+        chunks.colNames = allNames;
+        Ok(chunks)
+    }
+}
+
+// ========================================
+//  CSVファイルを数行ずつ読むリーダー（CSV::chunksで作る）
+//  Iteratorとして、chunkRows行ずつのCSV（全て数値列）を返す
+// ========================================
+pub struct CSVChunks {
+    reader: std::io::BufReader<std::fs::File>,
+    delimiter: char,
+    commentChar: Option<char>,
+    naValues: Vec<String>,
+    colNames: Vec<String>,  // ファイルの全ての列名
+    indices: Vec<usize>,    // 読み込む列の番号
+    chunkRows: usize,       // 1回に読む行数
+    line: usize,            // 次に読む行の行番号
+    pending: Vec<(usize, Vec<String>)>, // 読んだがまだ返していないレコード
+    done: bool              // ファイルの終わりまで読んだか（エラーの後も含む）
+}
+
+impl CSVChunks {
+    // 読み込む列の列名
+    pub fn colNames(&self) -> Vec<String> {
+        self.indices.iter().map(|j| self.colNames[*j].clone()).collect()
+    }
+
+    // -----------------------------------------------
+    // 1レコード分のテキスト（クォートの中の改行を含む）を読む
+    //
+    // @return (開始行番号, テキスト)．ファイルの終わりならNone
+    // -----------------------------------------------
+    fn readRecordText(&mut self) -> Result<Option<(usize, String)>, CSVError> {
+        use std::io::BufRead;
+
+        let start = self.line;
+        let mut text = String::new();
+        let mut inQuotes = false;
+        loop {
+            let mut physical = String::new();
+            let n = self.reader.read_line(&mut physical).map_err(CSVError::Io)?;
+            if n == 0 {
+                break;
+            }
+            self.line += 1;
+            let isComment = !inQuotes && self.commentChar.is_some_and(|c| physical.starts_with(c));
+            if !isComment && physical.chars().filter(|c| *c == '"').count() % 2 == 1 {
+                inQuotes = !inQuotes;
+            }
+            text.push_str(&physical);
+            if !inQuotes {
+                break;
+            }
+        }
+        if text.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some((start, text)))
+    }
+
+    // chunkRows行分のレコードを読んで数値の列にする
+    fn readChunk(&mut self) -> Result<Option<CSV>, CSVError> {
+        let mut records: Vec<(usize, Vec<String>)> = std::mem::take(&mut self.pending);
+        while records.len() < self.chunkRows {
+            let (line, text) = match self.readRecordText()? {
+                Some(t) => t,
+                None => { self.done = true; break; }
+            };
+            records.extend(parseRecords(&text, self.delimiter, self.commentChar).into_iter().map(|(l, r)| (line + l - 1, r)));
+        }
+        if records.is_empty() {
+            return Ok(None);
+        }
+
+        checkRagged(&records, self.colNames.len())?;
+        let mut cols = vec![];
+        for j in self.indices.iter().cloned() {
+            cols.push(numericColumn(&records, j, &self.colNames[j], &self.naValues)?);
+        }
+
+        let ncols = self.indices.len();
+        Ok(Some(CSV {
+            ncols: ncols as u32,
+            nrows: records.len() as u32,
+            cols,
+            colNames: self.colNames(),
+            kinds: vec![ColKind::Numeric; ncols],
+            levels: vec![vec![]; ncols],
+            texts: vec![vec![]; ncols],
+            ..CSV::new()
+        }))
+    }
+}
+
+impl Iterator for CSVChunks {
+    type Item = Result<CSV, CSVError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done && self.pending.is_empty() {
+            return None;
+        }
+        match self.readChunk() {
+            Ok(chunk) => chunk.map(Ok),
+            Err(e) => {
+                self.done = true;
+                self.pending.clear();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+// -----------------------------------------------
+// 列数が合わないレコードがあればCSVError::Ragged
+// -----------------------------------------------
+fn checkRagged(records: &[(usize, Vec<String>)], ncols: usize) -> Result<(), CSVError> {
+    for (line, record) in records {
+        if record.len() != ncols {
+            return Err(CSVError::Ragged { line: *line, expected: ncols, found: record.len() });
+        }
+    }
+    Ok(())
+}
+
+// -----------------------------------------------
+// レコードのj列目を数値として読む（欠損値はNaN）
+//
+// @param name 列名（エラー表示用）
+// -----------------------------------------------
+fn numericColumn(records: &[(usize, Vec<String>)], j: usize, name: &str, naValues: &[String]) -> Result<Vec<f64>, CSVError> {
+    let mut col = vec![];
+    for (line, record) in records {
+        let cell = record[j].trim();
+        if naValues.iter().any(|na| na == cell) {
+            col.push(f64::NAN);
+            continue;
+        }
+        match cell.parse::<f64>() {
+            Ok(v) => col.push(v),
+            Err(_) => {
+                return Err(CSVError::Parse { line: *line, column: name.to_string(), text: record[j].clone() });
+            }
+        }
+    }
+    Ok(col)
+}
+
+// -----------------------------------------------
+// 列の指定を列番号にする
+//
+// @param c 列の指定
+// @param names 全ての列名
+// -----------------------------------------------
+fn resolveColumn(c: &Col, names: &[String]) -> Result<usize, CSVError> {
+    let found = match c {
+        Col::Name(name) => names.iter().position(|n| n == name),
+        Col::Index(i) => if *i < names.len() { Some(*i) } else { None }
+    };
+    match found {
+        Some(j) => Ok(j),
+        None => {
+            let name = match c {
+                Col::Name(name) => name.clone(),
+                Col::Index(i) => format!("#{}", i)
+            };
+            Err(CSVError::UnknownColumn(name))
+        }
+    }
+}
+
+// -----------------------------------------------
+// 区切り文字を推定する
+// コメント、空行を除いた最初の行で（クォートの外に）最も多く現れる候補を選ぶ．どれも無ければ ,
+// -----------------------------------------------
+fn detectDelimiter(text: &str, commentChar: Option<char>) -> char {
+    let first = text.lines()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty() && commentChar.map_or(true, |c| !l.starts_with(c)))
+        .unwrap_or("");
+
+    let mut counts = [0usize; DELIMITERS.len()];
+    let mut inQuotes = false;
+    for c in first.chars() {
+        if c == '"' {
+            inQuotes = !inQuotes;
+        } else if !inQuotes {
+            if let Some(k) = DELIMITERS.iter().position(|d| *d == c) {
+                counts[k] += 1;
+            }
+        }
+    }
+    match (0..DELIMITERS.len()).filter(|k| counts[*k] > 0).max_by_key(|k| (counts[*k], DELIMITERS.len() - k)) {
+        Some(k) => DELIMITERS[k],
+        None => ','
+    }
+}
+
+// -----------------------------------------------
+// CSVのテキストをレコード(フィールドのリスト)に分ける（RFC 4180）
+// クォートされていないフィールドは前後の空白を取り除く
+//
+// @param text ファイルの内容
+// @param delimiter 区切り文字
+// @param commentChar コメント行の先頭文字
+//
+// @return (レコードが始まる行番号, フィールドのリスト)のリスト
+// -----------------------------------------------
+fn parseRecords(text: &str, delimiter: char, commentChar: Option<char>) -> Vec<(usize, Vec<String>)> {
+    let mut records: Vec<(usize, Vec<String>)> = vec![];
+    let mut record: Vec<String> = vec![];
+    let mut field = String::new();
+    let mut quoted = false;         // 今のフィールドがクォートされていたか
+    let mut inQuotes = false;       // クォートの中か
+    let mut atRecordStart = true;   // レコードの先頭か（コメントの判定用）
+    let mut line = 1;               // 今読んでいる行番号
+    let mut recordLine = 1;         // 今のレコードが始まった行番号
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line += 1;
+        }
+        if inQuotes {
+            if c == '"' {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');    // "" --> "
+                    chars.next();
+                } else {
+                    inQuotes = false;
+                }
+            } else {
+                field.push(c);
+            }
+            continue;
+        }
+
+        if atRecordStart && Some(c) == commentChar {
+            // コメント行は行末まで読み飛ばす
+            for c2 in chars.by_ref() {
+                if c2 == '\n' {
+                    line += 1;
+                    break;
+                }
+            }
+            continue;
+        }
+        if atRecordStart {
+            recordLine = line;
+        }
+        atRecordStart = false;
+
+        if c == delimiter || c == '\n' {
+            record.push(if quoted { field.clone() } else { field.trim().to_string() });
+            field.clear();
+            quoted = false;
+            if c == '\n' {
+                // 空行は読み飛ばす
+                if record.len() > 1 || !record[0].is_empty() {
+                    records.push((recordLine, record));
+                }
+                record = vec![];
+                atRecordStart = true;
+            }
+        } else if c == '"' && field.trim().is_empty() && !quoted {
+            field.clear();
+            inQuotes = true;
+            quoted = true;
+        } else if c == '\r' || quoted {
+            // CRLFのCR、閉じクォートと区切り文字の間の文字は無視する
+        } else {
+            field.push(c);
+        }
+    }
+
+    // 最後の行が改行で終わっていない場合
+    if !atRecordStart {
+        record.push(if quoted { field } else { field.trim().to_string() });
+        if record.len() > 1 || !record[0].is_empty() {
+            records.push((recordLine, record));
+        }
+    }
+    records
 }
 
 // =================================================
@@ -149,7 +714,7 @@ impl CSV {
 //
 // @return vの平均値
 // -----------------------------------------------
-pub fn mean(v: &Vec<f64>) -> f64 {
+pub fn mean(v: &[f64]) -> f64 {
     // vの合計 --> sum
     let sum = v.iter().fold(0.0, |total, e| total+e);
 
@@ -164,11 +729,39 @@ pub fn mean(v: &Vec<f64>) -> f64 {
 //
 // @return vの標準偏差
 // -----------------------------------------------
-pub fn stdev(v: &Vec<f64>) -> f64 {
+pub fn stdev(v: &[f64]) -> f64 {
     let v2: Vec<f64> = v.iter().map(|x| (x-mean(v)).powf(2.0)).collect();
     mean(&v2).sqrt()
 }
 
+// -----------------------------------------------
+// 分位点を求める（隣り合う値の間は線形補間）
+//
+// @param v 数値ベクトル
+// @param q 0以上1以下（0.5なら中央値）
+//
+// @return vのq分位点．欠損値(NaN)は除いて求め、値がなければNaN
+// -----------------------------------------------
+pub fn quantile(v: &[f64], q: f64) -> f64 {
+    let mut sorted: Vec<f64> = v.iter().cloned().filter(|e| !e.is_nan()).collect();
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    sorted.sort_by(|a, b| a.total_cmp(b));
+
+    let pos = q * ((sorted.len() - 1) as f64);
+    let lo = pos.floor() as usize;
+    let hi = pos.ceil() as usize;
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - (lo as f64))
+}
+
+// -----------------------------------------------
+// 中央値を求める
+// -----------------------------------------------
+pub fn median(v: &[f64]) -> f64 {
+    quantile(v, 0.5)
+}
+
 // -----------------------------------------------
 //  最大、最小型
 // -----------------------------------------------
@@ -180,17 +773,20 @@ pub struct MinMax {
 // -----------------------------------------------
 // 最大値・最小値を求める
 //
-// @param v 数値ベクトル
+// @param v 数値ベクトル（欠損値(NaN)は除いて求める）
 //
-// @return vの最大値、最小値
+// @return vの最大値、最小値．空（全て欠損値）ならどちらもNaN
 // -----------------------------------------------
-pub fn calcMinMax(v: &Vec<f64>) -> MinMax {
-    let mut min: f64 = v[0];
-    let mut max: f64 = v[0];
-    let n = v.len();
+pub fn calcMinMax(v: &[f64]) -> MinMax {
+    let mut present = v.iter().cloned().filter(|e| !e.is_nan());
+    let first = match present.next() {
+        Some(e) => e,
+        None => return MinMax { min: f64::NAN, max: f64::NAN }
+    };
+    let mut min: f64 = first;
+    let mut max: f64 = first;
 
-    for i in 1..n {
-        let e: f64 = v[i];
+    for e in present {
         if e < min {
             min = e;
         }
@@ -200,8 +796,8 @@ pub fn calcMinMax(v: &Vec<f64>) -> MinMax {
     }
 
     MinMax {
-        min: min,
-        max: max
+        min,
+        max
     }
 }
 
@@ -222,9 +818,7 @@ pub type Matrix = Vec<Vec<f64>>;
 pub fn MatSelectRow(m: &Matrix, irows: &[usize]) -> Matrix {
     let mut result: Matrix = vec![];
 
-    let ncol = m.len();
-    for i in 0..ncol {
-        let col: &Vec<f64> = &m[i];
+    for col in m.iter() {
         let mut newCol: Vec<f64> = vec![];
         for irow in irows {
             newCol.push(col[*irow]);
@@ -235,17 +829,204 @@ pub fn MatSelectRow(m: &Matrix, irows: &[usize]) -> Matrix {
     result
 }
 
-// -----------------------------------------------
-//  Matrix型の変数の内容を表示
-// -----------------------------------------------
-pub fn MatPrint(m: &Matrix) {
-    let ncol = m.len();
-    let nrow = m[0].len();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantileInterpolatesAndSkipsNaN() {
+        let v = [4.0, f64::NAN, 1.0, 3.0, 2.0];
+        assert_eq!(quantile(&v, 0.0), 1.0);
+        assert_eq!(quantile(&v, 1.0), 4.0);
+        assert_eq!(median(&v), 2.5);
+        assert_eq!(quantile(&v, 0.25), 1.75);
+        assert!(quantile(&[], 0.5).is_nan());
+        assert!(quantile(&[f64::NAN], 0.5).is_nan());
+    }
+
+    #[test]
+    fn minMaxSkipsNaN() {
+        let mm = calcMinMax(&[f64::NAN, 3.0, -1.0, f64::NAN, 2.0]);
+        assert_eq!((mm.min, mm.max), (-1.0, 3.0));
+        let empty = calcMinMax(&[]);
+        assert!(empty.min.is_nan() && empty.max.is_nan());
+        assert!(calcMinMax(&[f64::NAN]).min.is_nan());
+    }
+
+    // テスト用のCSVファイルを一時ディレクトリに書く（テストごとに別の名前にする）
+    fn writeTemp(name: &str, text: &str) -> String {
+        let path = std::env::temp_dir().join(format!("ex1-test-{}-{}.csv", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn readText(csv: CSV, name: &str, text: &str) -> Result<CSV, CSVError> {
+        let mut csv = csv;
+        let path = writeTemp(name, text);
+        let result = csv.read(&path);
+        std::fs::remove_file(&path).unwrap();
+        result.map(|_| csv)
+    }
+
+    #[test]
+    fn parseQuotedFields() {
+        let text = "a,b,c\r\n\"x, y\",\"he said \"\"hi\"\"\",  3 \n\n\"multi\nline\",2,\"\"\n";
+        let records = parseRecords(text, ',', None);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0], (1, vec![String::from("a"), String::from("b"), String::from("c")]));
+        assert_eq!(records[1].1, vec!["x, y", "he said \"hi\"", "3"]);
+        assert_eq!(records[2], (4, vec![String::from("multi\nline"), String::from("2"), String::new()]));
+    }
+
+    #[test]
+    fn detectsDelimiter() {
+        assert_eq!(detectDelimiter("# a,b,c\nx;y;z\n", Some('#')), ';');
+        assert_eq!(detectDelimiter("\"a;b\"\tc\n", None), '\t');
+        assert_eq!(detectDelimiter("abc\n", None), ',');
+    }
+
+    #[test]
+    fn readWithoutHeader() {
+        let csv = readText(CSV::new().header(false).delimiter('|').comment('#'), "noheader", "# comment\n1|2\n3|4\n").unwrap();
+        assert_eq!(csv.colNames, vec!["col0", "col1"]);
+        assert_eq!(csv.cols, vec![vec![1.0, 3.0], vec![2.0, 4.0]]);
+        assert_eq!((csv.nrows, csv.ncols), (2, 2));
+    }
 
-    for irow in 0..nrow {
-        for jcol in 0..ncol {
-            print!("{}, ", m[jcol][irow]);
+    #[test]
+    fn readSelectedColumns() {
+        let csv = readText(CSV::new().columns(&[Col::Name(String::from("c")), Col::Index(0)]), "select", "a;b;c\n1;2;3\n4;5;6").unwrap();
+        assert_eq!(csv.colNames, vec!["c", "a"]);
+        assert_eq!(csv.cols, vec![vec![3.0, 6.0], vec![1.0, 4.0]]);
+    }
+
+    #[test]
+    fn raggedRowReportsLine() {
+        // クォート内の改行、コメント行も行番号に数える
+        let text = "\"a\nx\",b\n# note\n1,3\n4,5,6\n";
+        match readText(CSV::new().comment('#'), "ragged", text) {
+            Err(e @ CSVError::Ragged { .. }) => {
+                assert!(matches!(e, CSVError::Ragged { line: 5, expected: 2, found: 3 }));
+                assert_eq!(e.to_string(), "line 5: expected 2 fields but found 3");
+            },
+            other => panic!("unexpected {:?}", other.map(|c| c.colNames))
+        }
+    }
+
+    #[test]
+    fn parseErrorReportsLineAndColumn() {
+        let text = "x,y\n1,2\n3,abc\n";
+        match readText(CSV::new(), "parse", text) {
+            Err(e @ CSVError::Parse { .. }) => {
+                assert_eq!(e.to_string(), "line 3, column \"y\": cannot parse \"abc\" as a number");
+            },
+            other => panic!("unexpected {:?}", other.map(|c| c.colNames))
         }
-        println!("");
+    }
+
+    #[test]
+    fn chunkErrorsReportLine() {
+        let path = writeTemp("chunkerr", "a,b\n1,2\n3,4\n5,x\n");
+        let chunks: Vec<Result<CSV, CSVError>> = CSV::new().chunks(&path, 2).unwrap().collect();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].as_ref().unwrap().cols, vec![vec![1.0, 3.0], vec![2.0, 4.0]]);
+        assert!(matches!(chunks[1], Err(CSVError::Parse { line: 4, .. })));
+    }
+
+    #[test]
+    fn missingFileAndUnknownColumn() {
+        let mut csv = CSV::new();
+        assert!(matches!(csv.read("/nonexistent/ex1-test.csv"), Err(CSVError::Io(_))));
+        let e = readText(CSV::new().columns(&[Col::Index(5)]), "unknown", "a,b\n1,2\n").err().unwrap();
+        assert_eq!(e.to_string(), "unknown column #5");
+    }
+
+    #[test]
+    fn missingValuesReadAsNaN() {
+        let csv = readText(CSV::new(), "na", "a,b\n1,NA\n,2\n?,null\n").unwrap();
+        assert_eq!(csv.kinds, vec![ColKind::Numeric, ColKind::Numeric]);
+        assert_eq!(csv.cols[0][0], 1.0);
+        assert!(csv.cols[0][1].is_nan() && csv.cols[0][2].is_nan());
+        assert!(csv.cols[1][0].is_nan() && csv.cols[1][2].is_nan());
+
+        // 欠損値の指定を変えると"NA"は数値として読めない
+        let csv = readText(CSV::new().naValues(&["-"]), "na2", "a,b\n1,NA\n-,2\n");
+        assert!(matches!(csv, Err(CSVError::Parse { line: 2, .. })));
+    }
+
+    #[test]
+    fn categoricalOnlyWhenMostCellsAreText() {
+        // 過半数が数値として読めない列はカテゴリ列
+        let csv = readText(CSV::new(), "kind1", "a,color\n1,red\n2,NA\n3,white\n4,7\n").unwrap();
+        assert_eq!(csv.kinds, vec![ColKind::Numeric, ColKind::Categorical]);
+        assert_eq!(csv.levels[1], vec!["7", "red", "white"]);
+
+        // 数値の列に紛れ込んだ文字列は誤りとして報告する．指定すればカテゴリ列として読める
+        let text = "a,b\n1,2\n2,abc\n3,4\n";
+        assert!(matches!(readText(CSV::new(), "kind2", text), Err(CSVError::Parse { line: 3, .. })));
+        let csv = readText(CSV::new().columnType(Col::Name(String::from("b")), ColKind::Categorical), "kind3", text).unwrap();
+        assert_eq!(csv.kinds[1], ColKind::Categorical);
+    }
+
+    #[test]
+    fn categoricalLevelsAreSorted() {
+        let text = "size,grade,color,note\n10,b,red,x y\n9,a,,hello\n2,c,blue,x y\n10,a,red,\"z\"\n";
+        let csv = readText(CSV::new().columnType(Col::Name(String::from("size")), ColKind::Categorical)
+            .columnType(Col::Index(3), ColKind::Text), "levels", text).unwrap();
+        assert_eq!(csv.kinds, vec![ColKind::Categorical, ColKind::Categorical, ColKind::Categorical, ColKind::Text]);
+
+        // 全て数値の水準は数値の順（文字列の順なら"10" < "2" < "9"）
+        assert_eq!(csv.levels[0], vec!["2", "9", "10"]);
+        assert_eq!(csv.cols[0], vec![2.0, 1.0, 0.0, 2.0]);
+        assert_eq!(csv.levels[1], vec!["a", "b", "c"]);
+        assert_eq!(csv.cols[1], vec![1.0, 0.0, 2.0, 0.0]);
+
+        // 欠損値は水準に含めない
+        assert_eq!(csv.levels[2], vec!["blue", "red"]);
+        assert!(csv.cols[2][1].is_nan());
+
+        // テキスト列は値を持たずに内容だけ残す
+        assert_eq!(csv.texts[3], vec!["x y", "hello", "x y", "z"]);
+        assert!(csv.cols[3].iter().all(|v| v.is_nan()));
+        assert!(csv.levels[3].is_empty());
+    }
+
+    #[test]
+    fn columnSpecFromStr() {
+        assert!(matches!("3".parse::<Col>(), Ok(Col::Index(3))));
+        assert!(matches!(" quality ".parse::<Col>(), Ok(Col::Name(ref n)) if n == "quality"));
+        assert!(matches!("=2020".parse::<Col>(), Ok(Col::Name(ref n)) if n == "2020"));
+    }
+
+    #[test]
+    fn splitByNames() {
+        let csv = readText(CSV::new(), "split", "2019,2020,color,y\n1,2,red,3\n4,5,blue,6\n").unwrap();
+        let name = |s: &str| s.parse::<Col>().unwrap();
+
+        // 目的変数以外の全ての列から、dropの列を除く
+        let (x, y) = csv.splitXY(&[name("y")], None, &[name("=2019")]).unwrap();
+        assert_eq!(x.colNames, vec!["2020", "color"]);
+        assert_eq!(x.kinds, vec![ColKind::Numeric, ColKind::Categorical]);
+        assert_eq!(x.levels[1], vec!["blue", "red"]);
+        assert_eq!(y.cols, vec![vec![3.0, 6.0]]);
+
+        // 説明変数を指定した場合も目的変数の列は除く
+        let (x, y) = csv.splitXY(&[name("3"), name("0")], Some(&[name("0"), name("=2020")]), &[]).unwrap();
+        assert_eq!(x.colNames, vec!["2020"]);
+        assert_eq!(y.colNames, vec!["y", "2019"]);
+
+        assert!(matches!(csv.splitXY(&[name("missing")], None, &[]), Err(CSVError::UnknownColumn(ref c)) if c == "missing"));
+        assert!(matches!(csv.splitXY(&[name("9")], None, &[]), Err(CSVError::UnknownColumn(ref c)) if c == "#9"));
+    }
+
+    #[test]
+    fn selectColumnsKeepsColumnKinds() {
+        let csv = readText(CSV::new(), "partial", "a,b,c\n1,x,2\n3,y,4\n").unwrap();
+        let part = csv.selectColumns(&[2, 1]);
+        assert_eq!(part.colNames, vec!["c", "b"]);
+        assert_eq!(part.kinds, vec![ColKind::Numeric, ColKind::Categorical]);
+        assert_eq!(part.levels[1], vec!["x", "y"]);
+        assert_eq!((part.nrows, part.ncols), (2, 2));
     }
 }
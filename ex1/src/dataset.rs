@@ -0,0 +1,255 @@
+// ******************************************************************
+//  データセット
+//  値を列優先で1本の連続した配列に持ち、行番号、列番号の集合(View)で
+//  一部の行、列を値をコピーせずに参照する（決定木のノードごとに行列を作り直さないため）
+// ******************************************************************
+
+#![allow(non_snake_case)]
+
+use super::U;   // main.rsのコメントを参照
+
+// =================================================
+//  形の誤り
+// =================================================
+#[derive(Debug)]
+pub enum ShapeError {
+    Ragged { column: usize, expected: usize, found: usize },    // 列によって行数が違う
+    Names { expected: usize, found: usize },    // 列名の数が列数と違う
+    Rows { x: usize, y: usize },                // 説明変数と目的変数の行数が違う
+    Columns { expected: usize, found: usize },  // 列数が学習済みのモデルと違う
+    Weights { expected: usize, found: usize }   // 行ごとの重みの数が行数と違う
+}
+
+impl std::fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ShapeError::Ragged { column, expected, found } =>
+                write!(f, "column {} has {} rows but expected {}", column, found, expected),
+            ShapeError::Names { expected, found } =>
+                write!(f, "{} column names for {} columns", found, expected),
+            ShapeError::Rows { x, y } =>
+                write!(f, "explanatory variables have {} rows but target has {}", x, y),
+            ShapeError::Columns { expected, found } =>
+                write!(f, "{} columns but expected {}", found, expected),
+            ShapeError::Weights { expected, found } =>
+                write!(f, "{} weights for {} rows", found, expected)
+        }
+    }
+}
+
+impl std::error::Error for ShapeError {}
+
+// =================================================
+//  データセット（列優先の連続した配列と列名）
+// =================================================
+pub struct Dataset {
+    data: Vec<f64>,     // data[jcol * nrows + irow]
+    nrows: usize,
+    ncols: usize,
+    names: Vec<String>  // 列名（指定しなければ"x1", "x2", ...）
+}
+
+impl Dataset {
+    // ==========================================================
+    //  行列(Matrix[jcol][irow])からデータセットを作る
+    //
+    //  @return 列によって行数が違えばShapeError::Ragged
+    // ==========================================================
+    pub fn fromMatrix(m: &U::Matrix) -> Result<Self, ShapeError> {
+        let ncols = m.len();
+        let nrows = if ncols > 0 { m[0].len() } else { 0 };
+
+        let mut data = Vec::with_capacity(nrows * ncols);
+        for (j, col) in m.iter().enumerate() {
+            if col.len() != nrows {
+                return Err(ShapeError::Ragged { column: j, expected: nrows, found: col.len() });
+            }
+            data.extend_from_slice(col);
+        }
+
+        Ok(Dataset {
+            data,
+            nrows,
+            ncols,
+            names: (1..=ncols).map(|i| format!("x{}", i)).collect()
+        })
+    }
+
+    // 列名を付ける
+    // (ex) let ds = Dataset::fromMatrix(&x)?.names(&names)?;
+    pub fn names(mut self, names: &[String]) -> Result<Self, ShapeError> {
+        if names.len() != self.ncols {
+            return Err(ShapeError::Names { expected: self.ncols, found: names.len() });
+        }
+        self.names = names.to_vec();
+        Ok(self)
+    }
+
+    // j列目の値（全ての行）
+    pub fn col(&self, j: usize) -> &[f64] {
+        &self.data[j * self.nrows..(j + 1) * self.nrows]
+    }
+
+    // 全ての行、列のビュー
+    pub fn view(&self) -> View<'_> {
+        View {
+            data: self,
+            rows: (0..self.nrows).collect(),
+            cols: (0..self.ncols).collect()
+        }
+    }
+}
+
+// =================================================
+//  データセットの一部の行、列のビュー
+//  値は持たず、元のデータセットの行番号、列番号だけを持つ
+// =================================================
+#[derive(Clone)]
+pub struct View<'a> {
+    data: &'a Dataset,
+    rows: Vec<usize>,   // 元のデータセットの行番号
+    cols: Vec<usize>    // 元のデータセットの列番号
+}
+
+impl<'a> View<'a> {
+    pub fn nrows(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn ncols(&self) -> usize {
+        self.cols.len()
+    }
+
+    pub fn colNames(&self) -> Vec<String> {
+        self.cols.iter().map(|j| self.data.names[*j].clone()).collect()
+    }
+
+    // このビューのirow行目、j列目の値
+    pub fn get(&self, irow: usize, j: usize) -> f64 {
+        self.data.col(self.cols[j])[self.rows[irow]]
+    }
+
+    // ==========================================================
+    //  j列目の値を、このビューの行の順にたどる（値はコピーしない）
+    //  (ex) let mean = x.columnIter(j).sum::<f64>() / (x.nrows() as f64);
+    // ==========================================================
+    pub fn columnIter(&self, j: usize) -> impl Iterator<Item = f64> + Clone + '_ {
+        let col = self.data.col(self.cols[j]);
+        self.rows.iter().map(move |i| col[*i])
+    }
+
+    // ==========================================================
+    //  このビューの一部の行のビューを作る（値はコピーしない）
+    //
+    //  @param irows このビューでの行番号
+    // ==========================================================
+    pub fn select(&self, irows: &[usize]) -> View<'a> {
+        View {
+            data: self.data,
+            rows: irows.iter().map(|i| self.rows[*i]).collect(),
+            cols: self.cols.clone()
+        }
+    }
+
+    // ==========================================================
+    //  このビューの一部の列のビューを作る（値はコピーしない）
+    //
+    //  @param jcols このビューでの列番号
+    // ==========================================================
+    pub fn selectColumns(&self, jcols: &[usize]) -> View<'a> {
+        View {
+            data: self.data,
+            rows: self.rows.clone(),
+            cols: jcols.iter().map(|j| self.cols[*j]).collect()
+        }
+    }
+
+    // 行列(Matrix[jcol][irow])にする（値をコピーする．ビューを扱えないモデルに渡す場合）
+    pub fn toMatrix(&self) -> U::Matrix {
+        (0..self.ncols()).map(|j| self.columnIter(j).collect()).collect()
+    }
+}
+
+// -----------------------------------------------
+// 説明変数と目的変数の行数が同じか確かめる
+// -----------------------------------------------
+pub fn checkRows(x: &View, y: &View) -> Result<(), ShapeError> {
+    if x.nrows() != y.nrows() {
+        return Err(ShapeError::Rows { x: x.nrows(), y: y.nrows() });
+    }
+    Ok(())
+}
+
+// -----------------------------------------------
+// 行ごとの重みの数がビューの行数と同じか確かめる（重みがなければ何もしない）
+// -----------------------------------------------
+pub fn checkWeights(x: &View, weights: Option<&[f64]>) -> Result<(), ShapeError> {
+    match weights {
+        Some(w) if w.len() != x.nrows() => Err(ShapeError::Weights { expected: x.nrows(), found: w.len() }),
+        _ => Ok(())
+    }
+}
+
+// -----------------------------------------------
+// 説明変数、目的変数の行列からデータセットを作る（行列を受け取るfitなどから使う）
+//
+// @return (説明変数, 目的変数)．列の長さが揃っていなければShapeError::Ragged,
+//         説明変数と目的変数の行数が違えばShapeError::Rows
+// -----------------------------------------------
+pub fn fromMatrices(x: &U::Matrix, y: &U::Matrix) -> Result<(Dataset, Dataset), ShapeError> {
+    let xd = Dataset::fromMatrix(x)?;
+    let yd = Dataset::fromMatrix(y)?;
+    checkRows(&xd.view(), &yd.view())?;
+    Ok((xd, yd))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fromMatrixChecksShape() {
+        let ragged: U::Matrix = vec![vec![1.0, 2.0], vec![3.0]];
+        assert!(matches!(Dataset::fromMatrix(&ragged), Err(ShapeError::Ragged { column: 1, expected: 2, found: 1 })));
+
+        let m: U::Matrix = vec![vec![1.0, 2.0], vec![3.0, 4.0]];
+        let ds = Dataset::fromMatrix(&m).unwrap();
+        assert_eq!(ds.view().colNames(), vec!["x1", "x2"]);
+        assert_eq!(ds.col(1), &[3.0, 4.0]);
+        assert!(matches!(ds.names(&[String::from("a")]), Err(ShapeError::Names { expected: 2, found: 1 })));
+
+        let y: U::Matrix = vec![vec![1.0, 2.0, 3.0]];
+        assert!(matches!(fromMatrices(&m, &y), Err(ShapeError::Rows { x: 2, y: 3 })));
+    }
+
+    #[test]
+    fn viewsFollowOriginalRowsAndColumns() {
+        let m: U::Matrix = vec![vec![0.0, 1.0, 2.0, 3.0], vec![10.0, 11.0, 12.0, 13.0], vec![20.0, 21.0, 22.0, 23.0]];
+        let names: Vec<String> = vec![String::from("a"), String::from("b"), String::from("c")];
+        let ds = Dataset::fromMatrix(&m).unwrap().names(&names).unwrap();
+
+        // 行、列を絞ったビューをさらに絞ると、元の行番号、列番号をたどる
+        let v = ds.view().select(&[3, 1, 0]).selectColumns(&[2, 0]);
+        assert_eq!((v.nrows(), v.ncols()), (3, 2));
+        assert_eq!(v.colNames(), vec!["c", "a"]);
+        assert_eq!(v.get(0, 0), 23.0);
+        let w = v.select(&[2, 0]).selectColumns(&[1]);
+        assert_eq!(w.columnIter(0).collect::<Vec<f64>>(), vec![0.0, 3.0]);
+        assert_eq!(w.toMatrix(), vec![vec![0.0, 3.0]]);
+
+        // 列を全て除いても行数は残る
+        assert_eq!(v.selectColumns(&[]).nrows(), 3);
+    }
+
+    #[test]
+    fn checkRowsComparesViews() {
+        let x: U::Matrix = vec![vec![1.0, 2.0, 3.0]];
+        let y: U::Matrix = vec![vec![1.0, 2.0]];
+        let xd = Dataset::fromMatrix(&x).unwrap();
+        let yd = Dataset::fromMatrix(&y).unwrap();
+        let e = checkRows(&xd.view(), &yd.view()).err().unwrap();
+        assert_eq!(e.to_string(), "explanatory variables have 3 rows but target has 2");
+        assert!(checkRows(&xd.view().select(&[0, 2]), &yd.view()).is_ok());
+        assert!(matches!(checkWeights(&yd.view(), Some(&[1.0])), Err(ShapeError::Weights { expected: 2, found: 1 })));
+    }
+}
@@ -7,51 +7,196 @@
 #![allow(non_snake_case)]
 
 use super::U;
-use super::zeror;
 use super::linear;
+use super::logistic;
+use super::dataset;
+use super::dataset::{Dataset, ShapeError, View};
+use super::parallel;
+use super::random;
+use std::borrow::Cow;
 
-enum Model {
-    Zeror(zeror::ZeroRule),
-    Linear(linear::Linear)
-}
+// メトリック関数の型定義（目的変数のビュー、その中で対象にする行の行番号、目的変数ごとの根ノードの分散）
+type Metric = fn(&View, &[usize], &[f64]) -> f64;
+
+// 行ごとの重み付きのメトリック関数の型定義（重みはビューの行番号で引く）
+type WeightedMetric = fn(&View, &[usize], &[f64], &[f64]) -> f64;
 
-// メトリック関数の型定義
-type Metric = fn(&U::Matrix) -> f64;
+// yの1列目（クラスラベル）のrows行について、クラスごとの重みの合計（重みがなければ行数．ラベルの昇順）
+fn classTotals(y: &View, rows: &[usize], w: Option<&[f64]>) -> Vec<f64> {
+    let mut totals: Vec<(f64, f64)> = vec![];   // (ラベル, 重みの合計)
+    for i in rows {
+        let label = y.get(*i, 0);
+        let we = w.map_or(1.0, |w| w[*i]);
+        match totals.iter_mut().find(|(l, _)| *l == label) {
+            Some(t) => t.1 += we,
+            None => totals.push((label, we))
+        }
+    }
+    totals.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));   // ラベルの昇順（metrics::classLabelsと同じ）
+    totals.into_iter().map(|(_, t)| t).collect()
+}
 
 // =====================================================
 //  gini impurity（ジニ不純物）を計算
 //
-//  @param y カテゴリ変数を水準ごとの確率値で表した行列
-//  (ex) irisの場合：
-//  y = array([[1., 0., 0.],
-// 		    [1., 0., 0.],
-// 		    [1., 0., 0.],
-// 		    [1., 0., 0.],
-//  			...
-// 			[0., 1., 0.],
-// 	        [0., 0., 1.]])
+//  @param y クラスラベル(1列)
+//  @param rows 対象にする行（yでの行番号）
+//  @param _rootVariances 使わない（deviationとメトリック関数の型を揃えるため）
 //
 //  @return ジニ不純物値
 // =====================================================
-pub fn gini(y: &U::Matrix) -> f64 {
-    let size = y[0].len();  // 行数
-
-    // yの各水準の割合 --> e
-    let e = y.iter()    // 列でループ
-        .map(|col| col.iter().sum())    // 各列の合計を取る
-        .map(|x: f64| (x/(size as f64)).powf(2.0))  // 行数で割ると水準の確率になる．さらにそれを2乗
-        .sum::<f64>();  // 各列の水準の確率の二乗和
-    
+pub fn gini(y: &View, rows: &[usize], _rootVariances: &[f64]) -> f64 {
+    let size = rows.len();  // 行数
+
+    // yの各クラスの割合の二乗和 --> e
+    let e = classTotals(y, rows, None).iter()   // クラスごとの行数
+        .map(|n| (n/(size as f64)).powf(2.0))   // 行数で割るとクラスの確率になる．さらにそれを2乗
+        .sum::<f64>();
+
+    1.0 - e
+}
+
+// =====================================================
+//  重み付きジニ不純物を計算（giniの重み付き版）
+//  クラスの割合を行数の代わりに重みの合計で求める
+//
+//  @param y クラスラベル(1列)
+//  @param rows 対象にする行（yでの行番号）
+//  @param _rootVariances 使わない
+//  @param w 行ごとの重み（yでの行番号で引く）
+// =====================================================
+pub fn weightedGini(y: &View, rows: &[usize], _rootVariances: &[f64], w: &[f64]) -> f64 {
+    let total: f64 = rows.iter().map(|i| w[*i]).sum();
+
+    let e = classTotals(y, rows, Some(w)).iter()    // クラスごとの重みの合計
+        .map(|n| (n/total).powf(2.0))
+        .sum::<f64>();
+
     1.0 - e
 }
 
+// yのj列目のrows行の分散（値はコピーせずにビューから読む．計算はU::stdevと同じ）
+fn variance(y: &View, rows: &[usize], j: usize) -> f64 {
+    let n = rows.len() as f64;
+    let mean = rows.iter().fold(0.0, |total, i| total + y.get(*i, j)) / n;
+    rows.iter().fold(0.0, |total, i| total + (y.get(*i, j) - mean).powf(2.0)) / n
+}
+
+// yのj列目のrows行の重み付き分散（重みwはyでの行番号で引く）
+fn weightedVariance(y: &View, rows: &[usize], j: usize, w: &[f64]) -> f64 {
+    let wsum = rows.iter().map(|i| w[*i]).sum::<f64>();
+    let mean = rows.iter().fold(0.0, |total, i| total + y.get(*i, j) * w[*i]) / wsum;
+    rows.iter().fold(0.0, |total, i| total + (y.get(*i, j) - mean).powf(2.0) * w[*i]) / wsum
+}
+
 // ==============================================
 //  標準偏差を計算
 //
-//  y ... 数値列(1列)
+//  y ... 数値列．複数列（多出力回帰）の場合は、列ごとの分散をその列の根ノードの分散で割ってから
+//        足した和の平方根（単位や大きさの違う目的変数を同じ重さで扱うため）
+//  rows ... 対象にする行（yでの行番号）
+//  rootVariances ... 目的変数ごとの根ノードの分散（1列の場合は使わない）
+// ==============================================
+pub fn deviation(y: &View, rows: &[usize], rootVariances: &[f64]) -> f64 {
+    if y.ncols() == 1 {
+        variance(y, rows, 0).sqrt()
+    } else {
+        (0..y.ncols()).map(|j| variance(y, rows, j) / rootVariances[j]).sum::<f64>().sqrt()
+    }
+}
+
 // ==============================================
-pub fn deviation(y: &U::Matrix) -> f64 {
-    U::stdev(&y[0])
+//  重み付き標準偏差を計算（deviationの重み付き版）
+//
+//  y ... 数値列
+//  rows ... 対象にする行（yでの行番号）
+//  rootVariances ... 目的変数ごとの根ノードの（重み付き）分散
+//  w ... 行ごとの重み（yでの行番号で引く）
+// ==============================================
+pub fn weightedDeviation(y: &View, rows: &[usize], rootVariances: &[f64], w: &[f64]) -> f64 {
+    if y.ncols() == 1 {
+        weightedVariance(y, rows, 0, w).sqrt()
+    } else {
+        (0..y.ncols()).map(|j| weightedVariance(y, rows, j, w) / rootVariances[j]).sum::<f64>().sqrt()
+    }
+}
+
+// =================================================
+//  葉のモデルに渡す説明変数のビュー
+//  カテゴリ列（水準の番号）は大小に意味がないので回帰の説明変数には使わない
+//  全ての列がカテゴリ列なら列のないビューになる（切片だけのモデルになる）
+//
+//  categorical ... カテゴリ列（の列番号）
+// =================================================
+fn leafView<'v, 'a>(categorical: &[usize], x: &'v View<'a>) -> Cow<'v, View<'a>> {
+    if categorical.is_empty() {
+        return Cow::Borrowed(x);
+    }
+    let cols: Vec<usize> = (0..x.ncols()).filter(|j| !categorical.contains(j)).collect();
+    Cow::Owned(x.selectColumns(&cols))
+}
+
+// =================================================
+//  葉のモデルの種類
+// =================================================
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LeafKind {
+    Linear,     // 線形回帰（回帰木．分割は標準偏差で評価）
+    Logistic    // ロジスティック回帰（分類木．目的変数はクラスラベル、分割はジニ不純物で評価）
+}
+
+// 文字列から葉の種類を作る（コマンドラインオプション用）: "linear", "logistic"
+impl std::str::FromStr for LeafKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(LeafKind::Linear),
+            "logistic" => Ok(LeafKind::Logistic),
+            _ => Err(())
+        }
+    }
+}
+
+// =================================================
+//  葉のモデル
+// =================================================
+enum LeafModel {
+    Linear(Box<linear::Linear>),
+    Logistic(logistic::Logistic)
+}
+
+impl LeafModel {
+    fn new(kind: LeafKind, solver: linear::Solver) -> Self {
+        match kind {
+            LeafKind::Linear => LeafModel::Linear(Box::new(linear::Linear::new().solver(solver))),
+            LeafKind::Logistic => LeafModel::Logistic(logistic::Logistic::new())
+        }
+    }
+
+    // weights: 行ごとの重み（Noneなら重みなし）
+    fn fitView(&mut self, x: &View, y: &View, weights: Option<&[f64]>) -> Result<(), ShapeError> {
+        match self {
+            LeafModel::Linear(l) => l.fitView(x, y, weights),
+            LeafModel::Logistic(g) => {
+                // ロジスティック回帰は行列で学習する（葉に入った行の値だけをコピーする）
+                let (xm, ym) = (x.toMatrix(), y.toMatrix());
+                match weights {
+                    Some(w) => g.fitWeighted(&xm, &ym, w),
+                    None => g.fit(&xm, &ym)
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // 目的変数ごとの予測値（ロジスティック回帰の場合はクラスラベルの1列）
+    fn predictView(&self, x: &View) -> U::Matrix {
+        match self {
+            LeafModel::Linear(l) => l.predictView(x),
+            LeafModel::Logistic(g) => vec![g.predictView(x)]
+        }
+    }
 }
 
 // =================================================
@@ -59,38 +204,143 @@ pub fn deviation(y: &U::Matrix) -> f64 {
 // =================================================
 pub struct DecisionTree {
     metric: Metric,
-    // leaf: Model,
+    weightedMetric: WeightedMetric,     // fitWeightedの時に使うメトリック
     left: NodeType,
     right: NodeType,
     feat_index: usize,
     feat_val: f64,
     score: f64,
     depth: u32,
-    max_depth: u32
+    max_depth: u32,
+    leafSolver: linear::Solver, // 葉（線形モデル）の回帰係数の求め方
+    leafKind: LeafKind,         // 葉のモデルの種類
+    rootVariances: Vec<f64>,    // 目的変数ごとの根ノードの分散（多出力回帰の損失の正規化用．fitで決め、子ノードに引き継ぐ）
+    missingLeft: bool,          // 分割対象変数が欠損値(NaN)の行を左側の枝に入れるか（fitで決める）
+    categorical: Vec<usize>,    // 水準の番号を持つカテゴリ列（の列番号）．水準の集合で分割する
+    leftLevels: Option<Vec<f64>>,   // カテゴリ列で分割する場合、左側の枝に入れる水準（fitで決める）
+    threads: usize,             // 分割探索に使うスレッド数
+    maxFeatures: Option<usize>, // ノードごとに分割を探す列の数（Noneなら全ての列）
+    randomThresholds: bool,     // 列ごとに基準値を1つだけ無作為に選ぶか(ExtraTrees)．falseなら全ての値を試す
+    seed: u64                   // 分割を探す列、基準値を選ぶ乱数のシード
 }
 
+// 1列分の最良の分割（split_treeで列ごとに求めて比べる）
+struct SplitCandidate {
+    loss: f64,
+    left: Vec<usize>,
+    right: Vec<usize>,
+    missingLeft: bool,
+    val: f64,                       // 分割の基準値（カテゴリ列ならNaN）
+    leftLevels: Option<Vec<f64>>    // カテゴリ列の場合、左に入れる水準
+}
+
+// 分割探索を複数のスレッドで行う最小の大きさ（行数x列数）．小さいノードはスレッドを作る方が遅い
+const PARALLEL_MIN_CELLS: usize = 2000;
+
 enum NodeType {
     Node(Box<DecisionTree>),    // 末端でない（分岐する）所
-    Leaf(Box<linear::Linear>)   // ツリーの末端
+    Leaf(Box<LeafModel>)        // ツリーの末端
 }
 
 impl DecisionTree {
     pub fn new(depth: u32, max_depth: u32) -> Self {
-        let l = linear::Linear::new();
-        let r = linear::Linear::new();
+        let l = LeafModel::new(LeafKind::Linear, linear::Solver::Sgd);
+        let r = LeafModel::new(LeafKind::Linear, linear::Solver::Sgd);
 
         DecisionTree {
             metric: deviation,
+            weightedMetric: weightedDeviation,
             left: NodeType::Leaf(Box::new(l)),
             right: NodeType::Leaf(Box::new(r)),
             feat_index: 0,
             feat_val: f64::NAN,
             score: f64::NAN,
-            depth: depth,
-            max_depth: max_depth
+            depth,
+            max_depth,
+            leafSolver: linear::Solver::Sgd,
+            leafKind: LeafKind::Linear,
+            rootVariances: vec![],
+            missingLeft: false,
+            categorical: vec![],
+            leftLevels: None,
+            threads: 1,
+            maxFeatures: None,
+            randomThresholds: false,
+            seed: 0
         }
     }
 
+    // ノードごとに列をk個だけ無作為に選び、その中で分割を探す（子ノードにも引き継がれる）
+    // 列の選び方はseedで決まり、子ノードはノードごとに派生させたシードを使う
+    // (ex) let d = dtree::DecisionTree::new(1, 3).maxFeatures(4).seed(1);
+    pub fn maxFeatures(mut self, k: usize) -> Self {
+        self.maxFeatures = Some(k.max(1));
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    // 列ごとに基準値を最小値と最大値の間から1つだけ無作為に選ぶ（子ノードにも引き継がれる）
+    // カテゴリ列は目的変数の平均の順で前から何個を左に入れるかを無作為に選ぶ
+    // 全ての値を試すより速く、木ごとに分割がばらつくのでアンサンブル(ensemble::Forest)に向く
+    // (ex) let d = dtree::DecisionTree::new(1, 3).randomThresholds(true).seed(1);
+    pub fn randomThresholds(mut self, randomThresholds: bool) -> Self {
+        self.randomThresholds = randomThresholds;
+        self
+    }
+
+    // 分割探索（列ごとの最良の分割）に使うスレッド数を指定する（子ノードにも引き継がれる）
+    // 結果はスレッド数によらない
+    // (ex) let d = dtree::DecisionTree::new(1, 3).threads(parallel::availableThreads());
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // 水準の番号(0, 1, 2, ...)を持つカテゴリ列を指定する（子ノードにも引き継がれる）
+    // 指定した列は大小ではなく水準の集合で分割する．水準を目的変数の平均の順に並べ、
+    // その順で前からk個を左に入れる分割を試す（CART．L個の水準でL-1通り）
+    // (ex) let d = dtree::DecisionTree::new(1, 3).categorical(&[11]);
+    pub fn categorical(mut self, cols: &[usize]) -> Self {
+        self.categorical = cols.to_vec();
+        self
+    }
+
+    // 葉の線形モデルの回帰係数の求め方を指定する（子ノードにも引き継がれる）
+    // 葉に入る行数が少ないとSGDでは収束しにくいので、Solver::Qrなどを指定する
+    // (ex) let d = dtree::DecisionTree::new(1, 3).leafSolver(linear::Solver::Qr);
+    pub fn leafSolver(mut self, solver: linear::Solver) -> Self {
+        self.leafSolver = solver;
+        self.resetLeaves();
+        self
+    }
+
+    // 葉のモデルの種類を指定する（子ノードにも引き継がれる）
+    // LeafKind::Logisticにすると分類木になる．目的変数の1列目をクラスラベルとして学習し、predictはクラスラベルを返す
+    // (ex) let d = dtree::DecisionTree::new(1, 3).leaf(dtree::LeafKind::Logistic);
+    pub fn leaf(mut self, kind: LeafKind) -> Self {
+        self.leafKind = kind;
+        self.metric = match kind {
+            LeafKind::Linear => deviation,
+            LeafKind::Logistic => gini
+        };
+        self.weightedMetric = match kind {
+            LeafKind::Linear => weightedDeviation,
+            LeafKind::Logistic => weightedGini
+        };
+        self.resetLeaves();
+        self
+    }
+
+    // 左右の葉を現在の設定の新しいモデルにする
+    fn resetLeaves(&mut self) {
+        self.left = NodeType::Leaf(Box::new(LeafModel::new(self.leafKind, self.leafSolver)));
+        self.right = NodeType::Leaf(Box::new(LeafModel::new(self.leafKind, self.leafSolver)));
+    }
+
     pub fn print(&self) {
         self.printSub(0);
     }
@@ -103,8 +353,14 @@ impl DecisionTree {
 
         println!("{}------------------------", s);
         println!("{}+feat_index: {}", s, self.feat_index);
-        println!("{} feat_val: {}", s, self.feat_val);
+        match self.leftLevels {
+            Some(ref levels) => println!("{} feat_levels: {:?}", s, levels),
+            None => println!("{} feat_val: {}", s, self.feat_val)
+        }
         println!("{} score: {}", s, self.score);
+        if self.missingLeft {
+            println!("{} missing: left", s);
+        }
         println!("{} depth: {}", s, self.depth);
 
         if let NodeType::Node(ref node) = self.left {
@@ -119,19 +375,44 @@ impl DecisionTree {
     // ============================================================
     // ベクトルを基準値より小さいものと、それ以外とに分ける
     //
-    // @param feat 数値ベクトル
+    // @param feat 数値ベクトル（の値をたどるイテレータ）
     // @param val 分割基準値
     //
     // @return (left, right)
     // left = valより小さい値
     // right = val以上の値
+    // 欠損値(NaN)はself.missingLeftがtrueなら左、falseなら右
+    // ============================================================
+    pub fn make_split<I: Iterator<Item = f64>>(&self, feat: I, val: f64) -> (Vec<usize>, Vec<usize>) {
+        let mut left = Vec::<usize>::new();
+        let mut right = Vec::<usize>::new();
+
+        for (i, v) in feat.enumerate() {
+            if v < val || (v.is_nan() && self.missingLeft) {
+                left.push(i);
+            } else {
+                right.push(i);
+            }
+        }
+
+        (left, right)
+    }
+
     // ============================================================
-    pub fn make_split(&self, feat: &Vec<f64>, val: f64) -> (Vec<usize>, Vec<usize>) {
+    // 水準の番号のベクトルを、指定の水準とそれ以外とに分ける
+    //
+    // @param feat 水準の番号のベクトル（の値をたどるイテレータ）
+    // @param levels 左に入れる水準の番号
+    //
+    // @return (left, right)
+    // 欠損値(NaN)はself.missingLeftがtrueなら左、falseなら右
+    // ============================================================
+    pub fn make_category_split<I: Iterator<Item = f64>>(&self, feat: I, levels: &[f64]) -> (Vec<usize>, Vec<usize>) {
         let mut left = Vec::<usize>::new();
         let mut right = Vec::<usize>::new();
 
-        for (i, v) in feat.iter().enumerate() {
-            if *v < val {
+        for (i, v) in feat.enumerate() {
+            if levels.contains(&v) || (v.is_nan() && self.missingLeft) {
                 left.push(i);
             } else {
                 right.push(i);
@@ -141,18 +422,61 @@ impl DecisionTree {
         (left, right)
     }
 
+    // このノードの分割（数値列なら基準値、カテゴリ列なら水準の集合）でビューの行を左右に分ける
+    fn splitRows(&self, x: &View) -> (Vec<usize>, Vec<usize>) {
+        let feat = x.columnIter(self.feat_index);
+        match self.leftLevels {
+            Some(ref levels) => self.make_category_split(feat, levels),
+            None => self.make_split(feat, self.feat_val)
+        }
+    }
+
+    // ============================================================
+    // カテゴリ列の水準を目的変数（1列目）の平均の昇順に並べる
+    //
+    // @param x 説明変数
+    // @param i カテゴリ列（の列番号）
+    // @param y 目的変数（1列目を使う）
+    // @param weights 行ごとの重み（Noneなら重みなし）
+    //
+    // @return 並べた水準の番号（欠損値は含まない）
+    // ============================================================
+    fn orderLevels(x: &View, i: usize, y: &View, weights: Option<&[f64]>) -> Vec<f64> {
+        let mut levels: Vec<f64> = x.columnIter(i).filter(|v| !v.is_nan()).collect();
+        levels.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        levels.dedup();
+
+        let means: Vec<f64> = levels.iter().map(|level| {
+            let mut sum = 0.0;
+            let mut count = 0.0;
+            for (irow, v) in x.columnIter(i).enumerate() {
+                if v == *level {
+                    let w = weights.map_or(1.0, |w| w[irow]);
+                    sum += w * y.get(irow, 0);
+                    count += w;
+                }
+            }
+            sum / count
+        }).collect();
+
+        let mut order: Vec<usize> = (0..levels.len()).collect();
+        order.sort_by(|a, b| means[*a].partial_cmp(&means[*b]).unwrap_or(std::cmp::Ordering::Equal));
+        order.iter().map(|k| levels[*k]).collect()
+    }
+
     // ============================================================
     // 損失関数
     //
     // @param self.metric メトリック関数
-    // @param y1
-    // @param y2
+    // @param y 目的変数
+    // @param y1 左に分けた行（yでの行番号）
+    // @param y2 右に分けた行（yでの行番号）
     //
     // @return 損失値
     // ============================================================
-    pub fn make_loss(&self, y1: &U::Matrix, y2: &U::Matrix) -> f64 {
-        let nrow_y1 = y1[0].len();
-        let nrow_y2 = y2[0].len();
+    pub fn make_loss(&self, y: &View, y1: &[usize], y2: &[usize]) -> f64 {
+        let nrow_y1 = y1.len();
+        let nrow_y2 = y2.len();
 
         if (nrow_y1 == 0) || (nrow_y2 == 0) {
             return f64::INFINITY;
@@ -161,8 +485,8 @@ impl DecisionTree {
         let f: Metric = self.metric;
 
         let nrow_total = nrow_y1 + nrow_y2;
-        let m1 = f(y1) * ((nrow_y1 as f64) / (nrow_total as f64));
-        let m2 = f(y2) * ((nrow_y2 as f64) / (nrow_total as f64));
+        let m1 = f(y, y1, &self.rootVariances) * ((nrow_y1 as f64) / (nrow_total as f64));
+        let m2 = f(y, y2, &self.rootVariances) * ((nrow_y2 as f64) / (nrow_total as f64));
 
         // println!("  y1={:?}", y1);
         // println!("  y2={:?}", y2);
@@ -173,12 +497,36 @@ impl DecisionTree {
         m1 + m2
     }
 
+    // ============================================================
+    // 損失関数（行ごとの重み付き）
+    // 左右の大きさの比は重みの合計の比にする
+    //
+    // @param y 目的変数
+    // @param y1, y2 左右に分けた行（yでの行番号）
+    // @param w 行ごとの重み（yでの行番号で引く）
+    //
+    // @return 損失値
+    // ============================================================
+    pub fn make_loss_weighted(&self, y: &View, y1: &[usize], y2: &[usize], w: &[f64]) -> f64 {
+        if y1.is_empty() || y2.is_empty() {
+            return f64::INFINITY;
+        }
+
+        let f: WeightedMetric = self.weightedMetric;
+
+        let sum1: f64 = y1.iter().map(|i| w[*i]).sum();
+        let sum2: f64 = y2.iter().map(|i| w[*i]).sum();
+        let total = sum1 + sum2;
+        f(y, y1, &self.rootVariances, w) * (sum1 / total) + f(y, y2, &self.rootVariances, w) * (sum2 / total)
+    }
+
     // ============================================================
     //  データを分割する列を決める．
     //  同時に分割基準値と、分割する際の行番号を求める
     //
     //  @param x 説明変数
     //  @param y 目的変数
+    //  @param weights 行ごとの重み（Noneなら重みなし．Someなら重み付きの損失関数で分割を決める）
     //
     //  @return (left, right)
     //  left .. 左側の枝に入れる行（の行番号）
@@ -188,13 +536,14 @@ impl DecisionTree {
     //  self.feat_val   分割の基準値
     //  self.score      最良の分割点のスコア
     // ============================================================
-    pub fn split_tree(&mut self, x: &U::Matrix, y: &U::Matrix) -> (Vec<usize>, Vec<usize>) {
+    pub fn split_tree(&mut self, x: &View, y: &View, weights: Option<&[f64]>) -> (Vec<usize>, Vec<usize>) {
         self.feat_index = 0;
         self.feat_val = f64::INFINITY;
+        self.missingLeft = false;   // 探索中の欠損値は右（make_split参照）
+        self.leftLevels = None;
 
-        // xの行数、列数を得る
-        let ncol = x.len();
-        let nrow = x[0].len();
+        // xの行数を得る
+        let nrow = x.nrows();
 
         // 左右のインデックス
         let mut left: Vec<usize> = (0..nrow).collect();
@@ -205,171 +554,546 @@ impl DecisionTree {
         // (2) self.feat_val : 分割の基準値
         // (3) left : 左側の枝に入れる行（の行番号）
         // (4) right : 右側の枝に入れる行（の行番号）
+        // 列ごとの最良の分割を（self.threads個のスレッドで分担して）求め、列の順に比べる
+        // 同じ損失なら列番号の小さい方を選ぶので、スレッド数によらず1列ずつ調べた場合と同じ結果になる
+        // maxFeaturesが指定されていれば、このノードのシードで選んだ列だけを調べる
+        let ncol = x.ncols();
+        let cols: Vec<usize> = match self.maxFeatures {
+            Some(k) if k < ncol => random::Rng::new(self.seed).choose(ncol, k),
+            _ => (0..ncol).collect()
+        };
+        let threads = if nrow * cols.len() < PARALLEL_MIN_CELLS { 1 } else { self.threads };
+        let candidates = parallel::map(cols.len(), threads, |k| self.bestSplitOf(x, y, cols[k], weights));
+
         let mut score = f64::INFINITY;
-        for i in 0..ncol {  // xの全列についてループ
-            let feat: &Vec<f64> = &x[i]; // i列目のベクトル
-            for val in feat.iter() {    // featの要素1個ずつについてループ
-                let (l, r) = self.make_split(feat, *val);   // featをvalで分割 --> (l, r)
-                let y1: U::Matrix = U::MatSelectRow(y, &l); // yのl行目を取り出す --> y1
-                let y2: U::Matrix = U::MatSelectRow(y, &r); // yのr行目を取り出す --> y2
-                let loss = self.make_loss(&y1, &y2);    // y1, y2から損失値を計算
-                // println!("-------------------");
-                // println!("i={}, val={}", i, val);
-                // println!("l={:?}", l);
-                // println!("r={:?}", r);
-                // println!("loss={}", loss);
-                if loss < score {   // 最小の損失値を探す
-                    score = loss;
-                    left = l;
-                    right = r;
+        let mut missingLeft = false;
+        let mut leftLevels = None;
+        for (i, c) in cols.into_iter().zip(candidates) {
+            if let Some(c) = c {
+                if c.loss < score {   // 最小の損失値を探す
+                    score = c.loss;
+                    left = c.left;
+                    right = c.right;
+                    missingLeft = c.missingLeft;
+                    leftLevels = c.leftLevels;
                     self.feat_index = i;
-                    self.feat_val = *val;
+                    self.feat_val = c.val;
                 }
             }
         }
+        self.leftLevels = leftLevels;
+        self.missingLeft = missingLeft;
         self.score = score; // 最良の分割点のスコア
 
         (left, right)
     }
 
+    // ============================================================
+    //  i列目で分割する場合の最良の分割を求める（split_treeから列ごとに呼ぶ）
+    //
+    //  @param x 説明変数
+    //  @param y 目的変数
+    //  @param i 分割対象変数（の列番号）
+    //  @param weights 行ごとの重み（Noneなら重みなし）
+    //
+    //  @return 最良の分割．損失値が有限になる分割がなければNone
+    // ============================================================
+    fn bestSplitOf(&self, x: &View, y: &View, i: usize, weights: Option<&[f64]>) -> Option<SplitCandidate> {
+        let missing: Vec<usize> = x.columnIter(i).enumerate().filter(|(_, v)| v.is_nan()).map(|(irow, _)| irow).collect();  // 欠損値の行
+
+        if self.randomThresholds {
+            return self.randomSplitOf(x, y, i, weights, &missing);
+        }
+
+        let mut best: Option<SplitCandidate> = None;
+
+        if self.categorical.contains(&i) {
+            // カテゴリ列．目的変数の平均の順で前からk個の水準を左に入れる
+            let order = DecisionTree::orderLevels(x, i, y, weights);
+            for k in 1..order.len() {
+                let (l, r) = self.make_category_split(x.columnIter(i), &order[..k]);
+                if let Some((loss, l, r, toLeft)) = self.bestCandidate(&missing, l, r, y, weights) {
+                    if loss < best.as_ref().map_or(f64::INFINITY, |b| b.loss) {
+                        best = Some(SplitCandidate { loss, left: l, right: r, missingLeft: toLeft, val: f64::NAN, leftLevels: Some(order[..k].to_vec()) });
+                    }
+                }
+            }
+            return best;
+        }
+
+        for val in x.columnIter(i).filter(|v| !v.is_nan()) {    // i列目の要素1個ずつについてループ
+            let (l, r) = self.make_split(x.columnIter(i), val);   // i列目をvalで分割 --> (l, r)
+            if let Some((loss, l, r, toLeft)) = self.bestCandidate(&missing, l, r, y, weights) {
+                // println!("-------------------");
+                // println!("i={}, val={}", i, val);
+                // println!("loss={}", loss);
+                if loss < best.as_ref().map_or(f64::INFINITY, |b| b.loss) {
+                    best = Some(SplitCandidate { loss, left: l, right: r, missingLeft: toLeft, val, leftLevels: None });
+                }
+            }
+        }
+        best
+    }
+
+    // ============================================================
+    //  i列目で無作為に選んだ基準値で分割する（bestSplitOfから呼ぶ．ExtraTrees）
+    //  乱数はこのノードのシードから列ごとに派生させる（子ノードは1, 2なので3+i）ので、
+    //  列を並列に調べても結果は変わらない
+    //
+    //  @param missing i列目が欠損値の行（昇順）
+    //
+    //  @return 分割．全て同じ値（欠損値）で分けられなければNone
+    // ============================================================
+    fn randomSplitOf(&self, x: &View, y: &View, i: usize, weights: Option<&[f64]>, missing: &[usize]) -> Option<SplitCandidate> {
+        let mut rng = random::Rng::new(random::deriveSeed(self.seed, 3 + i as u64));
+
+        if self.categorical.contains(&i) {
+            let order = DecisionTree::orderLevels(x, i, y, weights);
+            if order.len() < 2 {
+                return None;
+            }
+            let k = 1 + rng.range(order.len() - 1);
+            let (l, r) = self.make_category_split(x.columnIter(i), &order[..k]);
+            return self.bestCandidate(missing, l, r, y, weights)
+                .map(|(loss, l, r, toLeft)| SplitCandidate { loss, left: l, right: r, missingLeft: toLeft, val: f64::NAN, leftLevels: Some(order[..k].to_vec()) });
+        }
+
+        // 欠損値を除いた最小値、最大値（全て欠損値ならNaNなので分けられない）
+        let mm = U::calcMinMax(&x.columnIter(i).collect::<Vec<f64>>());
+        if mm.min.is_nan() || mm.min >= mm.max {
+            return None;
+        }
+        // (min, max]から選ぶ（minだと左が空になる）
+        let val = mm.max - rng.nextF64() * (mm.max - mm.min);
+        let (l, r) = self.make_split(x.columnIter(i), val);
+        self.bestCandidate(missing, l, r, y, weights)
+            .map(|(loss, l, r, toLeft)| SplitCandidate { loss, left: l, right: r, missingLeft: toLeft, val, leftLevels: None })
+    }
+
+    // ============================================================
+    //  分割(l, r)の損失値を求める
+    //  欠損値があれば、欠損値の行を右に入れた場合と左に入れた場合の両方を試して良い方を返す
+    //
+    //  @param missing 分割対象変数が欠損値の行（昇順）
+    //  @param l, r 左右に分けた行（欠損値は右）
+    //  @param weights 行ごとの重み（Noneなら重みなし）
+    //
+    //  @return (損失値, 左の行, 右の行, 欠損値を左に入れたか)．損失値が有限でなければNone
+    // ============================================================
+    fn bestCandidate(&self, missing: &[usize], l: Vec<usize>, r: Vec<usize>, y: &View, weights: Option<&[f64]>) -> Option<(f64, Vec<usize>, Vec<usize>, bool)> {
+        let mut candidates = vec![(l, r, false)];
+        if !missing.is_empty() {
+            let (l, r, _) = &candidates[0];
+            let mut l2: Vec<usize> = l.iter().chain(missing.iter()).cloned().collect();
+            l2.sort_unstable();
+            let r2: Vec<usize> = r.iter().filter(|irow| missing.binary_search(irow).is_err()).cloned().collect();
+            candidates.push((l2, r2, true));
+        }
+
+        let mut best: Option<(f64, Vec<usize>, Vec<usize>, bool)> = None;
+        for (l, r, toLeft) in candidates {
+            // yのl行目、r行目から損失値を計算（値はコピーしない）
+            let loss = match weights {
+                Some(w) => self.make_loss_weighted(y, &l, &r, w),
+                None => self.make_loss(y, &l, &r)
+            };
+            if loss < best.as_ref().map_or(f64::INFINITY, |b| b.0) {
+                best = Some((loss, l, r, toLeft));
+            }
+        }
+        best
+    }
+
     // ============================================================
     //  モデルを構築する
+    //
+    //  @return 列の長さが揃っていなければShapeError
+    // ============================================================
+    pub fn fit(&mut self, x: &U::Matrix, y: &U::Matrix, max_depth: u32) -> Result<(), ShapeError> {
+        let (xd, yd) = dataset::fromMatrices(x, y)?;
+        self.fitView(&xd.view(), &yd.view(), None, max_depth)
+    }
+
+    // ============================================================
+    //  行ごとの重みを付けてモデルを構築する
+    //  分割は重み付きの損失関数で決め、葉のモデルも重み付きで学習する
+    //
+    //  @param weights 行ごとの重み（長さ=行数）
+    //
+    //  @return 列の長さ、重みの数が揃っていなければShapeError
+    // ============================================================
+    pub fn fitWeighted(&mut self, x: &U::Matrix, y: &U::Matrix, weights: &[f64], max_depth: u32) -> Result<(), ShapeError> {
+        let (xd, yd) = dataset::fromMatrices(x, y)?;
+        self.fitView(&xd.view(), &yd.view(), Some(weights), max_depth)
+    }
+
     // ============================================================
-    pub fn fit(&mut self, x: &U::Matrix, y: &U::Matrix, max_depth: u32) -> &Self {
+    //  モデルを構築する（データセットのビューから）
+    //  子ノードには行番号を絞ったビューを渡すので、ノードごとに値をコピーしない
+    //
+    //  @param weights 行ごとの重み（Noneなら重みなし）
+    //
+    //  @return x, y, weightsの行数が違えばShapeError
+    // ============================================================
+    pub fn fitView(&mut self, x: &View, y: &View, weights: Option<&[f64]>, max_depth: u32) -> Result<(), ShapeError> {
+        dataset::checkRows(x, y)?;
+        dataset::checkWeights(x, weights)?;
+        self.rootVariances = DecisionTree::rootVariancesOf(y, weights);
+        self.fit_sub(x, y, weights, max_depth)
+    }
+
+    // 目的変数ごとの全ての行の（重み付き）分散（全て同じ値の列は1にする）
+    fn rootVariancesOf(y: &View, weights: Option<&[f64]>) -> Vec<f64> {
+        let rows: Vec<usize> = (0..y.nrows()).collect();
+        (0..y.ncols()).map(|j| {
+            let v = match weights {
+                Some(w) => weightedVariance(y, &rows, j, w),
+                None => variance(y, &rows, j)
+            };
+            if v > 0.0 { v } else { 1.0 }
+        }).collect()
+    }
+
+    // 子ノード（左ならside=1, 右ならside=2）を作る（設定と根ノードの分散を引き継ぐ．シードはノードごとに派生させる）
+    fn child(&self, side: u64, max_depth: u32) -> DecisionTree {
+        let mut node = DecisionTree::new(self.depth+1, max_depth)
+            .leafSolver(self.leafSolver)
+            .leaf(self.leafKind)
+            .categorical(&self.categorical)
+            .threads(self.threads)
+            .randomThresholds(self.randomThresholds)
+            .seed(random::deriveSeed(self.seed, side));
+        node.maxFeatures = self.maxFeatures;
+        node.rootVariances = self.rootVariances.clone();
+        node
+    }
+
+    // fitViewの本体
+    fn fit_sub(&mut self, x: &View, y: &View, weights: Option<&[f64]>, max_depth: u32) -> Result<(), ShapeError> {
         // x, yを最も上手く分割する仕方 --> (left, right)
         // left ... 左側の枝に入れる行番号
         // right ... 右側の枝に入れる行番号
-        let (left, right) = self.split_tree(&x, &y);
+        let (left, right) = self.split_tree(x, y, weights);
+
+        // 左右に分けた重み
+        let select = |rows: &Vec<usize>| weights.map(|w| rows.iter().map(|i| w[*i]).collect::<Vec<f64>>());
+        let wl = select(&left);
+        let wr = select(&right);
 
         if self.depth < self.max_depth {
-            if left.len() > 0 {
+            if !left.is_empty() {
                 // まだ最大深度に達していない、かつ左側に分割する行がある --> self.leftを新しいノードに置き換える
-                self.left = NodeType::Node(Box::new(DecisionTree::new(self.depth+1, max_depth)));
+                self.left = NodeType::Node(Box::new(self.child(1, max_depth)));
             }
 
-            if right.len() > 0 {
+            if !right.is_empty() {
                 // まだ最大深度に達していない、かつ右側に分割する行がある --> self.rightを新しいノードに置き換える
-                self.right = NodeType::Node(Box::new(DecisionTree::new(self.depth+1, max_depth)));
+                self.right = NodeType::Node(Box::new(self.child(2, max_depth)));
             }
         }
 
-        if left.len() > 0 {
-            // x, yから左側の枝に入れるデータを取り出す --> xl, yl
-            let xl: U::Matrix = U::MatSelectRow(x, &left);
-            let yl: U::Matrix = U::MatSelectRow(y, &left);
+        if !left.is_empty() {
+            // x, yから左側の枝に入れる行のビューを作る --> xl, yl
+            let xl = x.select(&left);
+            let yl = y.select(&left);
             match self.left {
-                // self.leftがノード --> 再帰的にfit()をコール
-                NodeType::Node(ref mut node) => { node.fit(&xl, &yl, max_depth); },
-                // self.leftがリーフ（末端） --> 線形モデルでfitさせる
-                NodeType::Leaf(ref mut leaf) => { leaf.fit(&xl, &yl); }
+                // self.leftがノード --> 再帰的にfit_sub()をコール
+                NodeType::Node(ref mut node) => node.fit_sub(&xl, &yl, wl.as_deref(), max_depth)?,
+                // self.leftがリーフ（末端） --> 葉のモデルでfitさせる
+                NodeType::Leaf(ref mut leaf) => leaf.fitView(&leafView(&self.categorical, &xl), &yl, wl.as_deref())?
             }
         }
 
-        if right.len() > 0 {
-            // x, yから右側の枝に入れるデータを取り出す --> xr, yr
-            let xr: U::Matrix = U::MatSelectRow(x, &right);
-            let yr: U::Matrix = U::MatSelectRow(y, &right);
+        if !right.is_empty() {
+            // x, yから右側の枝に入れる行のビューを作る --> xr, yr
+            let xr = x.select(&right);
+            let yr = y.select(&right);
             match self.right {
-                // self.rightがノード --> 再帰的にfit()をコール
-                NodeType::Node(ref mut node) => { node.fit(&xr, &yr, max_depth); },
-                // self.rightがリーフ（末端） --> 線形モデルでfitさせる
-                NodeType::Leaf(ref mut leaf) => { leaf.fit(&xr, &yr); }
+                // self.rightがノード --> 再帰的にfit_sub()をコール
+                NodeType::Node(ref mut node) => node.fit_sub(&xr, &yr, wr.as_deref(), max_depth)?,
+                // self.rightがリーフ（末端） --> 葉のモデルでfitさせる
+                NodeType::Leaf(ref mut leaf) => leaf.fitView(&leafView(&self.categorical, &xr), &yr, wr.as_deref())?
             }
         }
 
-        self
+        Ok(())
+    }
+
+    // ============================================================
+    //  予測値を計算する（1列目の目的変数）．xに行がなければ空
+    //
+    //  @return 列の長さが揃っていなければShapeError
+    // ============================================================
+    pub fn predict(&self, x: &U::Matrix) -> Result<Vec<f64>, ShapeError> {
+        Ok(self.predictMulti(x)?.into_iter().next().unwrap_or_default())
     }
 
     // ============================================================
-    //  予測値を計算する
+    //  全ての目的変数の予測値を計算する（Matrix[目的変数][行]）
+    //
+    //  @return 列の長さが揃っていなければShapeError
     // ============================================================
-    pub fn predict(&self, x: &U::Matrix) -> Vec<f64> {
+    pub fn predictMulti(&self, x: &U::Matrix) -> Result<U::Matrix, ShapeError> {
+        let xd = Dataset::fromMatrix(x)?;
+        Ok(self.predictView(&xd.view()))
+    }
+
+    // ============================================================
+    //  全ての目的変数の予測値を計算する（データセットのビューから）
+    // ============================================================
+    pub fn predictView(&self, x: &View) -> U::Matrix {
         // self.feat_index .. このノードでの分割対象列
         // self.feat_val .... 分割値
-        let feat: &Vec<f64> = &x[self.feat_index];
-        let val: f64 = self.feat_val;
-        let (l, r) = self.make_split(feat, val);
+        // self.leftLevels .. カテゴリ列の場合、左に入れる水準
+        let (l, r) = self.splitRows(x);
 
-        let nrow = x[0].len();
-        let mut z: Vec<f64> = vec![0.0; nrow];    // 中身が0.0で長さnrowのベクトル
+        let nrow = x.nrows();
+        let mut z: U::Matrix = vec![];  // 予測値．左右の結果の列数に合わせて確保する
 
-        if (l.len() > 0) && (r.len() > 0) {
-            let xl: U::Matrix = U::MatSelectRow(x, &l);
+        if !l.is_empty() && !r.is_empty() {
+            let xl = x.select(&l);
             let left = match self.left {
-                // self.leftはノード --> 再帰的にpredict()をコール
-                NodeType::Node(ref node) => { node.predict(&xl) },
-                // self.leftはリーフ（末端） --> 線形モデルで予測
-                NodeType::Leaf(ref leaf) => { leaf.predict(&xl, false) }
+                // self.leftはノード --> 再帰的にpredictView()をコール
+                NodeType::Node(ref node) => { node.predictView(&xl) },
+                // self.leftはリーフ（末端） --> 葉のモデルで予測
+                NodeType::Leaf(ref leaf) => { leaf.predictView(&leafView(&self.categorical, &xl)) }
             };
 
-            let xr: U::Matrix = U::MatSelectRow(x, &r);
+            let xr = x.select(&r);
             let right = match self.right {
-                // self.rightはノード --> 再帰的にpredict()をコール
-                NodeType::Node(ref node) => { node.predict(&xr) },
-                // self.rightはリーフ（末端） --> 線形モデルで予測
-                NodeType::Leaf(ref leaf) => { leaf.predict(&xr, false) }
+                // self.rightはノード --> 再帰的にpredictView()をコール
+                NodeType::Node(ref node) => { node.predictView(&xr) },
+                // self.rightはリーフ（末端） --> 葉のモデルで予測
+                NodeType::Leaf(ref leaf) => { leaf.predictView(&leafView(&self.categorical, &xr)) }
             };
 
-            for i in 0..l.len() {
-                z[l[i]] = left[i];
-            }
-            for i in 0..r.len() {
-                z[r[i]] = right[i];
+            z = vec![vec![0.0; nrow]; left.len()];  // 中身が0.0で長さnrowのベクトル x 目的変数の数
+            for (zcol, (lcol, rcol)) in z.iter_mut().zip(left.iter().zip(right.iter())) {
+                for i in 0..l.len() {
+                    zcol[l[i]] = lcol[i];
+                }
+                for i in 0..r.len() {
+                    zcol[r[i]] = rcol[i];
+                }
             }
-        } else if l.len() > 0 {
+        } else if !l.is_empty() {
             z = match self.left {
-                NodeType::Node(ref node) => { node.predict(&x) },
-                NodeType::Leaf(ref leaf) => { leaf.predict(&x, false) }
+                NodeType::Node(ref node) => { node.predictView(x) },
+                NodeType::Leaf(ref leaf) => { leaf.predictView(&leafView(&self.categorical, x)) }
             }
-        } else if r.len() > 0 {
+        } else if !r.is_empty() {
             z = match self.right {
-                NodeType::Node(ref node) => { node.predict(&x) },
-                NodeType::Leaf(ref leaf) => { leaf.predict(&x, false) }
+                NodeType::Node(ref node) => { node.predictView(x) },
+                NodeType::Leaf(ref leaf) => { leaf.predictView(&leafView(&self.categorical, x)) }
             }
         }
 
         z
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 1列のラベルのデータセットと、その全ての行の行番号
+    fn labels(v: &[f64]) -> (Dataset, Vec<usize>) {
+        (Dataset::fromMatrix(&vec![v.to_vec()]).unwrap(), (0..v.len()).collect())
+    }
 
-    pub fn test_make_split(&self, x: &U::Matrix, y: &U::Matrix) {
-        let feat: &Vec<f64> = &x[0];
-        let nrow = feat.len();
-        for irow in 0..nrow {
-            let val = feat[irow];
-            let (left, right) = self.make_split(feat, val);
-            println!("-----------------------------------");
-            println!("[{}] l={:?}", irow, left);
-            println!("[{}] r={:?}", irow, right);
-        }
+    #[test]
+    fn giniOfLabels() {
+        let (y, rows) = labels(&[1.0, 1.0, 1.0]);
+        assert_eq!(gini(&y.view(), &rows, &[]), 0.0);
+        let (y, rows) = labels(&[0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(gini(&y.view(), &rows, &[]), 0.5);
+        assert_eq!(gini(&y.view(), &[0, 1, 2], &[]), 4.0 / 9.0);    // 一部の行だけ
+        let (y, rows) = labels(&[0.0, 1.0, 2.0]);
+        assert!((gini(&y.view(), &rows, &[]) - 2.0 / 3.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn weightedGiniCountsWeights() {
+        // 重み2の行は同じ行が2つあるのと同じ
+        let (y, rows) = labels(&[0.0, 1.0, 2.0]);
+        let (repeated, rrows) = labels(&[0.0, 0.0, 1.0, 2.0]);
+        assert!((weightedGini(&y.view(), &rows, &[], &[2.0, 1.0, 1.0]) - gini(&repeated.view(), &rrows, &[])).abs() < 1e-12);
+    }
+
+    #[test]
+    fn logisticLeavesPredictLabels() {
+        // x < 10ならクラス3, それ以外はクラス7
+        let x: U::Matrix = vec![(0..20).map(|i| i as f64).collect()];
+        let y: U::Matrix = vec![(0..20).map(|i| if i < 10 { 3.0 } else { 7.0 }).collect()];
+        let mut d = DecisionTree::new(1, 1).leaf(LeafKind::Logistic);
+        d.fit(&x, &y, 1).unwrap();
+        assert_eq!(d.feat_val, 10.0);
+        assert_eq!(d.score, 0.0);
+        assert_eq!(d.predict(&x).unwrap(), y[0]);
+
+        // 説明変数が全てカテゴリ列なら、葉は列のないビューで学習、予測する
+        let xc: U::Matrix = vec![(0..20).map(|i| if i < 10 { 0.0 } else { 1.0 }).collect()];
+        let mut d = DecisionTree::new(1, 1).leaf(LeafKind::Logistic).categorical(&[0]);
+        d.fit(&xc, &y, 1).unwrap();
+        assert_eq!(d.predict(&xc).unwrap(), y[0]);
+    }
+
+    // y1はx1 < 20かどうか、y2はx2 < 10かどうかで決まる（x2の方は少しばらつきを加える）
+    fn twoTargets(scale2: f64) -> (U::Matrix, U::Matrix) {
+        let x1: Vec<f64> = (0..40).map(|i| i as f64).collect();
+        let x2: Vec<f64> = (0..40).map(|i| ((i * 7) % 40) as f64).collect();
+        let y1: Vec<f64> = x1.iter().map(|v| if *v < 20.0 { 0.0 } else { 1.0 }).collect();
+        let y2: Vec<f64> = x2.iter().enumerate().map(|(i, v)| (if *v < 10.0 { 0.0 } else { 3.0 } + (i % 3) as f64 * 0.1) * scale2).collect();
+        (vec![x1, x2], vec![y1, y2])
+    }
+
+    #[test]
+    fn predictWithoutRowsIsEmpty() {
+        let (x, y) = twoTargets(1.0);
+        let mut d = DecisionTree::new(1, 2).leafSolver(linear::Solver::Qr);
+        d.fit(&x, &y, 2).unwrap();
+        let empty: U::Matrix = vec![vec![], vec![]];
+        assert!(d.predict(&empty).unwrap().is_empty());
+        assert!(d.predictMulti(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn multiOutputPredictsEveryTarget() {
+        let (x, y) = twoTargets(1.0);
+        let mut d = DecisionTree::new(1, 3).leafSolver(linear::Solver::Qr);
+        d.fit(&x, &y, 3).unwrap();
+        let pred = d.predictMulti(&x).unwrap();
+        assert_eq!(pred.len(), 2);
+        assert!(pred.iter().all(|col| col.len() == 40));
+        assert!(pred[0].iter().zip(y[0].iter()).all(|(p, t)| (p - t).abs() < 1e-6));
     }
 
-    pub fn test_split_tree(&mut self, x: &U::Matrix, y: &U::Matrix) {
-        let (left, right) = self.split_tree(&x, &y);
-        println!("-----------------------------------");
-        println!("l={:?}", left);
-        println!("r={:?}", right);
-        println!("feat_index={}", self.feat_index);
-        println!("feat_val={}", self.feat_val);
-        println!("score={}", self.score);
+    #[test]
+    fn multiOutputSplitIgnoresTargetScale() {
+        // 根ノードの分散で割るので、目的変数の単位を変えても同じ列で分割する
+        let split = |scale2: f64| {
+            let (x, y) = twoTargets(scale2);
+            let mut d = DecisionTree::new(1, 1).leafSolver(linear::Solver::Qr);
+            d.fit(&x, &y, 1).unwrap();
+            (d.feat_index, d.feat_val)
+        };
+        assert_eq!(split(1.0), split(1000.0));
+        assert_eq!(split(1.0), split(0.001));
     }
 
-    pub fn test1(&self, x: &U::Matrix, y: &U::Matrix) {
-        let z = zeror::ZeroRule::new();
-        let mut m1: Model = Model::Zeror(z);
-        if let Model::Zeror(ref mut m) = m1 {
-            m.fit(&x, &y);
+    #[test]
+    fn missingValuesGoToTheBetterSide() {
+        // 欠損値の行の目的変数は大きい側と同じ --> 欠損値は右(x >= 基準値の側)に入れる
+        let x: U::Matrix = vec![vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0, f64::NAN, f64::NAN]];
+        let y: U::Matrix = vec![vec![0.0, 0.0, 0.0, 10.0, 10.0, 10.0, 10.0, 10.0]];
+        let mut d = DecisionTree::new(1, 1).leafSolver(linear::Solver::Qr);
+        d.fit(&x, &y, 1).unwrap();
+        assert_eq!(d.feat_val, 3.0);
+        assert!(!d.missingLeft);
 
+        // 小さい側と同じなら左
+        let y: U::Matrix = vec![vec![0.0, 0.0, 0.0, 10.0, 10.0, 10.0, 0.0, 0.0]];
+        let mut d = DecisionTree::new(1, 1).leafSolver(linear::Solver::Qr);
+        d.fit(&x, &y, 1).unwrap();
+        assert!(d.missingLeft);
+        let pred = d.predict(&vec![vec![f64::NAN, 5.0]]).unwrap();
+        assert!(pred[0].abs() < 1e-9 && (pred[1] - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn categoricalSplitGroupsLevels() {
+        // 水準0, 2の行と水準1, 3の行で目的変数が分かれる（大小の1回の分割では分けられない）
+        let x: U::Matrix = vec![(0..40).map(|i| (i % 4) as f64).collect(), (0..40).map(|i| i as f64).collect()];
+        let y: U::Matrix = vec![(0..40).map(|i| if i % 2 == 0 { 1.0 } else { 5.0 } + (i as f64) * 0.01).collect()];
+        let mut d = DecisionTree::new(1, 1).leafSolver(linear::Solver::Qr).categorical(&[0]);
+        d.fit(&x, &y, 1).unwrap();
+        assert_eq!(d.feat_index, 0);
+        let mut left = d.leftLevels.clone().unwrap();
+        left.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(left, vec![0.0, 2.0]);
+
+        // 葉の線形モデルはカテゴリ列を説明変数に使わない
+        match d.left {
+            NodeType::Leaf(ref leaf) => match **leaf {
+                LeafModel::Linear(ref l) => assert_eq!(l.report().len(), 2),    // 切片と2列目
+                LeafModel::Logistic(_) => panic!("expected a linear leaf")
+            },
+            NodeType::Node(_) => panic!("expected a leaf")
         }
+        let pred = d.predict(&x).unwrap();
+        assert!(pred.iter().zip(y[0].iter()).all(|(p, t)| (p - t).abs() < 1e-9));
+
+        // 説明変数が全てカテゴリ列なら、葉は水準のグループごとの平均になる
+        let xc: U::Matrix = vec![x[0].clone()];
+        let mut d = DecisionTree::new(1, 1).leafSolver(linear::Solver::Qr).categorical(&[0]);
+        d.fit(&xc, &y, 1).unwrap();
+        let pred = d.predict(&xc).unwrap();
+        assert!((pred[0] - 1.19).abs() < 1e-9 && (pred[1] - 5.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn fitViewUsesOnlySelectedRows() {
+        let (x, y) = twoTargets(1.0);
+        let xd = Dataset::fromMatrix(&x).unwrap();
+        let yd = Dataset::fromMatrix(&y).unwrap();
 
-        let l = linear::Linear::new();
-        let mut m2: Model = Model::Linear(l);
-        if let Model::Linear(ref mut l) = m2 {
-            l.fit(&x, &y);
+        // ビューで選んだ行だけで学習すると、その行を取り出した行列で学習したのと同じ木になる
+        let rows: Vec<usize> = (0..40).filter(|i| i % 4 != 1).collect();
+        let mut byView = DecisionTree::new(1, 2).leafSolver(linear::Solver::Qr);
+        byView.fitView(&xd.view().select(&rows), &yd.view().select(&rows), None, 2).unwrap();
+        let mut byMatrix = DecisionTree::new(1, 2).leafSolver(linear::Solver::Qr);
+        byMatrix.fit(&U::MatSelectRow(&x, &rows), &U::MatSelectRow(&y, &rows), 2).unwrap();
+        assert_eq!((byView.feat_index, byView.feat_val), (byMatrix.feat_index, byMatrix.feat_val));
+        assert_eq!(byView.predictView(&xd.view()), byMatrix.predictMulti(&x).unwrap());
+    }
 
-            let result: Vec<f64> = l.predict(&x, false);
+    #[test]
+    fn fitReturnsShapeErrors() {
+        let (x, y) = twoTargets(1.0);
+        let mut d = DecisionTree::new(1, 1);
+        assert!(matches!(d.fit(&x, &vec![vec![0.0; 3]], 1), Err(ShapeError::Rows { x: 40, y: 3 })));
+        assert!(matches!(d.fitWeighted(&x, &y, &[1.0], 1), Err(ShapeError::Weights { expected: 40, found: 1 })));
+        let ragged: U::Matrix = vec![x[0].clone(), vec![1.0]];
+        assert!(matches!(d.predict(&ragged), Err(ShapeError::Ragged { column: 1, .. })));
+    }
 
-            println!("*** Linear output (2) ***");
-            println!("{:?}", result);
+    #[test]
+    fn sameTreeForAnyThreadCount() {
+        // 分割探索を並列に行う大きさ(PARALLEL_MIN_CELLS以上)のデータ
+        let (x, y) = random::noisyData(300, 8, 5);
+        let fit = |threads: usize, maxFeatures: usize, randomThresholds: bool| {
+            let mut d = DecisionTree::new(1, 4).leafSolver(linear::Solver::Qr).threads(threads)
+                .maxFeatures(maxFeatures).randomThresholds(randomThresholds).seed(11);
+            d.fit(&x, &y, 4).unwrap();
+            (d.feat_index, d.feat_val, d.predict(&x).unwrap())
+        };
+        // 全ての列、ノードごとにシードで選んだ5列．基準値を無作為に選ぶ場合も
+        for randomThresholds in [false, true] {
+            for maxFeatures in [8, 5] {
+                let one = fit(1, maxFeatures, randomThresholds);
+                assert_eq!(one, fit(4, maxFeatures, randomThresholds));
+                assert_eq!(one, fit(16, maxFeatures, randomThresholds));
+            }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn randomThresholdWithinRange() {
+        let (x, y) = random::noisyData(50, 2, 8);
+        let (xd, yd) = dataset::fromMatrices(&x, &y).unwrap();
+        let mm = U::calcMinMax(&x[0]);
+        for seed in 0..20 {
+            let mut d = DecisionTree::new(1, 1).randomThresholds(true).seed(seed);
+            d.rootVariances = vec![1.0];
+            let c = d.randomSplitOf(&xd.view(), &yd.view(), 0, None, &[]).unwrap();
+            assert!(c.val > mm.min && c.val <= mm.max);
+            assert!(!c.left.is_empty() && !c.right.is_empty());
+        }
+
+        // 全て同じ値（欠損値）の列は分けられない
+        for v in [1.0, f64::NAN] {
+            let cd = Dataset::fromMatrix(&vec![vec![v; 50]]).unwrap();
+            assert!(DecisionTree::new(1, 1).randomThresholds(true).randomSplitOf(&cd.view(), &yd.view(), 0, None, &[]).is_none());
+        }
+    }
+}
@@ -0,0 +1,534 @@
+// ******************************************************************
+//  決定木のアンサンブル
+//  バギング(Bagging) ... 各木をブートストラップ標本（重複を許して選んだ行）で学習する
+//  ランダムフォレスト(Random forest) ... バギング＋ノードごとに無作為に選んだ列の中で分割を探す
+//  ExtraTrees (Extremely randomized trees)
+//    各ノードで列ごとに基準値を最小値と最大値の間から1つだけ無作為に選び、その中で最も良い分割を採る
+//  木ごとに分割がばらつくので、多数の木の予測値を平均すると1本の決定木より分散が小さくなる
+//
+//  ブートストラップ標本で学習した場合、各木が学習に使わなかった行(OOB: out-of-bag)での予測値を
+//  平均すると、検証用のデータを分けなくても汎化性能を見積もれる
+// ******************************************************************
+
+#![allow(non_snake_case)]
+
+use super::U;   // main.rsのコメントを参照
+use super::linear;
+use super::dtree::DecisionTree;
+use super::dataset;
+use super::dataset::{Dataset, ShapeError, View};
+use super::parallel;
+use super::random;
+use super::importance::Importance;
+use super::search::Scorer;
+
+// =================================================
+//  決定木のアンサンブル
+// =================================================
+pub struct Forest {
+    nTrees: usize,      // 木の数
+    max_depth: u32,     // 各木の最大深度
+    maxFeatures: Option<usize>, // ノードごとに分割を探す列の数（Noneなら全ての列）
+    randomThresholds: bool,     // 基準値を無作為に選ぶか(ExtraTrees)
+    bootstrap: bool,    // 各木をブートストラップ標本で学習するか
+    autoFeatures: bool, // maxFeaturesが指定されていなければ列数の1/3にするか(ランダムフォレスト)
+    leafSolver: linear::Solver, // 葉（線形モデル）の回帰係数の求め方
+    categorical: Vec<usize>,    // 水準の集合で分割するカテゴリ列（の列番号）
+    seed: u64,          // 乱数のシード（i番目の木はderiveSeed(seed, i)を使う）
+    threads: usize,     // 木を並列に学習するスレッド数
+    trees: Vec<DecisionTree>,
+    oobRows: Vec<Vec<usize>>,   // 木ごとの学習に使わなかった行（の行番号．昇順）
+    oobPred: Option<U::Matrix>  // OOBでの予測値（Matrix[目的変数][行]．OOBにならなかった行はNaN）
+}
+
+impl Forest {
+    fn new(nTrees: usize, max_depth: u32) -> Self {
+        Forest {
+            nTrees: nTrees.max(1),
+            max_depth,
+            maxFeatures: None,
+            randomThresholds: false,
+            bootstrap: false,
+            autoFeatures: false,
+            leafSolver: linear::Solver::Sgd,
+            categorical: vec![],
+            seed: 0,
+            threads: 1,
+            trees: vec![],
+            oobRows: vec![],
+            oobPred: None
+        }
+    }
+
+    // バギング（各木をブートストラップ標本で学習し、全ての列、全ての値で分割を探す）
+    // (ex) let mut b = ensemble::Forest::bagging(10, 3).seed(1);
+    pub fn bagging(nTrees: usize, max_depth: u32) -> Self {
+        Forest::new(nTrees, max_depth).bootstrap(true)
+    }
+
+    // ランダムフォレスト（バギング＋ノードごとにmaxFeatures列（指定しなければ列数の1/3）の中で分割を探す）
+    // (ex) let mut f = ensemble::Forest::randomForest(10, 3).seed(1);
+    pub fn randomForest(nTrees: usize, max_depth: u32) -> Self {
+        let mut f = Forest::new(nTrees, max_depth).bootstrap(true);
+        f.autoFeatures = true;
+        f
+    }
+
+    // ExtraTrees（全ての行で各木を学習し、基準値を無作為に選ぶ）
+    // (ex) let mut e = ensemble::Forest::extraTrees(10, 3).seed(1);
+    pub fn extraTrees(nTrees: usize, max_depth: u32) -> Self {
+        let mut e = Forest::new(nTrees, max_depth);
+        e.randomThresholds = true;
+        e
+    }
+
+    // 各木をブートストラップ標本で学習するか（trueならOOBの予測値を求める）
+    pub fn bootstrap(mut self, bootstrap: bool) -> Self {
+        self.bootstrap = bootstrap;
+        self
+    }
+
+    // ノードごとに列をk個だけ無作為に選び、その中で分割を探す
+    pub fn maxFeatures(mut self, k: usize) -> Self {
+        self.maxFeatures = Some(k.max(1));
+        self
+    }
+
+    // 葉の線形モデルの回帰係数の求め方（DecisionTree::leafSolverを参照）
+    pub fn leafSolver(mut self, solver: linear::Solver) -> Self {
+        self.leafSolver = solver;
+        self
+    }
+
+    // 水準の番号を持つカテゴリ列（DecisionTree::categoricalを参照）
+    pub fn categorical(mut self, cols: &[usize]) -> Self {
+        self.categorical = cols.to_vec();
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    // 木を並列に学習するスレッド数（結果はスレッド数によらない）
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    // i番目の木のシード
+    fn treeSeed(&self, i: usize) -> u64 {
+        random::deriveSeed(self.seed, i as u64)
+    }
+
+    // i番目の木を作る．木の数よりスレッドが多ければ、余ったスレッドを木の中の分割探索に回す
+    //
+    // @param ncol 説明変数の列数
+    fn newTree(&self, i: usize, ncol: usize) -> DecisionTree {
+        let mut d = DecisionTree::new(1, self.max_depth)
+            .leafSolver(self.leafSolver)
+            .categorical(&self.categorical)
+            .randomThresholds(self.randomThresholds)
+            .threads(self.threads / self.nTrees)
+            .seed(self.treeSeed(i));
+        let maxFeatures = if self.autoFeatures { self.maxFeatures.or(Some(ncol / 3)) } else { self.maxFeatures };
+        if let Some(k) = maxFeatures {
+            d = d.maxFeatures(k);
+        }
+        d
+    }
+
+    // -----------------------------------------------
+    // i番目の木の学習に使う行を選ぶ
+    // 木のシードから派生させた乱数を使う（木の中では0番を使わないので重ならない）
+    //
+    // @return (学習に使う行（重複あり）, 使わなかった行)
+    // -----------------------------------------------
+    fn sampleRows(&self, i: usize, nrow: usize) -> (Vec<usize>, Vec<usize>) {
+        if !self.bootstrap {
+            return ((0..nrow).collect(), vec![]);
+        }
+        let mut rng = random::Rng::new(random::deriveSeed(self.treeSeed(i), 0));
+        let inBag = rng.bootstrap(nrow);
+        let mut used = vec![false; nrow];
+        for irow in &inBag {
+            used[*irow] = true;
+        }
+        let oob = (0..nrow).filter(|irow| !used[*irow]).collect();
+        (inBag, oob)
+    }
+
+    // ============================================================
+    //  モデルを構築する
+    //
+    //  @return 列の長さが揃っていなければShapeError
+    // ============================================================
+    pub fn fit(&mut self, x: &U::Matrix, y: &U::Matrix) -> Result<(), ShapeError> {
+        let (xd, yd) = dataset::fromMatrices(x, y)?;
+        self.fitView(&xd.view(), &yd.view(), None)
+    }
+
+    // ============================================================
+    //  行ごとの重みを付けてモデルを構築する（各木を重み付きで学習する）
+    //
+    //  @param weights 行ごとの重み（長さ=行数）
+    //
+    //  @return 列の長さ、重みの数が揃っていなければShapeError
+    // ============================================================
+    pub fn fitWeighted(&mut self, x: &U::Matrix, y: &U::Matrix, weights: &[f64]) -> Result<(), ShapeError> {
+        let (xd, yd) = dataset::fromMatrices(x, y)?;
+        self.fitView(&xd.view(), &yd.view(), Some(weights))
+    }
+
+    // ============================================================
+    //  モデルを構築する（データセットのビューから）
+    //  各木は互いに独立なので、self.threads個のスレッドで分担して学習する
+    //  ブートストラップ標本で学習した場合は、OOBの予測値も求める
+    //
+    //  @param weights 行ごとの重み（Noneなら重みなし）
+    //
+    //  @return x, y, weightsの行数が違えばShapeError
+    // ============================================================
+    pub fn fitView(&mut self, x: &View, y: &View, weights: Option<&[f64]>) -> Result<(), ShapeError> {
+        dataset::checkRows(x, y)?;
+        dataset::checkWeights(x, weights)?;
+        let nrow = x.nrows();
+        let ncol = x.ncols();
+        let fitted = parallel::map(self.nTrees, self.threads, |i| {
+            let (inBag, oob) = self.sampleRows(i, nrow);
+            let mut d = self.newTree(i, ncol);
+            let w: Option<Vec<f64>> = weights.map(|w| inBag.iter().map(|irow| w[*irow]).collect());
+            d.fitView(&x.select(&inBag), &y.select(&inBag), w.as_deref(), self.max_depth).map(|_| (d, oob))
+        });
+        let (trees, oobRows) = fitted.into_iter().collect::<Result<Vec<(DecisionTree, Vec<usize>)>, ShapeError>>()?.into_iter().unzip();
+        self.trees = trees;
+        self.oobRows = oobRows;
+        self.oobPred = if self.bootstrap { Some(self.predictOob(x, y.ncols())) } else { None };
+        Ok(())
+    }
+
+    // ============================================================
+    //  学習に使った行のOOBでの予測値（その行を学習に使わなかった木の予測値の平均）
+    //  どの木でも学習に使われた行はNaN
+    //
+    //  @param nOutputs 目的変数の列数
+    // ============================================================
+    fn predictOob(&self, x: &View, nOutputs: usize) -> U::Matrix {
+        let nrow = x.nrows();
+        let mut sum: U::Matrix = vec![vec![0.0; nrow]; nOutputs];
+        let mut count = vec![0usize; nrow];
+        for (tree, oob) in self.trees.iter().zip(self.oobRows.iter()) {
+            if oob.is_empty() {
+                continue;
+            }
+            let p = tree.predictView(&x.select(oob));
+            for (scol, pcol) in sum.iter_mut().zip(p.iter()) {
+                for (k, irow) in oob.iter().enumerate() {
+                    scol[*irow] += pcol[k];
+                }
+            }
+            for irow in oob {
+                count[*irow] += 1;
+            }
+        }
+        for scol in sum.iter_mut() {
+            for (v, c) in scol.iter_mut().zip(count.iter()) {
+                *v = if *c > 0 { *v / (*c as f64) } else { f64::NAN };
+            }
+        }
+        sum
+    }
+
+    // OOBでの予測値（Matrix[目的変数][行]）．ブートストラップ標本で学習していなければNone
+    pub fn oobPrediction(&self) -> Option<&U::Matrix> {
+        self.oobPred.as_ref()
+    }
+
+    // ============================================================
+    //  OOBでの予測値のスコア（OOBになった行だけで計算する）
+    //
+    //  @param yTrue 学習に使った行の正解（toPredの結果と比べる値）
+    //  @param toPred 予測値(Matrix[目的変数][行])からスコアを計算する値を取り出す関数
+    //                （回帰なら1列目、one-hot表現のクラスを学習した場合は最も大きい列のクラスなど）
+    //  @param scorer スコア関数
+    //
+    //  @return (スコア, 計算に使った行数)．OOBの予測値がなければNone
+    // ============================================================
+    pub fn oobScore<D>(&self, yTrue: &[f64], toPred: D, scorer: Scorer) -> Option<(f64, usize)>
+        where D: Fn(U::Matrix) -> Vec<f64>
+    {
+        let pred = self.oobPred.as_ref()?;
+        let nrow = pred.first().map_or(0, |col| col.len());
+        let rows: Vec<usize> = (0..yTrue.len().min(nrow))
+            .filter(|irow| pred.iter().all(|col| !col[*irow].is_nan()))
+            .collect();
+        if rows.is_empty() {
+            return None;
+        }
+        let yo: Vec<f64> = rows.iter().map(|irow| yTrue[*irow]).collect();
+        Some((scorer(&yo, &toPred(U::MatSelectRow(pred, &rows))), rows.len()))
+    }
+
+    // ============================================================
+    //  OOBでの並べ替えによる特徴量の重要度
+    //  木ごとにOOBの行だけでj列目を並べ替え、その木のスコアの低下量を求めて全ての木で平均する
+    //  j列目の並べ替えにはderiveSeed(seed, j)の乱数を使う（列ごとに並列に計算しても結果は同じ）
+    //
+    //  @param x 学習に使った説明変数
+    //  @param yTrue 学習に使った行の正解（toPredの結果と比べる値）
+    //  @param toPred 木の予測値(Matrix[目的変数][行])からスコアを計算する値を取り出す関数（oobScoreを参照）
+    //  @param scorer スコア関数（大きいほど良い）
+    //  @param repeats 1本の木、1列あたりの並べ替えの回数
+    //  @param seed 乱数のシード
+    //
+    //  @return 列ごとの重要度（列の順）．OOBの行がなければ空．
+    //          xの列の長さが揃っていないか、行数が学習に使った行数、yTrueの長さと違えばShapeError
+    // ============================================================
+    pub fn oobImportance<D>(&self, x: &U::Matrix, yTrue: &[f64], toPred: D, scorer: Scorer, repeats: usize, seed: u64) -> Result<Vec<Importance>, ShapeError>
+        where D: Fn(U::Matrix) -> Vec<f64> + Sync
+    {
+        let xd = Dataset::fromMatrix(x)?;
+        let xv = xd.view();
+        if xv.nrows() != yTrue.len() {
+            return Err(ShapeError::Rows { x: xv.nrows(), y: yTrue.len() });
+        }
+        let nrow = match self.oobPred {
+            Some(ref p) => p.first().map_or(0, |col| col.len()),
+            None => return Ok(vec![])
+        };
+        if xv.nrows() != nrow {
+            return Err(ShapeError::Rows { x: xv.nrows(), y: nrow });    // 学習に使った行数と違う
+        }
+        if !self.oobRows.iter().any(|oob| !oob.is_empty()) {
+            return Ok(vec![]);
+        }
+
+        parallel::map(xv.ncols(), self.threads, |j| {
+            let mut rng = random::Rng::new(random::deriveSeed(seed, j as u64));
+            let mut drops = Vec::<f64>::new();
+            for (tree, oob) in self.trees.iter().zip(self.oobRows.iter()) {
+                if oob.is_empty() {
+                    continue;
+                }
+                let mut xo = xv.select(oob).toMatrix();
+                let yo: Vec<f64> = oob.iter().map(|irow| yTrue[*irow]).collect();
+                let baseline = scorer(&yo, &toPred(tree.predictMulti(&xo)?));
+                let col = xo[j].clone();
+                for _ in 0..repeats.max(1) {
+                    let order = rng.permutation(oob.len());
+                    xo[j] = order.iter().map(|k| col[*k]).collect();
+                    drops.push(baseline - scorer(&yo, &toPred(tree.predictMulti(&xo)?)));
+                }
+            }
+            Ok(Importance {
+                col: j,
+                mean: U::mean(&drops),
+                std: U::stdev(&drops)
+            })
+        }).into_iter().collect()
+    }
+
+    // ============================================================
+    //  予測値を計算する（1列目の目的変数）．xに行がなければ空
+    //
+    //  @return 列の長さが揃っていなければShapeError
+    // ============================================================
+    pub fn predict(&self, x: &U::Matrix) -> Result<Vec<f64>, ShapeError> {
+        Ok(self.predictMulti(x)?.into_iter().next().unwrap_or_default())
+    }
+
+    // ============================================================
+    //  全ての目的変数の予測値を計算する（Matrix[目的変数][行]）
+    //
+    //  @return 列の長さが揃っていなければShapeError
+    // ============================================================
+    pub fn predictMulti(&self, x: &U::Matrix) -> Result<U::Matrix, ShapeError> {
+        let xd = Dataset::fromMatrix(x)?;
+        Ok(self.predictView(&xd.view()))
+    }
+
+    // ============================================================
+    //  全ての目的変数の予測値を計算する（データセットのビューから）
+    //  各木の予測値の平均
+    // ============================================================
+    pub fn predictView(&self, x: &View) -> U::Matrix {
+        let mut z: U::Matrix = vec![];
+        for tree in &self.trees {
+            let p = tree.predictView(x);
+            if z.is_empty() {
+                z = p;
+            } else {
+                for (zcol, pcol) in z.iter_mut().zip(p.iter()) {
+                    for (a, b) in zcol.iter_mut().zip(pcol.iter()) {
+                        *a += b;
+                    }
+                }
+            }
+        }
+        let n = self.trees.len() as f64;
+        for zcol in z.iter_mut() {
+            for a in zcol.iter_mut() {
+                *a /= n;
+            }
+        }
+        z
+    }
+
+    pub fn print(&self) {
+        println!("trees: {}", self.trees.len());
+        println!("max_depth: {}", self.max_depth);
+        match self.maxFeatures {
+            Some(k) => println!("max_features: {}", k),
+            None if self.autoFeatures => println!("max_features: 1/3 of columns"),
+            None => println!("max_features: all")
+        }
+        println!("random thresholds: {}", self.randomThresholds);
+        println!("bootstrap: {}", self.bootstrap);
+        println!("seed: {}", self.seed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics;
+
+    // 1列目の予測値（回帰）
+    fn firstColumn(p: U::Matrix) -> Vec<f64> {
+        p.into_iter().next().unwrap_or_default()
+    }
+
+    #[test]
+    fn sameForestForAnyThreadCount() {
+        let (x, y) = random::noisyData(200, 6, 3);
+        for bootstrap in [false, true] {
+            let fit = |threads: usize| {
+                let mut f = Forest::extraTrees(6, 3).bootstrap(bootstrap).leafSolver(linear::Solver::Qr).seed(9).threads(threads);
+                f.fit(&x, &y).unwrap();
+                (f.predict(&x).unwrap(), f.oobRows.clone())
+            };
+            let one = fit(1);
+            assert_eq!(one, fit(3));
+            assert_eq!(one, fit(32));     // 木の数より多いスレッドは分割探索に回る
+        }
+        let fit = |threads: usize| {
+            let mut f = Forest::randomForest(6, 3).leafSolver(linear::Solver::Qr).seed(9).threads(threads);
+            f.fit(&x, &y).unwrap();
+            (f.predict(&x).unwrap(), f.oobRows.clone())
+        };
+        assert_eq!(fit(1), fit(4));
+    }
+
+    #[test]
+    fn extraTreesUseAllRows() {
+        let (x, y) = random::noisyData(200, 4, 6);
+        let mut e = Forest::extraTrees(10, 4).leafSolver(linear::Solver::Qr).seed(2);
+        e.fit(&x, &y).unwrap();
+        assert!(e.oobPrediction().is_none());
+        assert!(e.oobRows.iter().all(|oob| oob.is_empty()));
+        assert!(metrics::r2(&y[0], &e.predict(&x).unwrap()) > 0.9);
+
+        // 全ての行で学習しても、基準値を無作為に選ぶので木ごとに予測値が違う
+        assert_ne!(e.trees[0].predict(&x).unwrap(), e.trees[1].predict(&x).unwrap());
+
+        let mut other = Forest::extraTrees(10, 4).leafSolver(linear::Solver::Qr).seed(3);
+        other.fit(&x, &y).unwrap();
+        assert_ne!(e.predict(&x).unwrap(), other.predict(&x).unwrap());
+    }
+
+    #[test]
+    fn shapeErrorsAreReturned() {
+        let (x, y) = random::noisyData(20, 2, 1);
+        let mut f = Forest::bagging(2, 2);
+        assert!(matches!(f.fit(&x, &vec![vec![0.0; 19]]), Err(ShapeError::Rows { x: 20, y: 19 })));
+        assert!(matches!(f.fitWeighted(&x, &y, &[1.0; 3]), Err(ShapeError::Weights { expected: 20, found: 3 })));
+        let ragged: U::Matrix = vec![vec![0.0; 5], vec![0.0; 4]];
+        assert!(matches!(f.predictMulti(&ragged), Err(ShapeError::Ragged { .. })));
+
+        // OOBの重要度は学習に使った行で計算する
+        f.fit(&x, &y).unwrap();
+        let head = U::MatSelectRow(&x, &(0..10).collect::<Vec<usize>>());
+        assert!(matches!(f.oobImportance(&head, &y[0][..10], firstColumn, metrics::r2, 1, 0), Err(ShapeError::Rows { x: 10, y: 20 })));
+        assert!(matches!(f.oobImportance(&x, &y[0][..10], firstColumn, metrics::r2, 1, 0), Err(ShapeError::Rows { x: 20, y: 10 })));
+    }
+
+    #[test]
+    fn oobRowsAreDisjointFromInBag() {
+        let f = Forest::bagging(20, 2).seed(4);
+        for i in 0..20 {
+            let (inBag, oob) = f.sampleRows(i, 100);
+            assert_eq!(inBag.len(), 100);
+            assert!(!oob.is_empty());
+            assert!(oob.iter().all(|irow| inBag.binary_search(irow).is_err()));
+            let mut all: Vec<usize> = inBag.iter().chain(oob.iter()).cloned().collect();
+            all.sort_unstable();
+            all.dedup();
+            assert_eq!(all, (0..100).collect::<Vec<usize>>());
+        }
+        assert!(Forest::extraTrees(2, 2).sampleRows(0, 10).1.is_empty());
+    }
+
+    #[test]
+    fn oobPredictionAveragesTreesThatDidNotSeeTheRow() {
+        let (x, y) = random::noisyData(60, 3, 1);
+        let mut f = Forest::bagging(8, 2).leafSolver(linear::Solver::Qr).seed(5);
+        f.fit(&x, &y).unwrap();
+        let oob = f.oobPrediction().unwrap();
+
+        for (irow, pred) in oob[0].iter().enumerate() {
+            let trees: Vec<&DecisionTree> = f.trees.iter().zip(f.oobRows.iter())
+                .filter(|(_, rows)| rows.contains(&irow))
+                .map(|(t, _)| t)
+                .collect();
+            if trees.is_empty() {
+                assert!(pred.is_nan());
+                continue;
+            }
+            let xi = U::MatSelectRow(&x, &[irow]);
+            let mean = trees.iter().map(|t| t.predict(&xi).unwrap()[0]).sum::<f64>() / (trees.len() as f64);
+            assert!((pred - mean).abs() < 1e-9);
+        }
+
+        let counted = oob[0].iter().filter(|v| !v.is_nan()).count();
+        let (_, n) = f.oobScore(&y[0], firstColumn, metrics::r2).unwrap();
+        assert_eq!(n, counted);
+    }
+
+    #[test]
+    fn oobImportanceNeedsBootstrap() {
+        let (x, y) = random::noisyData(80, 3, 2);
+        let mut e = Forest::extraTrees(4, 2).leafSolver(linear::Solver::Qr);
+        e.fit(&x, &y).unwrap();
+        assert!(e.oobScore(&y[0], firstColumn, metrics::r2).is_none());
+        assert!(e.oobImportance(&x, &y[0], firstColumn, metrics::r2, 1, 0).unwrap().is_empty());
+
+        let mut f = Forest::bagging(8, 3).leafSolver(linear::Solver::Qr).seed(1);
+        f.fit(&x, &y).unwrap();
+        let imps = f.oobImportance(&x, &y[0], firstColumn, metrics::r2, 2, 0).unwrap();
+        assert_eq!(imps.len(), 3);
+        assert!(imps[0].mean > imps[2].mean);   // 目的変数は0列目(と1列目)で決まる
+    }
+
+    #[test]
+    fn oobAccuracyOnClassPredictions() {
+        // クラスは0列目で決まる．one-hot表現の全てのクラスの列を1つのアンサンブルで学習する
+        let (x, _) = random::noisyData(120, 3, 4);
+        let labels = vec![0.0, 1.0, 2.0];
+        let classes: Vec<f64> = x[0].iter().map(|v| (v * 3.0).floor().min(2.0)).collect();
+        let toLabels = |p: U::Matrix| {
+            let mut proba = p;
+            metrics::normalizeProba(&mut proba);
+            metrics::probaToLabels(&proba, &labels)
+        };
+        let mut f = Forest::bagging(10, 3).leafSolver(linear::Solver::Qr).seed(3);
+        f.fit(&x, &metrics::oneHot(&classes, &labels)).unwrap();
+        assert_eq!(f.oobPrediction().unwrap().len(), 3);
+
+        let (acc, _) = f.oobScore(&classes, toLabels, metrics::accuracy).unwrap();
+        assert!(acc > 0.8);
+        let imps = f.oobImportance(&x, &classes, toLabels, metrics::accuracy, 2, 0).unwrap();
+        assert!(imps[0].mean > 0.3);
+        assert!(imps[1].mean.abs() < 0.1 && imps[2].mean.abs() < 0.1);
+    }
+}
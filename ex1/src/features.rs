@@ -0,0 +1,639 @@
+// ******************************************************************
+//  特徴量の生成（多項式、交互作用）、欠損値の補完、カテゴリ列の数値化
+//  説明変数から x², x³, ..., xᵢ*xⱼ などの列を作って線形モデルで曲線を当てはめられるようにする
+// ******************************************************************
+
+#![allow(non_snake_case)]
+
+use super::U;   // main.rsのコメントを参照
+
+// 生成する列1つ分．(元の列番号, 次数)の積．(ex) [(0, 2)] = x0², [(0, 1), (3, 1)] = x0*x3
+type Term = Vec<(usize, u32)>;
+
+// =================================================
+//  多項式特徴量
+//  元の列 x1..xn に加えて、各列の2乗..degree乗と（指定すれば）2列以上の積で次数の合計がdegree以下の
+//  全ての項(x1*x2, x1²*x2, x1*x2*x3, ...)を作る
+// =================================================
+pub struct Polynomial {
+    degree: u32,        // 最大次数（積の項は次数の合計）
+    interactions: bool, // 2列以上の積(交互作用項)を加えるか
+    keep: Vec<usize>,   // べき乗、積に使わない列（元の列としてだけ残す）
+
+    // fitで決めた生成列
+    terms: Vec<Term>,
+    names: Vec<String>
+}
+
+impl Polynomial {
+    // @param degree 各列の最大次数（1なら元の列のみ）
+    pub fn new(degree: u32) -> Self {
+        Polynomial {
+            degree,
+            interactions: false,
+            keep: vec![],
+            terms: vec![],
+            names: vec![]
+        }
+    }
+
+    // 2列以上の積(交互作用項)を加える．次数の合計がdegree（1なら2）以下の全ての積
+    pub fn interactions(mut self, interactions: bool) -> Self {
+        self.interactions = interactions;
+        self
+    }
+
+    // べき乗、積に使わない列を指定する（元の列としてだけ残す）
+    // 水準の番号を持つカテゴリ列（--encoding native）は2乗や積に意味がないので指定する
+    // (ex) let p = features::Polynomial::new(2).keepColumns(&[11]);
+    pub fn keepColumns(mut self, cols: &[usize]) -> Self {
+        self.keep = cols.to_vec();
+        self
+    }
+
+    // ==========================================================
+    //  生成する列とその名前を決める
+    //  並びは 元の列, 各列のべき乗(列ごとに2..degree乗), 交互作用項(次数の合計の順に x0*x1, x0*x2, ..., x1*x2, ...,
+    //  x0²*x1, x0*x1², x0*x1*x2, ...)
+    //
+    //  @param nExpVars 元の説明変数の列数
+    //  @param names 元の説明変数の列名（長さが合わなければ"x1", "x2", ...とする）
+    // ==========================================================
+    pub fn fit(&mut self, nExpVars: usize, names: &[String]) {
+        let baseNames: Vec<String> = if names.len() == nExpVars {
+            names.to_vec()
+        } else {
+            (1..=nExpVars).map(|i| format!("x{}", i)).collect()
+        };
+
+        self.terms = vec![];
+        for j in 0..nExpVars {
+            self.terms.push(vec![(j, 1)]);
+        }
+        let expanded: Vec<usize> = (0..nExpVars).filter(|j| !self.keep.contains(j)).collect();
+        for j in expanded.iter().cloned() {
+            for k in 2..=self.degree {
+                self.terms.push(vec![(j, k)]);
+            }
+        }
+        if self.interactions {
+            for total in 2..=self.degree.max(2) {
+                for combo in multisets(&expanded, total as usize) {
+                    let term = toTerm(&combo);
+                    if term.len() >= 2 {    // 1列だけのべき乗は上で加えた
+                        self.terms.push(term);
+                    }
+                }
+            }
+        }
+
+        self.names = self.terms.iter().map(|term| {
+            let factors: Vec<String> = term.iter().map(|(j, k)| {
+                if *k == 1 { baseNames[*j].clone() } else { format!("{}^{}", baseNames[*j], k) }
+            }).collect();
+            factors.join("*")
+        }).collect();
+    }
+
+    // ==========================================================
+    //  説明変数から生成列を計算する（fitで決めた並び）
+    //
+    //  @param x 説明変数(2次元配列. 変数(=列)ごとの値)
+    //
+    //  @return 生成後の説明変数
+    // ==========================================================
+    pub fn transform(&self, x: &U::Matrix) -> U::Matrix {
+        let nrows = x[0].len();
+        self.terms.iter().map(|term| {
+            (0..nrows).map(|irow| {
+                term.iter().map(|(j, k)| x[*j][irow].powi(*k as i32)).product()
+            }).collect()
+        }).collect()
+    }
+
+    // fitとtransformを続けて行う
+    pub fn fitTransform(&mut self, x: &U::Matrix, names: &[String]) -> U::Matrix {
+        self.fit(x.len(), names);
+        self.transform(x)
+    }
+
+    // 生成後の列名
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+}
+
+// -----------------------------------------------
+// colsから重複を許してk個選ぶ組み合わせ（各組み合わせは昇順．組み合わせは辞書順）
+// (ex) multisets(&[0, 1], 2) = [[0, 0], [0, 1], [1, 1]]
+// -----------------------------------------------
+fn multisets(cols: &[usize], k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![vec![]];
+    }
+    let mut result = vec![];
+    for (a, c) in cols.iter().enumerate() {
+        for rest in multisets(&cols[a..], k - 1) {
+            let mut combo = vec![*c];
+            combo.extend(rest);
+            result.push(combo);
+        }
+    }
+    result
+}
+
+// 昇順の列番号の並びを(列番号, 次数)の積にする (ex) [0, 0, 3] -> [(0, 2), (3, 1)]
+fn toTerm(cols: &[usize]) -> Term {
+    let mut term: Term = vec![];
+    for c in cols {
+        match term.last_mut() {
+            Some((j, k)) if j == c => { *k += 1; },
+            _ => term.push((*c, 1))
+        }
+    }
+    term
+}
+
+// =================================================
+//  欠損値(NaN)の補完方法
+// =================================================
+#[derive(Clone, Copy, Debug)]
+pub enum ImputeStrategy {
+    Mean,           // 列の平均
+    Median,         // 列の中央値
+    MostFrequent,   // 列で最も多く現れる値（同数なら小さい方）
+    Constant(f64)   // 指定した値
+}
+
+// 文字列から補完方法を作る（コマンドラインオプション用）
+// "mean", "median", "mostfrequent", "constant:value"
+impl std::str::FromStr for ImputeStrategy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let elms: Vec<&str> = s.split(':').collect();
+        match (elms[0], elms.len()) {
+            ("mean", 1) => Ok(ImputeStrategy::Mean),
+            ("median", 1) => Ok(ImputeStrategy::Median),
+            ("mostfrequent", 1) => Ok(ImputeStrategy::MostFrequent),
+            ("constant", 2) => elms[1].parse::<f64>().map(ImputeStrategy::Constant).map_err(|_| ()),
+            _ => Err(())
+        }
+    }
+}
+
+// =================================================
+//  欠損値の補完
+//  fitで列ごとの補完値を求め、transformで欠損値を置き換える
+// =================================================
+pub struct Imputer {
+    strategy: ImputeStrategy,
+    fill: Vec<f64>      // 列ごとの補完値
+}
+
+impl Imputer {
+    pub fn new(strategy: ImputeStrategy) -> Self {
+        Imputer {
+            strategy,
+            fill: vec![]
+        }
+    }
+
+    // ==========================================================
+    //  列ごとの補完値を求める（欠損値を除いた値から）
+    //  全て欠損値の列は0で補完する
+    //
+    //  @param x 説明変数(2次元配列. 変数(=列)ごとの値)
+    // ==========================================================
+    pub fn fit(&mut self, x: &U::Matrix) {
+        self.fill = x.iter().map(|col| {
+            let present: Vec<f64> = col.iter().cloned().filter(|v| !v.is_nan()).collect();
+            if present.is_empty() {
+                return 0.0;
+            }
+            match self.strategy {
+                ImputeStrategy::Mean => U::mean(&present),
+                ImputeStrategy::Median => U::median(&present),
+                ImputeStrategy::MostFrequent => mostFrequent(&present),
+                ImputeStrategy::Constant(v) => v
+            }
+        }).collect();
+    }
+
+    // 欠損値を補完値に置き換える
+    pub fn transform(&self, x: &U::Matrix) -> U::Matrix {
+        x.iter().zip(self.fill.iter())
+            .map(|(col, f)| col.iter().map(|v| if v.is_nan() { *f } else { *v }).collect())
+            .collect()
+    }
+
+    // fitとtransformを続けて行う
+    pub fn fitTransform(&mut self, x: &U::Matrix) -> U::Matrix {
+        self.fit(x);
+        self.transform(x)
+    }
+
+    // 列ごとの補完値
+    pub fn fillValues(&self) -> &[f64] {
+        &self.fill
+    }
+}
+
+// 最も多く現れる値（同数なら小さい方）
+fn mostFrequent(v: &[f64]) -> f64 {
+    let mut sorted: Vec<f64> = v.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let mut best = sorted[0];
+    let mut bestCount = 0;
+    let mut i = 0;
+    while i < sorted.len() {
+        let mut j = i;
+        while j < sorted.len() && sorted[j] == sorted[i] {
+            j += 1;
+        }
+        if j - i > bestCount {
+            best = sorted[i];
+            bestCount = j - i;
+        }
+        i = j;
+    }
+    best
+}
+
+// =================================================
+//  カテゴリ列の数値化の方法
+// =================================================
+#[derive(Clone, Copy, Debug)]
+pub enum Encoding {
+    OneHot,     // 水準ごとの0/1の列（列名は"列名=水準"）
+    Ordinal,    // 水準の番号（水準の昇順）
+    Native,     // 水準の番号のまま渡し、決定木が水準の集合で分割する（決定木以外ではOrdinalと同じ）
+    Target(f64) // 水準ごとの目的変数の平均（全体の平均を指定の行数分混ぜて平滑化する）
+}
+
+// 文字列から数値化の方法を作る（コマンドラインオプション用）
+// "onehot", "ordinal", "native", "target", "target:smoothing"（smoothingの省略時は1）
+impl std::str::FromStr for Encoding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let elms: Vec<&str> = s.split(':').collect();
+        match (elms[0], elms.len()) {
+            ("onehot", 1) => Ok(Encoding::OneHot),
+            ("ordinal", 1) => Ok(Encoding::Ordinal),
+            ("native", 1) => Ok(Encoding::Native),
+            ("target", 1) => Ok(Encoding::Target(1.0)),
+            ("target", 2) => elms[1].parse::<f64>().map(Encoding::Target).map_err(|_| ()),
+            _ => Err(())
+        }
+    }
+}
+
+// =================================================
+//  transformの誤り
+// =================================================
+#[derive(Debug)]
+pub enum EncodeError {
+    Columns { expected: usize, found: usize },  // fitと列数が違う
+    Kind { column: String, expected: U::ColKind, found: U::ColKind }    // fitと列の種類が違う
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EncodeError::Columns { expected, found } =>
+                write!(f, "{} columns but the encoder was fitted on {}", found, expected),
+            EncodeError::Kind { column, expected, found } =>
+                write!(f, "column {} is {:?} but was {:?} when the encoder was fitted", column, found, expected)
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+// fitで決めた列1つ分の変換
+enum ColumnPlan {
+    Keep,                           // 数値列はそのまま
+    Drop,                           // テキスト列は使わない
+    Categorical(Vec<String>, Vec<f64>)   // カテゴリ列(fitで見た水準, 水準ごとのtarget encodingの値)
+}
+
+// =================================================
+//  カテゴリ列の数値化
+//  CSVの説明変数の列を、数値列はそのまま、カテゴリ列は指定の方法で数値にし、テキスト列は除いた行列にする
+//  水準は文字列で覚えるので、fitと異なるCSV（水準の辞書が異なる）もtransformできる
+//  欠損値はOneHotでは全ての列を0、Ordinal, TargetではNaNのままにする（補完や決定木の欠損値の扱いに任せる）
+//  fitで見なかった水準はOneHotでは全ての列を0、OrdinalではNaN、Targetでは全体の平均にする
+// =================================================
+pub struct CategoricalEncoder {
+    encoding: Encoding,
+
+    // fitで決めた変換
+    plans: Vec<ColumnPlan>,
+    prior: f64,         // 目的変数全体の平均（Targetで見なかった水準に使う）
+    names: Vec<String>
+}
+
+impl CategoricalEncoder {
+    pub fn new(encoding: Encoding) -> Self {
+        CategoricalEncoder {
+            encoding,
+            plans: vec![],
+            prior: 0.0,
+            names: vec![]
+        }
+    }
+
+    // ==========================================================
+    //  列ごとの変換と変換後の列名を決める
+    //
+    //  @param x 説明変数の列だけを持つCSV（splitXYで切り出したもの）
+    //  @param y 目的変数（Targetのみ使う．NaNの行は除いて平均する）
+    // ==========================================================
+    pub fn fit(&mut self, x: &U::CSV, y: &[f64]) {
+        let present: Vec<f64> = y.iter().cloned().filter(|v| !v.is_nan()).collect();
+        let prior = if present.is_empty() { 0.0 } else { U::mean(&present) };
+        let smoothing = match self.encoding {
+            Encoding::Target(m) => m,
+            _ => 0.0
+        };
+
+        self.plans = vec![];
+        self.names = vec![];
+        for (j, name) in x.colNames.iter().enumerate() {
+            match x.kinds[j] {
+                U::ColKind::Numeric => {
+                    self.plans.push(ColumnPlan::Keep);
+                    self.names.push(name.clone());
+                },
+                U::ColKind::Text => {
+                    self.plans.push(ColumnPlan::Drop);
+                },
+                U::ColKind::Categorical => {
+                    let levels = x.levels[j].clone();
+                    let mut sums = vec![0.0; levels.len()];
+                    let mut counts = vec![0.0; levels.len()];
+                    for (code, yi) in x.cols[j].iter().zip(y.iter()) {
+                        if !code.is_nan() && !yi.is_nan() {
+                            sums[*code as usize] += yi;
+                            counts[*code as usize] += 1.0;
+                        }
+                    }
+                    let means: Vec<f64> = sums.iter().zip(counts.iter())
+                        .map(|(s, n)| if *n + smoothing > 0.0 { (s + smoothing * prior) / (n + smoothing) } else { prior })
+                        .collect();
+
+                    match self.encoding {
+                        Encoding::OneHot => {
+                            for level in &levels {
+                                self.names.push(format!("{}={}", name, level));
+                            }
+                        },
+                        Encoding::Ordinal | Encoding::Native | Encoding::Target(_) => {
+                            self.names.push(name.clone());
+                        }
+                    }
+                    self.plans.push(ColumnPlan::Categorical(levels, means));
+                }
+            }
+        }
+        self.prior = prior;
+    }
+
+    // ==========================================================
+    //  CSVの説明変数の列を数値の行列にする（fitで決めた並び）
+    //
+    //  @param x 説明変数の列だけを持つCSV（fitと同じ列の並び、種類）
+    //
+    //  @return 変換後の説明変数．列数か列の種類がfitと違えばEncodeError
+    // ==========================================================
+    pub fn transform(&self, x: &U::CSV) -> Result<U::Matrix, EncodeError> {
+        if x.cols.len() != self.plans.len() {
+            return Err(EncodeError::Columns { expected: self.plans.len(), found: x.cols.len() });
+        }
+
+        let mut out: U::Matrix = vec![];
+        for (j, plan) in self.plans.iter().enumerate() {
+            // 水準の番号は種類がカテゴリの列にしかないので、fitと種類が違えば変換できない
+            let expected = match plan {
+                ColumnPlan::Keep => U::ColKind::Numeric,
+                ColumnPlan::Drop => U::ColKind::Text,
+                ColumnPlan::Categorical(_, _) => U::ColKind::Categorical
+            };
+            let found = x.kinds.get(j).cloned().unwrap_or(U::ColKind::Numeric);
+            if found != expected {
+                return Err(EncodeError::Kind { column: x.colNames[j].clone(), expected, found });
+            }
+
+            match plan {
+                ColumnPlan::Keep => out.push(x.cols[j].clone()),
+                ColumnPlan::Drop => {},
+                ColumnPlan::Categorical(levels, means) => {
+                    // xの水準の番号 -> fitで見た水準の番号
+                    let toFitted: Vec<Option<usize>> = x.levels[j].iter()
+                        .map(|l| levels.iter().position(|k| k == l))
+                        .collect();
+                    let codes: Vec<Option<usize>> = x.cols[j].iter()
+                        .map(|c| if c.is_nan() { None } else { toFitted[*c as usize] })
+                        .collect();
+
+                    match self.encoding {
+                        Encoding::OneHot => {
+                            for k in 0..levels.len() {
+                                out.push(codes.iter().map(|c| if *c == Some(k) { 1.0 } else { 0.0 }).collect());
+                            }
+                        },
+                        Encoding::Ordinal | Encoding::Native => {
+                            out.push(codes.iter().map(|c| c.map_or(f64::NAN, |k| k as f64)).collect());
+                        },
+                        Encoding::Target(_) => {
+                            out.push(x.cols[j].iter().zip(codes.iter()).map(|(c, k)| {
+                                match k {
+                                    Some(k) => means[*k],
+                                    None => if c.is_nan() { f64::NAN } else { self.prior }
+                                }
+                            }).collect());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+
+    // fitとtransformを続けて行う
+    pub fn fitTransform(&mut self, x: &U::CSV, y: &[f64]) -> Result<U::Matrix, EncodeError> {
+        self.fit(x, y);
+        self.transform(x)
+    }
+
+    // 変換後の列名
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    // Nativeの場合、水準の番号のまま渡すカテゴリ列（変換後の列番号）．他の方法では空
+    pub fn nativeColumns(&self) -> Vec<usize> {
+        let mut cols = vec![];
+        let mut j = 0;
+        for plan in &self.plans {
+            match plan {
+                ColumnPlan::Keep => { j += 1; },
+                ColumnPlan::Drop => {},
+                ColumnPlan::Categorical(levels, _) => {
+                    match self.encoding {
+                        Encoding::Native => { cols.push(j); j += 1; },
+                        Encoding::OneHot => { j += levels.len(); },
+                        _ => { j += 1; }
+                    }
+                }
+            }
+        }
+        cols
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn polynomialTerms() {
+        let mut p = Polynomial::new(3).interactions(true);
+        let x: U::Matrix = vec![vec![2.0, 1.0], vec![3.0, -1.0]];
+        let t = p.fitTransform(&x, &names(&["a", "b"]));
+        assert_eq!(p.names(), &names(&["a", "b", "a^2", "a^3", "b^2", "b^3", "a*b", "a^2*b", "a*b^2"])[..]);
+        let row0: Vec<f64> = t.iter().map(|col| col[0]).collect();
+        assert_eq!(row0, vec![2.0, 3.0, 4.0, 8.0, 9.0, 27.0, 6.0, 12.0, 18.0]);
+
+        // 交互作用なし、列名の長さが合わない場合
+        let mut p = Polynomial::new(2);
+        p.fit(2, &[]);
+        assert_eq!(p.names(), &names(&["x1", "x2", "x1^2", "x2^2"])[..]);
+    }
+
+    #[test]
+    fn interactionsCoverEveryMonomial() {
+        // 3列、次数3: 積の項は次数2が3個、次数3が(3列から重複を許して3個選ぶ10通り - べき乗3個) = 7個
+        let mut p = Polynomial::new(3).interactions(true);
+        p.fit(3, &[]);
+        assert_eq!(p.names().len(), 3 + 6 + 3 + 7);
+        assert!(p.names().contains(&String::from("x1*x2*x3")));
+
+        // 次数1でも2列の積は作る
+        let mut p = Polynomial::new(1).interactions(true);
+        p.fit(2, &[]);
+        assert_eq!(p.names(), &names(&["x1", "x2", "x1*x2"])[..]);
+    }
+
+    #[test]
+    fn imputerStrategies() {
+        let x: U::Matrix = vec![vec![1.0, f64::NAN, 2.0, 2.0, 7.0], vec![f64::NAN; 5]];
+        let fill = |strategy| {
+            let mut imp = Imputer::new(strategy);
+            imp.fit(&x);
+            imp.fillValues().to_vec()
+        };
+        assert_eq!(fill(ImputeStrategy::Mean), vec![3.0, 0.0]);
+        assert_eq!(fill(ImputeStrategy::Median), vec![2.0, 0.0]);
+        assert_eq!(fill(ImputeStrategy::MostFrequent), vec![2.0, 0.0]);
+        assert_eq!(fill(ImputeStrategy::Constant(-1.0)), vec![-1.0, 0.0]);
+
+        let mut imp = Imputer::new(ImputeStrategy::Mean);
+        assert_eq!(imp.fitTransform(&x), vec![vec![1.0, 3.0, 2.0, 2.0, 7.0], vec![0.0; 5]]);
+    }
+
+    #[test]
+    fn keptColumnsAreNotExpanded() {
+        let mut p = Polynomial::new(2).interactions(true).keepColumns(&[1]);
+        p.fit(3, &names(&["a", "b", "c"]));
+        assert_eq!(p.names(), &names(&["a", "b", "c", "a^2", "c^2", "a*c"])[..]);
+    }
+
+    // 列ごとに(列名, 種類, セルの内容)を与えてCSVを作る（カテゴリ列の水準は現れた順）
+    fn table(columns: &[(&str, U::ColKind, &[&str])]) -> U::CSV {
+        let mut csv = U::CSV::new();
+        for (name, kind, cells) in columns {
+            let mut levels: Vec<String> = vec![];
+            let col: Vec<f64> = cells.iter().map(|c| match kind {
+                U::ColKind::Numeric => c.parse::<f64>().unwrap_or(f64::NAN),
+                U::ColKind::Categorical if c.is_empty() => f64::NAN,
+                U::ColKind::Categorical => {
+                    if !levels.iter().any(|l| l == c) {
+                        levels.push(c.to_string());
+                    }
+                    levels.iter().position(|l| l == c).unwrap() as f64
+                },
+                U::ColKind::Text => f64::NAN
+            }).collect();
+            csv.nrows = col.len() as u32;
+            csv.cols.push(col);
+            csv.colNames.push(name.to_string());
+            csv.kinds.push(*kind);
+            csv.levels.push(levels);
+            csv.texts.push(if *kind == U::ColKind::Text { names(cells) } else { vec![] });
+        }
+        csv.ncols = columns.len() as u32;
+        csv
+    }
+
+    #[test]
+    fn oneHotAndOrdinal() {
+        let x = table(&[("n", U::ColKind::Numeric, &["1", "2", "3"]), ("c", U::ColKind::Categorical, &["b", "a", ""]),
+            ("t", U::ColKind::Text, &["p", "q", "r"])]);
+        let mut enc = CategoricalEncoder::new(Encoding::OneHot);
+        let m = enc.fitTransform(&x, &[0.0; 3]).unwrap();
+        assert_eq!(enc.names(), &names(&["n", "c=b", "c=a"])[..]);
+        assert_eq!(m, vec![vec![1.0, 2.0, 3.0], vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0]]);
+        assert!(enc.nativeColumns().is_empty());
+
+        let mut enc = CategoricalEncoder::new(Encoding::Native);
+        let m = enc.fitTransform(&x, &[0.0; 3]).unwrap();
+        assert_eq!(enc.names(), &names(&["n", "c"])[..]);
+        assert_eq!(m[1][..2], [0.0, 1.0]);
+        assert!(m[1][2].is_nan());
+        assert_eq!(enc.nativeColumns(), vec![1]);
+    }
+
+    #[test]
+    fn transformMapsLevelsByName() {
+        // 水準の辞書が違うCSVでも水準の文字列で対応させる．見なかった水準はOneHotで0、Ordinalで欠損値、Targetで全体の平均
+        let fitted = table(&[("c", U::ColKind::Categorical, &["a", "b", "b"])]);
+        let other = table(&[("c", U::ColKind::Categorical, &["z", "b", "a"])]);
+
+        let mut enc = CategoricalEncoder::new(Encoding::OneHot);
+        enc.fit(&fitted, &[0.0; 3]);
+        assert_eq!(enc.transform(&other).unwrap(), vec![vec![0.0, 0.0, 1.0], vec![0.0, 1.0, 0.0]]);
+
+        let mut enc = CategoricalEncoder::new(Encoding::Ordinal);
+        enc.fit(&fitted, &[0.0; 3]);
+        let m = enc.transform(&other).unwrap();
+        assert!(m[0][0].is_nan());
+        assert_eq!(m[0][1..], [1.0, 0.0]);
+
+        // 平滑化1: (水準の合計 + 1 * 全体の平均) / (水準の行数 + 1)
+        let mut enc = CategoricalEncoder::new(Encoding::Target(1.0));
+        enc.fit(&fitted, &[3.0, 6.0, 9.0]);
+        assert_eq!(enc.transform(&other).unwrap(), vec![vec![6.0, (15.0 + 6.0) / 3.0, (3.0 + 6.0) / 2.0]]);
+    }
+
+    #[test]
+    fn transformChecksColumns() {
+        let fitted = table(&[("a", U::ColKind::Numeric, &["1"]), ("c", U::ColKind::Categorical, &["x"])]);
+        let mut enc = CategoricalEncoder::new(Encoding::Ordinal);
+        enc.fit(&fitted, &[0.0]);
+
+        let fewer = table(&[("a", U::ColKind::Numeric, &["1"])]);
+        assert!(matches!(enc.transform(&fewer), Err(EncodeError::Columns { expected: 2, found: 1 })));
+
+        // 同じ名前でも数値列になっていれば水準の番号として読めない
+        let numeric = table(&[("a", U::ColKind::Numeric, &["1"]), ("c", U::ColKind::Numeric, &["0"])]);
+        let e = enc.transform(&numeric).err().unwrap();
+        assert_eq!(e.to_string(), "column c is Numeric but was Categorical when the encoder was fitted");
+    }
+}
@@ -0,0 +1,94 @@
+// ******************************************************************
+//  並べ替えによる特徴量の重要度(permutation importance)
+//  ある列の値だけを行の間で並べ替えて予測し、スコアがどれだけ下がるかをその列の重要度とする
+// ******************************************************************
+
+#![allow(non_snake_case)]
+
+use super::U;   // main.rsのコメントを参照
+use super::parallel;
+use super::random;
+use super::search::Scorer;
+
+// =================================================
+//  重要度（1列分）
+// =================================================
+pub struct Importance {
+    pub col: usize,     // 説明変数（の列番号）
+    pub mean: f64,      // スコアの低下量の平均（大きいほど重要）
+    pub std: f64        // スコアの低下量の標準偏差
+}
+
+// ============================================================
+//  列ごとの重要度を求める
+//  j列目の並べ替えにはderiveSeed(seed, j)の乱数を使うので、列ごとに並列に計算しても
+//  同じシードなら同じ結果になる
+//
+//  @param x 説明変数
+//  @param y 目的変数（スコアの計算に使う1列）
+//  @param predict 学習済みのモデルでxの予測値を返す関数（モデルの誤りはErr）
+//  @param scorer スコア関数（大きいほど良い）
+//  @param repeats 1列あたりの並べ替えの回数
+//  @param seed 乱数のシード
+//  @param threads スレッド数
+//
+//  @return 列ごとの重要度（列の順）．predictがErrを返せばそのErr
+// ============================================================
+pub fn permutationImportance<F, E>(x: &U::Matrix, y: &[f64], predict: F, scorer: Scorer, repeats: usize, seed: u64, threads: usize) -> Result<Vec<Importance>, E>
+    where F: Fn(&U::Matrix) -> Result<Vec<f64>, E> + Sync, E: Send
+{
+    let baseline = scorer(y, &predict(x)?);
+
+    parallel::map(x.len(), threads, |j| {
+        let mut rng = random::Rng::new(random::deriveSeed(seed, j as u64));
+        let mut xp = x.clone();
+        let mut drops: Vec<f64> = vec![];
+        for _ in 0..repeats.max(1) {
+            let order = rng.permutation(y.len());
+            xp[j] = order.iter().map(|i| x[j][*i]).collect();
+            drops.push(baseline - scorer(y, &predict(&xp)?));
+        }
+        Ok(Importance {
+            col: j,
+            mean: U::mean(&drops),
+            std: U::stdev(&drops)
+        })
+    }).into_iter().collect()
+}
+
+// -----------------------------------------------
+// 重要度の大きい順に表示する
+//
+// @param importances permutationImportance()の結果
+// @param names 説明変数の列名
+// -----------------------------------------------
+pub fn printImportances(importances: &[Importance], names: &[String]) {
+    let mut order: Vec<&Importance> = importances.iter().collect();
+    order.sort_by(|a, b| b.mean.partial_cmp(&a.mean).unwrap_or(std::cmp::Ordering::Equal));
+    println!("{:>5}{:>14}{:>12}  column", "rank", "importance", "std");
+    for (i, imp) in order.iter().enumerate() {
+        println!("{:>5}{:>14.6}{:>12.6}  {}", i+1, imp.mean, imp.std, names[imp.col]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unusedColumnHasNoImportance() {
+        // 予測に1列目しか使わないモデル
+        let x: U::Matrix = vec![(0..30).map(|i| i as f64).collect(), (0..30).map(|i| ((i * 7) % 30) as f64).collect()];
+        let y: Vec<f64> = x[0].iter().map(|v| v * 2.0).collect();
+        let predict = |m: &U::Matrix| -> Result<Vec<f64>, ()> { Ok(m[0].iter().map(|v| v * 2.0).collect()) };
+        let imps = permutationImportance(&x, &y, predict, crate::metrics::negMse, 3, 5, 1).unwrap();
+        assert_eq!(imps.len(), 2);
+        assert!(imps[0].mean > 0.0);
+        assert_eq!((imps[1].mean, imps[1].std), (0.0, 0.0));
+
+        // 同じシードならスレッド数によらず同じ
+        let again = permutationImportance(&x, &y, predict, crate::metrics::negMse, 3, 5, 2).unwrap();
+        assert_eq!(imps[0].mean, again[0].mean);
+        assert_eq!(imps[0].std, again[0].std);
+    }
+}
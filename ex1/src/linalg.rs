@@ -0,0 +1,219 @@
+// ******************************************************************
+//  線形代数（最小二乗法の厳密解用）
+//  Cholesky分解、QR分解（Householder変換、列ピボット付き）
+// ******************************************************************
+
+#![allow(non_snake_case)]
+
+use super::U;   // main.rsのコメントを参照
+
+// 特異（ランク落ち）とみなす対角成分の相対的な大きさ
+const RANK_EPS: f64 = 1e-10;
+
+// 正方行列．SqMatrix[i][j] = i行j列（U::Matrixとは逆の行優先）
+pub type SqMatrix = Vec<Vec<f64>>;
+
+// -----------------------------------------------
+// Cholesky分解 A = LLᵀ
+//
+// @param a 対称正定値行列
+//
+// @return 下三角行列L．正定値でない（数値的に特異な）場合はNone
+// -----------------------------------------------
+pub fn cholesky(a: &SqMatrix) -> Option<SqMatrix> {
+    let n = a.len();
+    let maxDiag = (0..n).map(|i| a[i][i].abs()).fold(0.0, f64::max);
+    let mut l: SqMatrix = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..=i {
+            let s: f64 = (0..j).map(|k| l[i][k] * l[j][k]).sum();
+            if i == j {
+                let d = a[i][i] - s;
+                if d <= RANK_EPS * maxDiag {
+                    return None;
+                }
+                l[i][i] = d.sqrt();
+            } else {
+                l[i][j] = (a[i][j] - s) / l[j][j];
+            }
+        }
+    }
+    Some(l)
+}
+
+// -----------------------------------------------
+// Cholesky分解の結果を使って LLᵀx = b を解く
+//
+// @param l cholesky()の結果
+// @param b 右辺
+// -----------------------------------------------
+pub fn choleskySolve(l: &SqMatrix, b: &[f64]) -> Vec<f64> {
+    let n = l.len();
+    // Lz = b（前進代入）
+    let mut z: Vec<f64> = vec![0.0; n];
+    for i in 0..n {
+        let s: f64 = (0..i).map(|k| l[i][k] * z[k]).sum();
+        z[i] = (b[i] - s) / l[i][i];
+    }
+    // Lᵀx = z（後退代入）
+    let mut x: Vec<f64> = vec![0.0; n];
+    for i in (0..n).rev() {
+        let s: f64 = (i+1..n).map(|k| l[k][i] * x[k]).sum();
+        x[i] = (z[i] - s) / l[i][i];
+    }
+    x
+}
+
+// -----------------------------------------------
+// Cholesky分解の結果を使って逆行列 A⁻¹ を求める
+//
+// @param l cholesky()の結果
+// -----------------------------------------------
+pub fn choleskyInverse(l: &SqMatrix) -> SqMatrix {
+    let n = l.len();
+    let mut inv: SqMatrix = vec![vec![0.0; n]; n];
+    for j in 0..n {
+        // 単位ベクトルe_jについて Ax = e_j を解くと、xがA⁻¹のj列目になる
+        let mut e = vec![0.0; n];
+        e[j] = 1.0;
+        for (i, v) in choleskySolve(l, &e).into_iter().enumerate() {
+            inv[i][j] = v;
+        }
+    }
+    inv
+}
+
+// ============================================================
+//  最小二乗法 min |Ax - b|² をQR分解で解く
+//  Householder変換による列ピボット付きQR分解を使うので、
+//  列どうしの相関が強い(条件数が大きい)場合やランク落ちしている場合にも使える．
+//  ランク落ちしている場合は、独立でない列の係数を0にした解を返す
+//
+//  @param a 行列(U::Matrix: 列ごとの値)
+//  @param b ベクトル（長さ=aの行数）
+//
+//  @return 解x（長さ=aの列数）
+// ============================================================
+pub fn lstsqQr(a: &U::Matrix, b: &[f64]) -> Vec<f64> {
+    let n = a.len();
+    let m = b.len();
+    let mut r: U::Matrix = a.clone();   // 変換後にRになる(r[j][i] = Rのi行j列)
+    let mut qb: Vec<f64> = b.to_vec();  // Qᵀb
+    let mut perm: Vec<usize> = (0..n).collect();
+
+    let steps = m.min(n);
+    for k in 0..steps {
+        // 残りの列のうち、k行目以降のノルムが最大の列をk列目に持ってくる
+        let colNorm = |col: &Vec<f64>| col[k..].iter().map(|v| v * v).sum::<f64>();
+        let mut p = k;
+        for j in k+1..n {
+            if colNorm(&r[j]) > colNorm(&r[p]) {
+                p = j;
+            }
+        }
+        r.swap(k, p);
+        perm.swap(k, p);
+
+        // k列目のk行目以降を(alpha, 0, 0, ...)にするHouseholder変換
+        let norm = colNorm(&r[k]).sqrt();
+        if norm == 0.0 {
+            break;  // 残りは全て0の列
+        }
+        let alpha = if r[k][k] > 0.0 { -norm } else { norm };
+        let mut v: Vec<f64> = r[k][k..].to_vec();
+        v[0] -= alpha;
+        let vv: f64 = v.iter().map(|e| e * e).sum();
+        if vv == 0.0 {
+            continue;
+        }
+
+        // H = I - 2vvᵀ/vᵀv を残りの列とbに適用
+        for col in r.iter_mut().skip(k) {
+            let s: f64 = v.iter().zip(col[k..].iter()).map(|(p, q)| p * q).sum::<f64>() * 2.0 / vv;
+            for (e, vi) in col[k..].iter_mut().zip(v.iter()) {
+                *e -= s * vi;
+            }
+        }
+        let s: f64 = v.iter().zip(qb[k..].iter()).map(|(p, q)| p * q).sum::<f64>() * 2.0 / vv;
+        for (e, vi) in qb[k..].iter_mut().zip(v.iter()) {
+            *e -= s * vi;
+        }
+    }
+
+    // ランク（対角成分が十分大きい列の数）
+    let mut rank = 0;
+    if steps > 0 {
+        let r00 = r[0][0].abs();
+        while rank < steps && r[rank][rank].abs() > RANK_EPS * r00 {
+            rank += 1;
+        }
+    }
+
+    // Rx = Qᵀb を後退代入で解く（rank以降の係数は0）
+    let mut z: Vec<f64> = vec![0.0; n];
+    for i in (0..rank).rev() {
+        let s: f64 = (i+1..rank).map(|j| r[j][i] * z[j]).sum();
+        z[i] = (qb[i] - s) / r[i][i];
+    }
+
+    // 列の並べ替えを元に戻す
+    let mut x: Vec<f64> = vec![0.0; n];
+    for (k, p) in perm.iter().enumerate() {
+        x[*p] = z[k];
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn near(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-10
+    }
+
+    #[test]
+    fn choleskyOfKnownMatrix() {
+        let a: SqMatrix = vec![vec![4.0, 2.0], vec![2.0, 3.0]];
+        let l = cholesky(&a).unwrap();
+        assert!(near(l[0][0], 2.0) && near(l[1][0], 1.0) && near(l[1][1], 2.0f64.sqrt()) && l[0][1] == 0.0);
+
+        // 4x + 2y = 8, 2x + 3y = 8 --> (1, 2)
+        let x = choleskySolve(&l, &[8.0, 8.0]);
+        assert!(near(x[0], 1.0) && near(x[1], 2.0));
+
+        let inv = choleskyInverse(&l);
+        assert!(near(inv[0][0], 3.0 / 8.0) && near(inv[0][1], -2.0 / 8.0) && near(inv[1][1], 4.0 / 8.0));
+    }
+
+    #[test]
+    fn choleskyRejectsSingularMatrix() {
+        assert!(cholesky(&vec![vec![1.0, 1.0], vec![1.0, 1.0]]).is_none());
+    }
+
+    #[test]
+    fn qrMatchesNormalEquations() {
+        // 計画行列 [1, x], y = (1, 3, 2, 5) の最小二乗解は 切片1.1, 傾き1.1
+        let a: U::Matrix = vec![vec![1.0; 4], vec![0.0, 1.0, 2.0, 3.0]];
+        let b = [1.0, 3.0, 2.0, 5.0];
+        let qr = lstsqQr(&a, &b);
+        // 正規方程式 AᵀA β = Aᵀb
+        let g: SqMatrix = vec![vec![4.0, 6.0], vec![6.0, 14.0]];
+        let ch = choleskySolve(&cholesky(&g).unwrap(), &[11.0, 22.0]);
+        for (p, q) in qr.iter().zip(ch.iter()) {
+            assert!(near(*p, 1.1) && near(*q, 1.1));
+        }
+    }
+
+    #[test]
+    fn qrHandlesRankDeficiency() {
+        // 2列目と3列目が同じ --> どちらか一方の係数を0にして当てはめる
+        let x = vec![0.0, 1.0, 2.0, 3.0];
+        let a: U::Matrix = vec![vec![1.0; 4], x.clone(), x.clone()];
+        let b: Vec<f64> = x.iter().map(|v| 1.0 + 2.0 * v).collect();
+        let sol = lstsqQr(&a, &b);
+        assert!(near(sol[0], 1.0));
+        assert!(near(sol[1] + sol[2], 2.0));
+        assert!(sol[1] == 0.0 || sol[2] == 0.0);
+    }
+}
@@ -7,63 +7,455 @@
 #![allow(non_snake_case)]
 
 use super::U;   // main.rsのコメントを参照
+use super::random;
+use super::linalg;
+use super::stats;
+use super::dataset;
+use super::dataset::{ShapeError, View};
+
+// =================================================
+//  学習率のスケジュール（エポックごとの学習率の変え方）
+//  tはエポック番号(0始まり)
+// =================================================
+#[derive(Clone, Copy, Debug)]
+pub enum LrSchedule {
+    Constant,           // 一定
+    TimeBased(f64),     // lr / (1 + decay * t)
+    Step(u32, f64),     // n エポックごとに factor 倍
+    Exponential(f64)    // lr * gamma^t
+}
+
+impl LrSchedule {
+    // tエポック目の学習率
+    pub fn rate(&self, lr: f64, t: u32) -> f64 {
+        match *self {
+            LrSchedule::Constant => lr,
+            LrSchedule::TimeBased(decay) => lr / (1.0 + decay * (t as f64)),
+            LrSchedule::Step(n, factor) => lr * factor.powi((t / n.max(1)) as i32),
+            LrSchedule::Exponential(gamma) => lr * gamma.powi(t as i32)
+        }
+    }
+}
+
+// -----------------------------------------------
+// 文字列からスケジュールを作る（コマンドラインオプション用）
+// "const", "time:decay", "step:n:factor", "exp:gamma"
+// -----------------------------------------------
+impl std::str::FromStr for LrSchedule {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let elms: Vec<&str> = s.split(':').collect();
+        let num = |i: usize| elms.get(i).ok_or(()).and_then(|e| e.parse::<f64>().map_err(|_| ()));
+        match (elms[0], elms.len()) {
+            ("const", 1) => Ok(LrSchedule::Constant),
+            ("time", 2) => Ok(LrSchedule::TimeBased(num(1)?)),
+            ("step", 3) => Ok(LrSchedule::Step(num(1)? as u32, num(2)?)),
+            ("exp", 2) => Ok(LrSchedule::Exponential(num(1)?)),
+            _ => Err(())
+        }
+    }
+}
+
+// =================================================
+//  回帰係数の求め方
+// =================================================
+#[derive(Clone, Copy, Debug)]
+pub enum Solver {
+    Sgd,        // 確率的勾配降下法（epochs, lrなどの設定を使う）
+    Cholesky,   // 正規方程式をCholesky分解で解く（特異な場合はQrで解き直す）
+    Qr,         // QR分解で最小二乗法を解く（相関の強い説明変数があっても安定）
+    Cd          // 座標降下法（L1正則化がある場合はCholesky, Qrを指定してもこれで解く）
+}
+
+// 文字列から求め方を作る（コマンドラインオプション用）: "sgd", "cholesky", "qr", "cd"
+impl std::str::FromStr for Solver {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sgd" => Ok(Solver::Sgd),
+            "cholesky" => Ok(Solver::Cholesky),
+            "qr" => Ok(Solver::Qr),
+            "cd" => Ok(Solver::Cd),
+            _ => Err(())
+        }
+    }
+}
+
+// =================================================
+//  損失関数（r = 予測値 - 目的変数．どちらも正規化後の値）
+//  Squared以外は確率的勾配降下法でのみ学習できる（Solverの指定によらずSGDを使う）
+// =================================================
+#[derive(Clone, Copy, Debug)]
+pub enum Loss {
+    Squared,                // 二乗誤差 r²（平均を予測）
+    Huber(f64),             // |r|≦deltaなら二乗誤差、それより外は絶対誤差 (delta)
+    Absolute,               // 絶対誤差 |r|（中央値を予測）
+    Quantile(f64),          // ピンボール損失（tau分位点を予測．tau=0.1ならP10）(tau)
+    EpsilonInsensitive(f64) // |r|≦epsilonの誤差は無視する（サポートベクター回帰と同じ） (epsilon)
+}
+
+impl Loss {
+    // 損失の値
+    pub fn value(&self, r: f64) -> f64 {
+        match *self {
+            Loss::Squared => r.powf(2.0),
+            Loss::Huber(delta) => if r.abs() <= delta { r * r } else { delta * (2.0 * r.abs() - delta) },
+            Loss::Absolute => r.abs(),
+            Loss::Quantile(tau) => if r < 0.0 { -tau * r } else { (1.0 - tau) * r },
+            Loss::EpsilonInsensitive(eps) => (r.abs() - eps).max(0.0)
+        }
+    }
+
+    // 予測値を動かす向き（勾配．Squaredの係数2は学習率に含める）
+    pub fn gradient(&self, r: f64) -> f64 {
+        match *self {
+            Loss::Squared => r,
+            Loss::Huber(delta) => r.clamp(-delta, delta),
+            Loss::Absolute => sign(r),
+            Loss::Quantile(tau) => if r < 0.0 { -tau } else { 1.0 - tau },
+            Loss::EpsilonInsensitive(eps) => if r.abs() <= eps { 0.0 } else { sign(r) }
+        }
+    }
+}
+
+// rの符号(0なら0)
+fn sign(r: f64) -> f64 {
+    if r > 0.0 {
+        1.0
+    } else if r < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+// 文字列から損失関数を作る（コマンドラインオプション用）
+// "squared", "huber:delta", "absolute", "quantile:tau", "epsilon:epsilon"
+impl std::str::FromStr for Loss {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let elms: Vec<&str> = s.split(':').collect();
+        let num = |i: usize| elms.get(i).ok_or(()).and_then(|e| e.parse::<f64>().map_err(|_| ()));
+        match (elms[0], elms.len()) {
+            ("squared", 1) => Ok(Loss::Squared),
+            ("huber", 2) => Ok(Loss::Huber(num(1)?)),
+            ("absolute", 1) => Ok(Loss::Absolute),
+            ("quantile", 2) => Ok(Loss::Quantile(num(1)?)),
+            ("epsilon", 2) => Ok(Loss::EpsilonInsensitive(num(1)?)),
+            _ => Err(())
+        }
+    }
+}
+
+// 座標降下法の最大繰り返し回数と、収束とみなす係数の変化量
+const CD_MAX_ITER: u32 = 1000;
+const CD_TOL: f64 = 1e-7;
+
+// =================================================
+//  正則化（切片には掛けない）
+//  損失 = 平均二乗誤差/2 + alpha * l1_ratio * |beta|₁ + alpha * (1 - l1_ratio) * |beta|₂² / 2
+// =================================================
+#[derive(Clone, Copy, Debug)]
+pub enum Penalty {
+    None,
+    Ridge(f64),             // L2正則化 (alpha)
+    Lasso(f64),             // L1正則化 (alpha)
+    ElasticNet(f64, f64)    // L1 + L2 (alpha, l1_ratio)
+}
+
+impl Penalty {
+    // L1正則化、L2正則化の強さ --> (l1, l2)
+    pub fn strength(&self) -> (f64, f64) {
+        match *self {
+            Penalty::None => (0.0, 0.0),
+            Penalty::Ridge(alpha) => (0.0, alpha),
+            Penalty::Lasso(alpha) => (alpha, 0.0),
+            Penalty::ElasticNet(alpha, ratio) => (alpha * ratio, alpha * (1.0 - ratio))
+        }
+    }
+
+    // 種類(l1_ratio)はそのままで、alphaだけ変えたものを返す
+    pub fn withAlpha(&self, alpha: f64) -> Penalty {
+        match *self {
+            Penalty::None => Penalty::None,
+            Penalty::Ridge(_) => Penalty::Ridge(alpha),
+            Penalty::Lasso(_) => Penalty::Lasso(alpha),
+            Penalty::ElasticNet(_, ratio) => Penalty::ElasticNet(alpha, ratio)
+        }
+    }
+
+    // L1正則化の割合(l1_ratio)
+    pub fn l1Ratio(&self) -> f64 {
+        match *self {
+            Penalty::None | Penalty::Ridge(_) => 0.0,
+            Penalty::Lasso(_) => 1.0,
+            Penalty::ElasticNet(_, ratio) => ratio
+        }
+    }
+}
+
+// 文字列から正則化を作る（コマンドラインオプション用）
+// "none", "ridge:alpha", "lasso:alpha", "enet:alpha:l1_ratio"
+impl std::str::FromStr for Penalty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let elms: Vec<&str> = s.split(':').collect();
+        let num = |i: usize| elms.get(i).ok_or(()).and_then(|e| e.parse::<f64>().map_err(|_| ()));
+        match (elms[0], elms.len()) {
+            ("none", 1) => Ok(Penalty::None),
+            ("ridge", 2) => Ok(Penalty::Ridge(num(1)?)),
+            ("lasso", 2) => Ok(Penalty::Lasso(num(1)?)),
+            ("enet", 3) => Ok(Penalty::ElasticNet(num(1)?, num(2)?)),
+            _ => Err(())
+        }
+    }
+}
+
+// =================================================
+//  正規化（スケーリング）の方法
+//  どの方法でも (値 - center) / scale で変換する
+// =================================================
+#[derive(Clone, Copy, Debug)]
+pub enum Scaling {
+    MinMax,     // center=最小値, scale=最大値-最小値（0以上1以下になる）
+    ZScore,     // center=平均, scale=標準偏差
+    Robust      // center=中央値, scale=四分位範囲（外れ値の影響を受けにくい）
+}
+
+// 文字列から正規化の方法を作る（コマンドラインオプション用）: "minmax", "zscore", "robust"
+impl std::str::FromStr for Scaling {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minmax" => Ok(Scaling::MinMax),
+            "zscore" => Ok(Scaling::ZScore),
+            "robust" => Ok(Scaling::Robust),
+            _ => Err(())
+        }
+    }
+}
+
+// =================================================
+//  列ごとの正規化パラメータ
+// =================================================
+#[derive(Clone, Copy, Debug)]
+pub struct Scale {
+    pub center: f64,
+    pub scale: f64      // 0になる(全て同じ値の)列は1にしてある
+}
+
+// =================================================
+//  回帰係数のレポート（1係数分．元の単位に戻した値）
+//  標準誤差、t値、p値は厳密解(Solver::Cholesky, Solver::Qr)で求めた場合のみSome
+// =================================================
+pub struct CoefReport {
+    pub name: String,       // 列名（切片は"(intercept)"）
+    pub coef: f64,          // 係数
+    pub stdErr: Option<f64>,    // 標準誤差
+    pub tValue: Option<f64>,    // t統計量 = coef / stdErr
+    pub pValue: Option<f64>     // 係数=0の両側検定のp値
+}
 
 // =================================================
 //  線形モデル
 // =================================================
 pub struct Linear {
+    solver: Solver,         // 回帰係数の求め方
+    penalty: Penalty,       // 正則化
+    loss: Loss,             // 損失関数
     // r: Vec<f64>,
     epochs: u32,
     lr: f64,
+    schedule: LrSchedule,   // 学習率のスケジュール
+    shuffleSeed: Option<u64>,   // Someならエポックごとに行の順番をシャッフルする（シード値）
+    batchSize: usize,       // ミニバッチの大きさ（1なら1行ごとに更新）
+    tol: Option<f64>,       // Someなら、エポック間の損失の改善がこの値未満になった時点で学習を打ち切る
+
+    // エポックごとの損失(正規化後の損失関数の値の平均．デフォルトは平均二乗誤差)
+    lossHistory: Vec<f64>,
 
     // 回帰式の係数．beta[0]=切片、beta[1..]=係数
     beta: Vec<f64>,
 
+    // 回帰係数の共分散行列（正規化後の空間）と残差の自由度
+    // 厳密解で求めた場合のみSome（標準誤差の計算用）
+    covBeta: Option<(linalg::SqMatrix, f64)>,
+
+    // 正規化の方法
+    scaling: Scaling,
+
     // 正規化計算用
-    // norm[0] = 目的変数の最大、最小
-    // norm[1..] = 説明変数の最大、最小
+    // norm[0] = 目的変数の正規化パラメータ
+    // norm[1..] = 説明変数の正規化パラメータ
     // 説明変数の次元数がnのとき、norm.len() = n+1になる
-    norm: Vec<U::MinMax>
+    norm: Vec<Scale>,
+
+    // 列名．names[0] = 目的変数, names[1..] = 説明変数（norm, betaと同じ並び）
+    // 指定されなければfit時に"y", "x1", "x2", ...とする
+    names: Vec<String>,
+
+    // 2列目以降の目的変数のモデル（多出力回帰）
+    // 目的変数ごとの係数は互いに独立に求まるので、同じ設定のモデルを目的変数ごとに作る
+    outputs: Vec<Linear>,
+    outputNames: Vec<String>    // outputsの目的変数の列名
 }
 
 impl Linear {
     pub fn new() -> Self {
-        return Linear {
+        Linear {
+            solver: Solver::Sgd,
+            penalty: Penalty::None,
+            loss: Loss::Squared,
             epochs: 20,
             lr: 0.01,
+            schedule: LrSchedule::Constant,
+            shuffleSeed: None,
+            batchSize: 1,
+            tol: None,
+            lossHistory: vec![],
             beta: vec![],
-            norm: vec![]
-        };
+            covBeta: None,
+            scaling: Scaling::MinMax,
+            norm: vec![],
+            names: vec![],
+            outputs: vec![],
+            outputNames: vec![]
+        }
     }
 
-    pub fn print(&self) {
-        println!("epochs = {}", self.epochs);
-        println!("lr = {}", self.lr);
-        println!("beta = {:?}", self.beta);
-        for e in &self.norm {
-            println!("norm(min, max)=({}, {})", e.min, e.max);
+    // 回帰係数の求め方を指定する
+    // (ex) let l = linear::Linear::new().solver(linear::Solver::Qr);
+    pub fn solver(mut self, solver: Solver) -> Self {
+        self.solver = solver;
+        self
+    }
+
+    // 正規化の方法を指定する（デフォルトはmin-max）
+    pub fn scaling(mut self, scaling: Scaling) -> Self {
+        self.scaling = scaling;
+        self
+    }
+
+    // 列名を指定する（正規化パラメータや係数を列名で参照・表示するため）
+    //
+    // @param xNames 説明変数の列名
+    // @param yNames 目的変数の列名（目的変数が複数の場合は列の順に）
+    pub fn columnNames(mut self, xNames: &[String], yNames: &[String]) -> Self {
+        self.names = vec![yNames[0].clone()];
+        self.names.extend(xNames.iter().cloned());
+        self.outputNames = yNames[1..].to_vec();
+        self
+    }
+
+    // 学習設定だけが同じ（学習結果を持たない）モデルを作る
+    fn sameSettings(&self) -> Linear {
+        Linear {
+            solver: self.solver,
+            penalty: self.penalty,
+            loss: self.loss,
+            epochs: self.epochs,
+            lr: self.lr,
+            schedule: self.schedule,
+            shuffleSeed: self.shuffleSeed,
+            batchSize: self.batchSize,
+            tol: self.tol,
+            scaling: self.scaling,
+            ..Linear::new()
         }
     }
 
+    // k列目(k >= 1)の目的変数のモデル（設定と説明変数の列名が同じ）
+    fn newOutput(&self, k: usize) -> Linear {
+        let mut m = self.sameSettings();
+        m.names = vec![self.outputNames.get(k-1).cloned().unwrap_or_else(|| format!("y{}", k+1))];
+        m.names.extend(self.names[1..].iter().cloned());
+        m
+    }
+
     // ==========================================================
-    //  説明変数、目的変数の最大・最小を計算してself.normに格納
-    //  後で正規化の際に使う
-    //
-    //  @param x: 説明変数(2次元配列. 変数(=列)ごとの値)
-    //  @param y: 目的変数(2次元配列．線形モデルでは回帰しか行えないので列数は常に1)
+    //  fitで求めた正規化パラメータを列名と組にして返す
+    //  先頭が目的変数、以降が説明変数
     // ==========================================================
-    pub fn fitnorm(&mut self, x: &U::Matrix, y: &U::Matrix) {
-        self.norm = Vec::<U::MinMax>::new();
+    pub fn scaler(&self) -> Vec<(&str, Scale)> {
+        self.names.iter().map(|n| n.as_str()).zip(self.norm.iter().cloned()).collect()
+    }
 
-        // 目的変数の最大・最小
-        self.norm.push(U::calcMinMax(&y[0]));
+    // 正則化を指定する
+    // (ex) let l = linear::Linear::new().solver(linear::Solver::Cd).penalty(linear::Penalty::Lasso(0.01));
+    pub fn penalty(mut self, penalty: Penalty) -> Self {
+        self.penalty = penalty;
+        self
+    }
+
+    // 損失関数を指定する（デフォルトは二乗誤差）
+    // (ex) let l = linear::Linear::new().loss(linear::Loss::Quantile(0.9));  // P90を予測
+    pub fn loss(mut self, loss: Loss) -> Self {
+        self.loss = loss;
+        self
+    }
+
+    // 学習の繰り返し回数を指定する（Solver::Sgdの時のみ）
+    // (ex) let l = linear::Linear::new().epochs(50).lr(0.05);
+    pub fn epochs(mut self, epochs: u32) -> Self {
+        self.epochs = epochs;
+        self
+    }
+
+    // 学習率を指定する
+    pub fn lr(mut self, lr: f64) -> Self {
+        self.lr = lr;
+        self
+    }
 
-        // 説明変数の次元数--> n
-        let n = x.len();
+    // 学習率のスケジュールを指定する
+    pub fn schedule(mut self, schedule: LrSchedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    // エポックごとに行の順番をシャッフルする（同じシードなら同じ順番になる．partialFitではエポックごとにシードを派生させる）
+    pub fn shuffle(mut self, seed: u64) -> Self {
+        self.shuffleSeed = Some(seed);
+        self
+    }
+
+    // ミニバッチの大きさを指定する（0は1とみなす）
+    pub fn batchSize(mut self, batchSize: usize) -> Self {
+        self.batchSize = batchSize.max(1);
+        self
+    }
+
+    // 早期終了の閾値を指定する
+    // エポック間の損失の改善がtol未満になったら、残りのエポックを打ち切る
+    pub fn tol(mut self, tol: f64) -> Self {
+        self.tol = Some(tol);
+        self
+    }
 
-        // 説明変数の最大・最小
-        for i in 0..n {
-            self.norm.push(U::calcMinMax(&x[i]));
+    pub fn print(&self) {
+        println!("solver = {:?}", self.solver);
+        println!("penalty = {:?}", self.penalty);
+        println!("loss function = {:?}", self.loss);
+        println!("epochs = {} (trained {})", self.epochs, self.lossHistory.len());
+        println!("lr = {}", self.lr);
+        println!("schedule = {:?}", self.schedule);
+        println!("batch size = {}", self.batchSize);
+        println!("loss = {:?}", self.lossHistory.last());
+        println!("beta = {:?}", self.beta);
+        println!("scaling = {:?}", self.scaling);
+        for (name, e) in self.scaler() {
+            println!("norm {}: (center, scale)=({}, {})", name, e.center, e.scale);
+        }
+        for m in &self.outputs {
+            println!("--- target {} ---", m.names[0]);
+            println!("loss = {:?}", m.lossHistory.last());
+            println!("beta = {:?}", m.beta);
+            println!("norm {}: (center, scale)=({}, {})", m.names[0], m.norm[0].center, m.norm[0].scale);
         }
     }
 
@@ -76,43 +468,15 @@ impl Linear {
     //  @return 正規化後の説明変数、目的変数
     // ==================================================================
     pub fn normalize(&self, x: &U::Matrix, y: &U::Matrix) -> (U::Matrix, U::Matrix) {
-        // 変数の値の範囲 --> ranges
-        // ranges[0] .. 目的変数の範囲
-        // ranges[1..] .. 説明変数の範囲
-        let mut ranges = Vec::<f64>::new();
-
-        for e in &self.norm {
-            let mut range: f64 = e.max - e.min;
-            if range == 0.0 {
-                range = 1.0;
-            }
-            ranges.push(range);
-        }
-
-        // println!("ranges.len() = {}", ranges.len());
-        // println!("nrow(x) = {}", x[0].len());
-
         // 説明変数を正規化
-        let mut expVars = U::Matrix::new();
-
-        let nExpVars = x.len();
-        for jcol in 0..nExpVars {
-            let xx = &x[jcol];
-            let mut expVar = Vec::<f64>::new();
-            let nrow = xx.len();    // 説明変数xxの行数 --> nrow
-            for i in 0..nrow {
-                expVar.push((xx[i] - self.norm[jcol+1].min) / ranges[jcol+1]);   // self.normは最初に目的変数が入っているので+1
-            }
-            expVars.push(expVar);
-        }
+        let expVars = applyScales(x, &self.norm[1..]);  // self.normは最初に目的変数が入っているので1から
 
         // 目的変数を正規化
         let mut objVars = U::Matrix::new();
         let mut objVar = Vec::<f64>::new();
-        if y.len() > 0 {
-            let nrow = y[0].len();
-            for i in 0..nrow {
-                objVar.push((y[0][i] - self.norm[0].min) / ranges[0]);
+        if !y.is_empty() {
+            for v in y[0].iter() {
+                objVar.push((v - self.norm[0].center) / self.norm[0].scale);
             }
         }
         objVars.push(objVar);
@@ -124,55 +488,694 @@ impl Linear {
     //  モデル作成
     //
     //  @param x: 説明変数(2次元配列. 変数(=列)ごとの値)
-    //  @param y: 目的変数(2次元配列．線形モデルでは回帰しか行えないので列数は常に1)
+    //  @param y: 目的変数(2次元配列．列数>1なら目的変数ごとに係数を求める)
+    //
+    //  @return 列の長さが揃っていなければShapeError
+    // ============================================
+    pub fn fit(&mut self, x: &U::Matrix, y: &U::Matrix) -> Result<(), ShapeError> {
+        self.fitMatrix(x, y, None)
+    }
+
+    // ============================================
+    //  行ごとの重みを付けてモデルを作成する
+    //  損失(勾配、残差平方和)を行ごとに重み付けする．重みが全て1ならfitと同じ
+    //
+    //  @param x: 説明変数
+    //  @param y: 目的変数
+    //  @param weights: 行ごとの重み（長さ=行数, 0以上）
+    //
+    //  @return 列の長さ、重みの数が揃っていなければShapeError
+    // ============================================
+    pub fn fitWeighted(&mut self, x: &U::Matrix, y: &U::Matrix, weights: &[f64]) -> Result<(), ShapeError> {
+        self.fitMatrix(x, y, Some(weights))
+    }
+
+    // fit, fitWeightedの本体．行列から列名付きのデータセットを作ってfitViewで学習する
+    fn fitMatrix(&mut self, x: &U::Matrix, y: &U::Matrix, weights: Option<&[f64]>) -> Result<(), ShapeError> {
+        let (xd, yd) = dataset::fromMatrices(x, y)?;
+        let (xNames, yNames) = self.matrixNames(x.len(), y.len());
+        let (xd, yd) = (xd.names(&xNames)?, yd.names(&yNames)?);
+        self.fitView(&xd.view(), &yd.view(), weights)
+    }
+
+    // -----------------------------------------------
+    //  行列で学習する場合の説明変数、目的変数の列名
+    //  columnNames()で指定した名前（指定がない、または列数が合わない場合は"y", "x1", "x2", ...）
+    // -----------------------------------------------
+    fn matrixNames(&self, nx: usize, ny: usize) -> (Vec<String>, Vec<String>) {
+        let given = self.names.len() == nx + 1;
+        let xNames: Vec<String> = if given {
+            self.names[1..].to_vec()
+        } else {
+            (1..=nx).map(|i| format!("x{}", i)).collect()
+        };
+        let mut yNames = vec![if given { self.names[0].clone() } else { String::from("y") }];
+        yNames.extend((1..ny).map(|k| self.outputNames.get(k-1).cloned().unwrap_or_else(|| format!("y{}", k+1))));
+        (xNames, yNames)
+    }
+
     // ============================================
-    pub fn fit(&mut self, x: &U::Matrix, y: &U::Matrix) {
-        // 最初にデータに含まれる値の範囲を0以上1以下に正規化する
-        self.fitnorm(x, y);
+    //  モデル作成（データセットのビューから）
+    //  ビューの列名を係数のレポートなどに使う
+    //  正規化した値の行列は作らず、学習中に値を読むたびに正規化する
+    //
+    //  @param x: 説明変数のビュー
+    //  @param y: 目的変数のビュー（列数>1なら目的変数ごとに係数を求める）
+    //  @param weights: 行ごとの重み（Noneなら重みなし）
+    //
+    //  @return x, y, weightsの行数が違えばShapeError
+    // ============================================
+    pub fn fitView(&mut self, x: &View, y: &View, weights: Option<&[f64]>) -> Result<(), ShapeError> {
+        dataset::checkRows(x, y)?;
+        dataset::checkWeights(x, weights)?;
+
+        let yNames = y.colNames();
+        self.names = vec![yNames[0].clone()];
+        self.names.extend(x.colNames());
+        self.outputNames = yNames[1..].to_vec();
+        self.fitViewSub(x, y, 0, weights);
+        Ok(())
+    }
+
+    // fitViewの本体．yのk列目を目的変数にする（k=0なら2列目以降は同じ設定の別モデルで学習する）
+    fn fitViewSub(&mut self, x: &View, y: &View, k: usize, weights: Option<&[f64]>) {
+        // 最初にデータに含まれる値の範囲を0以上1以下に正規化するためのパラメータを求める
+        self.norm = self.scalesOf(x, y, k);
 
-        // 正規化
-        let (nx, ny) = self.normalize(x, y);
+        self.outputs = vec![];
+        if k == 0 {
+            for kOut in 1..y.ncols() {
+                let mut m = self.newOutput(kOut);
+                m.fitViewSub(x, y, kOut, weights);
+                self.outputs.push(m);
+            }
+        }
 
-        // println!("**** nx ****");
-        // U::printMat(&nx);
+        let d = Normalized { x, y, k, norm: self.norm.clone() };
 
-        // println!("**** ny ****");
-        // U::printMat(&ny);
-        
-        let nExpVars = x.len(); // 説明変数の次元数
-        let nrows = x[0].len(); // xの行数 = 観測値の個数
+        self.lossHistory = vec![];
+        self.covBeta = None;
+        let (l1, _) = self.penalty.strength();
+        match self.solver {
+            _ if !matches!(self.loss, Loss::Squared) => { self.fitSgd(&d, weights); },
+            Solver::Sgd => { self.fitSgd(&d, weights); },
+            Solver::Cholesky | Solver::Qr if l1 == 0.0 => {
+                self.fitExact(&d, weights);
+                self.covBeta = self.coefCovariance(&d, weights);
+                // 厳密解の損失を1エポック分として記録
+                let loss = self.lossValue(&d, weights);
+                self.lossHistory.push(loss);
+            },
+            _ => {
+                let init = vec![0.0; d.ncols()+1];
+                self.fitCd(&d, init, weights);
+            }
+        }
+    }
+
+    // -----------------------------------------------
+    //  ビューのk列目の目的変数と説明変数の正規化パラメータ（並びはself.normと同じ）
+    //  1列ずつ値を集めてcalcScaleで求める
+    // -----------------------------------------------
+    fn scalesOf(&self, x: &View, y: &View, k: usize) -> Vec<Scale> {
+        let mut norm = vec![calcScale(&y.columnIter(k).collect::<Vec<f64>>(), self.scaling)];
+        norm.extend((0..x.ncols()).map(|j| calcScale(&x.columnIter(j).collect::<Vec<f64>>(), self.scaling)));
+        norm
+    }
+
+    // -----------------------------------------------
+    //  厳密解用の計画行列A = [1, nx]と右辺b = ny（QRの作業領域として作る）
+    //  重みがある場合は各行にsqrt(重み)を掛ける（重み付き最小二乗法）
+    // -----------------------------------------------
+    fn design(d: &Normalized, weights: Option<&[f64]>) -> (U::Matrix, Vec<f64>) {
+        let sw: Vec<f64> = (0..d.nrows()).map(|irow| rowWeight(weights, irow).sqrt()).collect();
+        // 切片用の列を先頭に付ける
+        let mut a: U::Matrix = vec![sw.clone()];
+        a.extend((0..d.ncols()).map(|j| sw.iter().enumerate().map(|(irow, s)| d.x(irow, j) * s).collect()));
+        let b: Vec<f64> = sw.iter().enumerate().map(|(irow, s)| d.y(irow) * s).collect();
+        (a, b)
+    }
+
+    // -----------------------------------------------
+    //  正規方程式の係数 AᵀWA と右辺 AᵀWb（A = [1, nx], Wは重みの対角行列）
+    //  Aは作らずに1行ずつ足し込む
+    // -----------------------------------------------
+    fn gram(d: &Normalized, weights: Option<&[f64]>) -> (linalg::SqMatrix, Vec<f64>) {
+        let p = d.ncols() + 1;
+        let mut g: linalg::SqMatrix = vec![vec![0.0; p]; p];
+        let mut c = vec![0.0; p];
+        let mut row = vec![1.0; p];
+        for irow in 0..d.nrows() {
+            for (j, e) in row.iter_mut().enumerate().skip(1) {
+                *e = d.x(irow, j-1);
+            }
+            let w = rowWeight(weights, irow);
+            let wy = w * d.y(irow);
+            for (i, ri) in row.iter().enumerate() {
+                c[i] += ri * wy;
+                for (j, rj) in row.iter().enumerate() {
+                    g[i][j] += w * ri * rj;
+                }
+            }
+        }
+        (g, c)
+    }
+
+    // ============================================
+    //  確率的勾配降下法で回帰係数を求める
+    //
+    //  @param d: 正規化した学習データ
+    //  @param weights: 行ごとの重み（Noneなら重みなし）
+    // ============================================
+    fn fitSgd(&mut self, d: &Normalized, weights: Option<&[f64]>) {
+        let nExpVars = d.ncols(); // 説明変数の次元数
+        let nrows = d.nrows();    // xの行数 = 観測値の個数
 
         self.beta = vec![0.0; nExpVars+1];    // 初期化．長さnExpVars+1の0ベクトル
 
-        for _ in 0..self.epochs {
-            for irow in 0..nrows {
-                // p=説明変数を保持する行列(1行xn列)．predict()の引数の型と合わせるため行列にする
-                let mut p: U::Matrix = U::Matrix::new();
-                for jcol in 0..nExpVars {
-                    let expVar: Vec<f64> = vec![nx[jcol][irow]]; // 要素1個だけのベクトル
-                    p.push(expVar);
+        // 行を処理する順番
+        let mut order: Vec<usize> = (0..nrows).collect();
+        let mut rng = self.shuffleSeed.map(random::Rng::new);
+
+        for epoch in 0..self.epochs {
+            if let Some(ref mut r) = rng {
+                r.shuffle(&mut order);
+            }
+            let lr = self.schedule.rate(self.lr, epoch);
+            self.sgdEpoch(d, &order, lr, weights);
+
+            // このエポック終了時点の損失
+            let loss = self.lossValue(d, weights);
+            let prev = self.lossHistory.last().copied();
+            self.lossHistory.push(loss);
+
+            // 早期終了
+            if let (Some(tol), Some(prev)) = (self.tol, prev) {
+                if prev - loss < tol {
+                    break;
                 }
+            }
+        }
+    }
 
-                // q=目的変数
-                let q: f64 = ny[0][irow];
+    // ============================================
+    //  SGDで1エポック分（orderの順に全ての行）モデルを更新する
+    //
+    //  @param d: 正規化した学習データ
+    //  @param order: 行を処理する順番
+    //  @param lr: このエポックの学習率
+    //  @param weights: 行ごとの重み（Noneなら重みなし）
+    // ============================================
+    fn sgdEpoch(&mut self, d: &Normalized, order: &[usize], lr: f64, weights: Option<&[f64]>) {
+        let nExpVars = d.ncols(); // 説明変数の次元数
+        let (l1, l2) = self.penalty.strength();
 
-                // 予測値計算 --> z
-                let z: Vec<f64> = self.predict(&p, true);   // true=正規化計算を行う
+        // ミニバッチごとに勾配の平均を求めてモデルを更新
+        for batch in order.chunks(self.batchSize) {
+            let rate = lr / (batch.len() as f64);
+            let mut delta: Vec<f64> = vec![0.0; nExpVars+1];
+            for irow in batch {
+                // 誤差率（予測値と目的変数の差に対する損失関数の勾配 x 学習率）
+                let err: f64 = self.loss.gradient(self.predictRow(d, *irow) - d.y(*irow)) * rate * rowWeight(weights, *irow);
+                delta[0] += err;    // 切片
+                for (jcol, e) in delta.iter_mut().enumerate().skip(1) {
+                    *e += d.x(*irow, jcol-1) * err; // 係数
+                }
+            }
+            for (b, d) in self.beta.iter_mut().zip(delta.iter()) {
+                *b -= d;
+            }
 
-                // 誤差率
-                let err: f64 = (z[0] - q) * self.lr;
+            // 正則化（切片以外）: L2は係数を縮小し、L1はソフト閾値処理で0に近づける
+            if l1 > 0.0 || l2 > 0.0 {
+                for b in self.beta.iter_mut().skip(1) {
+                    *b = softThreshold(*b * (1.0 - lr * l2), lr * l1);
+                }
+            }
+        }
+    }
 
-                // モデルを更新
-                self.beta[0] -= err;    // 切片
-                for jcol in 0..nExpVars {
-                    let delta: f64 = nx[jcol][irow] * err;
-                    self.beta[jcol+1] -= delta; // 係数
+    // ============================================
+    //  新しいバッチで学習を続ける（バッチの行についてSGDで1エポック分更新する）
+    //  まだ学習していなければ、このバッチから正規化パラメータを求め、係数を0から始める
+    //  （fit, fitStreamの後なら、その正規化パラメータと係数から続ける）
+    //  solverの指定に関わらずSGDで更新する．学習率のスケジュールのエポック番号はこれまでに学習したエポック数
+    //  目的変数のいずれかが欠損値(NaN)の行は除く（全て欠損値のバッチは学習せず、エポックにも数えない）
+    //
+    //  @param x: 説明変数（このバッチの行）
+    //  @param y: 目的変数（このバッチの行．列数は最初のバッチと同じ）
+    //
+    //  @return 列の長さが揃っていない、または列数が最初のバッチと違えばShapeError
+    //
+    //  (ex) 新しいデータが届くたびに更新する
+    //  let mut l = linear::Linear::new().lr(0.05);
+    //  for (x, y) in batches { l.partialFit(&x, &y)?; }
+    // ============================================
+    pub fn partialFit(&mut self, x: &U::Matrix, y: &U::Matrix) -> Result<(), ShapeError> {
+        let (xd, yd) = dataset::fromMatrices(x, y)?;
+        let (xv, yv) = (xd.view(), yd.view());
+        if self.beta.is_empty() {
+            let (xNames, yNames) = self.matrixNames(xv.ncols(), yv.ncols());
+            self.names = vec![yNames[0].clone()];
+            self.names.extend(xNames);
+            self.outputNames = yNames[1..].to_vec();
+            self.norm = self.scalesOf(&xv, &yv, 0);
+            self.beta = vec![0.0; xv.ncols()+1];
+            self.lossHistory = vec![];
+            self.covBeta = None;
+            self.outputs = vec![];
+            for k in 1..yv.ncols() {
+                let mut m = self.newOutput(k);
+                m.norm = self.scalesOf(&xv, &yv, k);
+                m.beta = vec![0.0; xv.ncols()+1];
+                self.outputs.push(m);
+            }
+        } else if xv.ncols() + 1 != self.beta.len() {
+            return Err(ShapeError::Columns { expected: self.beta.len() - 1, found: xv.ncols() });
+        } else if yv.ncols() != self.outputs.len() + 1 {
+            return Err(ShapeError::Columns { expected: self.outputs.len() + 1, found: yv.ncols() });
+        }
+
+        let epoch = self.lossHistory.len() as u32;
+        let mut rng = self.shuffleSeed.map(|seed| random::Rng::new(random::deriveSeed(seed, epoch as u64)));
+        let (losses, n) = self.sgdChunk(&xv, &yv, epoch, rng.as_mut());
+        if n == 0 {
+            return Ok(());
+        }
+        self.lossHistory.push(losses[0]);
+        for (m, loss) in self.outputs.iter_mut().zip(losses[1..].iter()) {
+            m.lossHistory.push(*loss);
+        }
+        Ok(())
+    }
+
+    // ============================================
+    //  CSVファイルを少しずつ読みながら学習する（メモリに載らない大きなファイル用．SGDのみ）
+    //  1パス目で正規化パラメータを求め、以降はエポックごとにファイルを読み直して、チャンクごとにSGDで更新する
+    //  エポックの損失は、各チャンクを更新した直後の損失の（行数による）平均
+    //
+    //  @param open: ファイルを先頭から読むリーダーを作る関数（パスごとに呼ぶ）
+    //  @param target: 目的変数の列
+    //  @param features: 説明変数の列（Noneなら目的変数以外の全ての列）
+    //  @param drop: 説明変数から除く列
+    //
+    //  @return 読み込みの誤り、1パスで求められない正規化(Robust)、行がない場合はStreamError
+    //
+    //  (ex)
+    //  let csv = U::CSV::new();
+    //  l.fitStream(|| csv.chunks("big.csv", 10000), &[U::Col::Name("quality".to_string())], None, &[])?;
+    // ============================================
+    pub fn fitStream<F>(&mut self, open: F, target: &[U::Col], features: Option<&[U::Col]>, drop: &[U::Col]) -> Result<(), StreamError>
+        where F: Fn() -> Result<U::CSVChunks, U::CSVError>
+    {
+        if let Scaling::Robust = self.scaling {
+            return Err(StreamError::Scaling(self.scaling));
+        }
+
+        // 1パス目: 列ごとの最小値、最大値、平均、分散
+        let mut xStats: Vec<RunningScale> = vec![];
+        let mut yStats: Vec<RunningScale> = vec![];
+        let mut names: Option<(Vec<String>, Vec<String>)> = None;
+        for chunk in open()? {
+            let (xc, yc) = chunk?.splitXY(target, features, drop)?;
+            if names.is_none() {
+                xStats = vec![RunningScale::new(); xc.cols.len()];
+                yStats = vec![RunningScale::new(); yc.cols.len()];
+                names = Some((xc.colNames.clone(), yc.colNames.clone()));
+            }
+            xStats.iter_mut().zip(xc.cols.iter()).for_each(|(st, col)| st.push(col));
+            yStats.iter_mut().zip(yc.cols.iter()).for_each(|(st, col)| st.push(col));
+        }
+        let (xNames, yNames) = match names {
+            Some(n) => n,
+            None => { return Err(StreamError::Empty); }
+        };
+        if yNames.is_empty() {
+            return Err(StreamError::Empty);
+        }
+        let xScales: Vec<Scale> = xStats.iter().map(|st| st.scale(self.scaling)).collect();
+
+        // 正規化パラメータ、列名、係数の初期化（目的変数ごと）
+        self.names = vec![yNames[0].clone()];
+        self.names.extend(xNames.iter().cloned());
+        self.outputNames = yNames[1..].to_vec();
+        self.norm = vec![yStats[0].scale(self.scaling)];
+        self.norm.extend(xScales.iter().cloned());
+        self.beta = vec![0.0; xNames.len()+1];
+        self.lossHistory = vec![];
+        self.covBeta = None;
+        self.outputs = vec![];
+        for (k, st) in yStats.iter().enumerate().skip(1) {
+            let mut m = self.newOutput(k);
+            m.norm = vec![st.scale(self.scaling)];
+            m.norm.extend(xScales.iter().cloned());
+            m.beta = vec![0.0; xNames.len()+1];
+            self.outputs.push(m);
+        }
+
+        // 2パス目以降: エポックごとにファイルを読み直して更新
+        let mut rng = self.shuffleSeed.map(random::Rng::new);
+        for epoch in 0..self.epochs {
+            let mut lossSums = vec![0.0; yNames.len()];
+            let mut nrows = 0;
+            for chunk in open()? {
+                let (xc, yc) = chunk?.splitXY(target, features, drop)?;
+                let (xd, yd) = dataset::fromMatrices(&xc.cols, &yc.cols)?;
+                let (losses, n) = self.sgdChunk(&xd.view(), &yd.view(), epoch, rng.as_mut());
+                lossSums.iter_mut().zip(losses.iter()).for_each(|(s, l)| *s += l * (n as f64));
+                nrows += n;
+            }
+            if nrows == 0 {
+                return Err(StreamError::Empty);
+            }
+
+            let losses: Vec<f64> = lossSums.iter().map(|s| s / (nrows as f64)).collect();
+            let loss = losses[0];
+            let prev = self.lossHistory.last().copied();
+            self.lossHistory.push(loss);
+            for (m, l) in self.outputs.iter_mut().zip(losses[1..].iter()) {
+                m.lossHistory.push(*l);
+            }
+
+            // 早期終了
+            if let (Some(tol), Some(prev)) = (self.tol, prev) {
+                if prev - loss < tol {
+                    break;
                 }
             }
         }
+        Ok(())
+    }
+
+    // ============================================
+    //  1チャンク分の行でSGDの1エポック分更新する（partialFit, fitStream用．正規化パラメータは求めてあること）
+    //  目的変数のいずれかが欠損値(NaN)の行は学習に使えないので除く
+    //
+    //  @param x: 説明変数（正規化前）
+    //  @param y: 目的変数（正規化前．2列目以降はself.outputsで学習する）
+    //  @param epoch: 学習率のスケジュールのエポック番号
+    //  @param rng: 行の順番のシャッフル用（Noneならシャッフルしない）
+    //
+    //  @return (更新後の損失（目的変数ごと）, 学習に使った行数)．使える行がなければ更新せずに行数0
+    // ============================================
+    fn sgdChunk(&mut self, x: &View, y: &View, epoch: u32, mut rng: Option<&mut random::Rng>) -> (Vec<f64>, usize) {
+        let rows: Vec<usize> = (0..y.nrows()).filter(|irow| (0..y.ncols()).all(|k| !y.get(*irow, k).is_nan())).collect();
+        if rows.is_empty() {
+            return (vec![0.0; y.ncols()], 0);
+        }
+        let (x, y) = (x.select(&rows), y.select(&rows));
 
-        // debug
-        // println!("self.beta={:?}", self.beta);  // 回帰係数
+        let lr = self.schedule.rate(self.lr, epoch);
+        let mut losses = vec![];
+        for k in 0..y.ncols() {
+            let m = if k == 0 { &mut *self } else { &mut self.outputs[k-1] };
+            let d = Normalized { x: &x, y: &y, k, norm: m.norm.clone() };
+            let mut order: Vec<usize> = (0..rows.len()).collect();
+            if let Some(ref mut r) = rng {
+                r.shuffle(&mut order);
+            }
+            m.sgdEpoch(&d, &order, lr, None);
+            losses.push(m.lossValue(&d, None));
+        }
+        (losses, rows.len())
+    }
+
+    // ============================================
+    //  最小二乗法の厳密解で回帰係数を求める（Solver::Cholesky, Solver::Qr）
+    //  係数の並びはSGDと同じ(beta[0]=切片、beta[1..]=係数．正規化後の空間での値)
+    //
+    //  @param d: 正規化した学習データ
+    //  @param weights: 行ごとの重み（Noneなら重みなし）
+    // ============================================
+    fn fitExact(&mut self, d: &Normalized, weights: Option<&[f64]>) {
+        // L2正則化(ridge): 損失をn倍すると |Ab - y|² + n*l2*|b|² になるので、
+        // 正規方程式ではAᵀAの対角(切片以外)にn*l2を足し、QRではAの下にsqrt(n*l2)*Iの行を付け足す
+        // （重みがある場合、nは重みの合計）
+        let (_, l2) = self.penalty.strength();
+        let ridge = weightSum(weights, d.nrows()) * l2;
+
+        if let Solver::Cholesky = self.solver {
+            let (mut g, c) = Linear::gram(d, weights);
+            for (j, row) in g.iter_mut().enumerate().skip(1) {
+                row[j] += ridge;
+            }
+            if let Some(l) = linalg::cholesky(&g) {
+                self.beta = linalg::choleskySolve(&l, &c);
+                return;
+            }
+            // AᵀAが特異（行数が少ない、定数列があるなど） --> QRで解く
+        }
+
+        // 計画行列A = [1, nx]
+        let (mut a, mut b) = Linear::design(d, weights);
+        if ridge > 0.0 {
+            let p = a.len();
+            for (j, col) in a.iter_mut().enumerate() {
+                let mut extra = vec![0.0; p-1];
+                if j > 0 {
+                    extra[j-1] = ridge.sqrt();
+                }
+                col.extend(extra);
+            }
+            b.extend(vec![0.0; p-1]);
+        }
+        self.beta = linalg::lstsqQr(&a, &b);
+    }
+
+    // ============================================
+    //  厳密解の回帰係数の共分散行列を求める（正規化後の空間）
+    //  σ²(AᵀA + R)⁻¹ AᵀA (AᵀA + R)⁻¹  (Rはridgeの対角成分．正則化なしならσ²(AᵀA)⁻¹)
+    //  σ²は残差平方和 / 自由度(行数 - 係数の数)
+    //  重みがある場合、行数の代わりに重みの合計を使う（重み2の行は同じ行が2つあるのと同じになる）
+    //
+    //  @return (共分散行列, 自由度)．自由度が0以下、AᵀAが特異な場合はNone
+    // ============================================
+    fn coefCovariance(&self, d: &Normalized, weights: Option<&[f64]>) -> Option<(linalg::SqMatrix, f64)> {
+        let nrows = d.nrows();
+        let p = d.ncols() + 1;
+        let dof = weightSum(weights, nrows) - (p as f64);
+        if dof <= 0.0 {
+            return None;
+        }
+
+        let (g, _) = Linear::gram(d, weights);
+
+        let (_, l2) = self.penalty.strength();
+        let ridge = weightSum(weights, nrows) * l2;
+        let mut gr = g.clone();
+        for (j, row) in gr.iter_mut().enumerate().skip(1) {
+            row[j] += ridge;
+        }
+        let inv = linalg::choleskyInverse(&linalg::cholesky(&gr)?);
+
+        let rss: f64 = (0..nrows).map(|irow| (self.predictRow(d, irow) - d.y(irow)).powi(2) * rowWeight(weights, irow)).sum();
+        let sigma2 = rss / dof;
+
+        // inv * g * inv * σ²
+        let mul = |m1: &linalg::SqMatrix, m2: &linalg::SqMatrix| -> linalg::SqMatrix {
+            (0..p).map(|i| (0..p).map(|j| (0..p).map(|k| m1[i][k] * m2[k][j]).sum()).collect()).collect()
+        };
+        let cov = mul(&mul(&inv, &g), &inv).into_iter()
+            .map(|row| row.into_iter().map(|v| v * sigma2).collect())
+            .collect();
+        Some((cov, dof))
+    }
+
+    // ============================================
+    //  座標降下法で回帰係数を求める（L1正則化を扱える）
+    //  係数を1つずつ、他の係数を固定したときの最適値に更新することを繰り返す
+    //
+    //  @param d: 正規化した学習データ
+    //  @param init: 係数の初期値（正則化パスで前のalphaの解から始めるため）
+    //  @param weights: 行ごとの重み（Noneなら重みなし）
+    // ============================================
+    fn fitCd(&mut self, d: &Normalized, init: Vec<f64>, weights: Option<&[f64]>) {
+        let (l1, l2) = self.penalty.strength();
+        let n = weightSum(weights, d.nrows());  // 重みがなければ行数
+        let w: Vec<f64> = (0..d.nrows()).map(|irow| rowWeight(weights, irow)).collect();
+        self.beta = init;
+
+        // 残差 r = y - (切片 + Σ係数*x)
+        let mut r: Vec<f64> = (0..d.nrows()).map(|irow| d.y(irow) - self.predictRow(d, irow)).collect();
+        // 各列の二乗平均（重み付き）
+        let sq: Vec<f64> = (0..d.ncols())
+            .map(|jcol| w.iter().enumerate().map(|(irow, wi)| d.x(irow, jcol).powi(2) * wi).sum::<f64>() / n)
+            .collect();
+
+        for _ in 0..CD_MAX_ITER {
+            let mut maxDelta: f64 = 0.0;
+
+            // 切片（正則化しない）
+            let d0 = r.iter().zip(w.iter()).map(|(e, wi)| e * wi).sum::<f64>() / n;
+            self.beta[0] += d0;
+            r.iter_mut().for_each(|e| *e -= d0);
+            maxDelta = maxDelta.max(d0.abs());
+
+            // 係数
+            for (jcol, sqj) in sq.iter().enumerate() {
+                if *sqj == 0.0 {
+                    continue;   // 全て0の列は係数0のまま
+                }
+                let old = self.beta[jcol+1];
+                let rho: f64 = r.iter().zip(w.iter()).enumerate().map(|(irow, (e, wi))| d.x(irow, jcol) * e * wi).sum::<f64>() / n + sqj * old;
+                let new = softThreshold(rho, l1) / (sqj + l2);
+                let delta = new - old;
+                if delta != 0.0 {
+                    for (irow, e) in r.iter_mut().enumerate() {
+                        *e -= delta * d.x(irow, jcol);
+                    }
+                    self.beta[jcol+1] = new;
+                }
+                maxDelta = maxDelta.max(delta.abs());
+            }
+
+            let loss = self.lossValue(d, weights);
+            self.lossHistory.push(loss);
+            if maxDelta < CD_TOL {
+                break;
+            }
+        }
+    }
+
+    // ============================================================
+    //  正則化パス
+    //  alphaを大きい値(全ての係数が0になる値)から小さい値まで対数的に変えながら
+    //  順に学習し、alphaごとの回帰係数を返す．前のalphaの解を次の初期値にする
+    //  正則化の種類(l1_ratio)はself.penaltyのものを使う（Noneの場合はLasso）
+    //
+    //  @param x: 説明変数
+    //  @param y: 目的変数（1列目を使う）
+    //  @param weights: 行ごとの重み（Noneなら重みなし）
+    //  @param nAlphas: alphaの個数
+    //
+    //  @return (alpha, 回帰係数(正規化後の空間での値))のリスト．列の長さ、重みの数が揃っていなければShapeError
+    // ============================================================
+    pub fn regularizationPath(&mut self, x: &U::Matrix, y: &U::Matrix, weights: Option<&[f64]>, nAlphas: usize) -> Result<Vec<(f64, Vec<f64>)>, ShapeError> {
+        if let Penalty::None = self.penalty {
+            self.penalty = Penalty::Lasso(0.0);
+        }
+
+        let (xd, yd) = dataset::fromMatrices(x, y)?;
+        let (xv, yv) = (xd.view(), yd.view());
+        dataset::checkWeights(&xv, weights)?;
+        let (xNames, yNames) = self.matrixNames(x.len(), y.len());
+        self.names = vec![yNames[0].clone()];
+        self.names.extend(xNames);
+        self.norm = self.scalesOf(&xv, &yv, 0);
+        let d = Normalized { x: &xv, y: &yv, k: 0, norm: self.norm.clone() };
+
+        let n = weightSum(weights, d.nrows());  // 重みがなければ行数
+        let w: Vec<f64> = (0..d.nrows()).map(|irow| rowWeight(weights, irow)).collect();
+
+        // 係数が全て0になる最小のalpha: max|xᵀW(y - 重み付き平均)| / (n * l1_ratio)
+        let ym = w.iter().enumerate().map(|(irow, wi)| d.y(irow) * wi).sum::<f64>() / w.iter().sum::<f64>();
+        let maxCorr = (0..d.ncols())
+            .map(|jcol| w.iter().enumerate().map(|(irow, wi)| wi * d.x(irow, jcol) * (d.y(irow) - ym)).sum::<f64>().abs())
+            .fold(0.0, f64::max);
+        let alphaMax = maxCorr / (n * self.penalty.l1Ratio().max(1e-3));
+        let alphaMin = alphaMax * 1e-3;
+
+        let mut result = Vec::<(f64, Vec<f64>)>::new();
+        let mut init = vec![0.0; d.ncols()+1];
+        for i in 0..nAlphas {
+            let t = if nAlphas > 1 { (i as f64) / ((nAlphas - 1) as f64) } else { 0.0 };
+            let alpha = alphaMax * (alphaMin / alphaMax).powf(t);
+            self.penalty = self.penalty.withAlpha(alpha);
+            self.lossHistory = vec![];
+            self.fitCd(&d, init, weights);
+            init = self.beta.clone();
+            result.push((alpha, self.beta.clone()));
+        }
+        Ok(result)
+    }
+
+    // ============================================================
+    //  回帰係数を元の単位（正規化前の説明変数、目的変数の単位）に戻したレポートを作る
+    //  正規化後の回帰式 (y - cy)/sy = b0 + Σ bj (xj - cj)/sj を展開すると
+    //    係数 = sy * bj / sj,  切片 = cy + sy * (b0 - Σ bj * cj / sj)
+    //
+    //  @return 先頭が切片、以降は説明変数の順
+    // ============================================================
+    pub fn report(&self) -> Vec<CoefReport> {
+        let objScale = self.norm[0].scale;
+
+        // 正規化後の係数ベクトルに掛けると元の単位の値になる重み（行ごとに係数1つ分）
+        let mut weights: Vec<Vec<f64>> = vec![];
+        let mut w0 = vec![objScale];
+        w0.extend(self.norm[1..].iter().map(|e| -objScale * e.center / e.scale));
+        weights.push(w0);
+        for (j, e) in self.norm[1..].iter().enumerate() {
+            let mut w = vec![0.0; self.beta.len()];
+            w[j+1] = objScale / e.scale;
+            weights.push(w);
+        }
+
+        let mut names = vec![String::from("(intercept)")];
+        names.extend(self.names[1..].iter().cloned());
+
+        weights.iter().zip(names).enumerate().map(|(k, (w, name))| {
+            let mut coef: f64 = w.iter().zip(self.beta.iter()).map(|(wi, b)| wi * b).sum();
+            if k == 0 {
+                coef += self.norm[0].center;
+            }
+
+            // 分散 = wᵀ Cov w
+            let stdErr = self.covBeta.as_ref().map(|(cov, _)| {
+                let var: f64 = (0..w.len()).map(|i| (0..w.len()).map(|j| w[i] * cov[i][j] * w[j]).sum::<f64>()).sum();
+                var.max(0.0).sqrt()
+            });
+            let tValue = stdErr.map(|se| coef / se);
+            let pValue = match (tValue, &self.covBeta) {
+                (Some(t), Some((_, dof))) => Some(stats::tTestPValue(t, *dof)),
+                _ => None
+            };
+            CoefReport { name, coef, stdErr, tValue, pValue }
+        }).collect()
+    }
+
+    // -----------------------------------------------
+    //  report()の結果を表として表示する（目的変数が複数あれば目的変数ごとに）
+    // -----------------------------------------------
+    pub fn printReport(&self) {
+        let multi = !self.outputs.is_empty();
+        self.printCoefficients(multi);
+        for m in &self.outputs {
+            m.printCoefficients(true);
+        }
+    }
+
+    // withTarget: 見出しに目的変数の列名を付けるか
+    fn printCoefficients(&self, withTarget: bool) {
+        let fmt = |v: Option<f64>| v.map_or(String::from("-"), |v| format!("{:.6}", v));
+        if withTarget {
+            println!("*** Coefficients (original units) : target {} ***", self.names[0]);
+        } else {
+            println!("*** Coefficients (original units) ***");
+        }
+        println!("{:<24}{:>16}{:>14}{:>12}{:>12}", "name", "coef", "std err", "t", "p-value");
+        for r in self.report() {
+            println!("{:<24.24}{:>16.6}{:>14}{:>12}{:>12}", r.name, r.coef, fmt(r.stdErr), fmt(r.tValue), fmt(r.pValue));
+        }
+        if let Some((_, dof)) = &self.covBeta {
+            println!("residual degrees of freedom = {}", dof);
+        }
+    }
+
+    // 正規化後の損失関数の値の平均（二乗誤差なら平均二乗誤差．重みがあれば重み付き平均）
+    fn lossValue(&self, d: &Normalized, weights: Option<&[f64]>) -> f64 {
+        let nrows = d.nrows();
+        (0..nrows).map(|irow| self.loss.value(self.predictRow(d, irow) - d.y(irow)) * rowWeight(weights, irow)).sum::<f64>() / weightSum(weights, nrows)
+    }
+
+    // -----------------------------------------------
+    //  正規化した学習データのirow行目について、回帰式の値を計算する
+    // -----------------------------------------------
+    fn predictRow(&self, d: &Normalized, irow: usize) -> f64 {
+        let mut z: f64 = self.beta[0];
+        for jcol in 0..d.ncols() {
+            z += d.x(irow, jcol) * self.beta[jcol+1];
+        }
+        z
     }
 
     // ============================================================
@@ -187,35 +1190,33 @@ impl Linear {
     //  返り値は回帰式にxを当てはめた値(=予測値)
     // ============================================================
     pub fn predict(&self, x: &U::Matrix, normalized: bool) -> Vec<f64> {
-        let nx: U::Matrix;
-        if !normalized {
+        let nx: U::Matrix = if !normalized {
             // 正規化されていないので、正規化する
             let dummy = U::Matrix::new();
-            nx = self.normalize(x, &dummy).0;
+            self.normalize(x, &dummy).0
         } else {
             // 正規化の必要なし
-            nx = x.clone();
-        }
+            x.clone()
+        };
 
-        let n = x.len(); // 説明変数の次元数
         let m = x[0].len(); // 説明変数の行数
 
-        // 目的変数の最小値と値の範囲
-        let objMin: f64 = self.norm[0].min;
-        let objRange: f64 = self.norm[0].max - self.norm[0].min;
+        // 目的変数の正規化パラメータ
+        let objCenter: f64 = self.norm[0].center;
+        let objScale: f64 = self.norm[0].scale;
 
         let mut zs: Vec<f64> = vec![0.0; m];    // 予測値
 
         for irow in 0..m {
             // 回帰式に説明変数を当てはめて予測値を計算 ---> z
             let mut z: f64 = self.beta[0];
-            for i in 0..n {
-                z += nx[i][irow] * self.beta[i+1];
+            for (i, col) in nx.iter().enumerate() {   // 説明変数の次元でループ
+                z += col[irow] * self.beta[i+1];
             }
 
             if !normalized {
                 // 元のスケールに戻す
-                z = z * objRange + objMin;
+                z = z * objScale + objCenter;
             }
 
             zs[irow] = z;
@@ -223,4 +1224,594 @@ impl Linear {
 
         zs
     }
-}
\ No newline at end of file
+
+    // ============================================================
+    //  全ての目的変数の予測値を計算（多出力回帰）
+    //
+    //  @param x 説明変数（正規化前）
+    //
+    //  @return 予測値（Matrix[目的変数][行]．fitに渡したyと同じ並び）
+    // ============================================================
+    pub fn predictMulti(&self, x: &U::Matrix) -> U::Matrix {
+        let mut result: U::Matrix = vec![self.predict(x, false)];
+        result.extend(self.outputs.iter().map(|m| m.predict(x, false)));
+        result
+    }
+
+    // 全ての目的変数の予測値を計算（データセットのビューから）
+    pub fn predictView(&self, x: &View) -> U::Matrix {
+        let mut result: U::Matrix = vec![self.predictViewSub(x)];
+        result.extend(self.outputs.iter().map(|m| m.predictViewSub(x)));
+        result
+    }
+
+    // 1つの目的変数の予測値（ビューの値を1行ずつ読んで正規化する．計算はpredictと同じ）
+    fn predictViewSub(&self, x: &View) -> Vec<f64> {
+        let objCenter: f64 = self.norm[0].center;
+        let objScale: f64 = self.norm[0].scale;
+
+        (0..x.nrows()).map(|irow| {
+            let mut z: f64 = self.beta[0];
+            for (i, e) in self.norm[1..].iter().enumerate() {
+                let v = x.get(irow, i);
+                let nv = if v.is_nan() { 0.0 } else { (v - e.center) / e.scale };
+                z += nv * self.beta[i+1];
+            }
+            z * objScale + objCenter
+        }).collect()
+    }
+}
+
+// =================================================
+//  正規化した学習データ（fitViewの中で使う）
+//  値はコピーせず、ビューの値を読むたびに(値 - center) / scaleで正規化する
+//  説明変数の欠損値(NaN)は0(=center)にする（applyScalesと同じ）
+// =================================================
+struct Normalized<'v, 'a> {
+    x: &'v View<'a>,    // 説明変数
+    y: &'v View<'a>,    // 目的変数
+    k: usize,           // 目的変数にするyの列
+    norm: Vec<Scale>    // norm[0]=目的変数, norm[1..]=説明変数の正規化パラメータ（Linear::normと同じ並び）
+}
+
+impl Normalized<'_, '_> {
+    fn nrows(&self) -> usize {
+        self.x.nrows()
+    }
+
+    fn ncols(&self) -> usize {
+        self.x.ncols()
+    }
+
+    // irow行目、j列目の説明変数
+    fn x(&self, irow: usize, j: usize) -> f64 {
+        let v = self.x.get(irow, j);
+        let e = &self.norm[j+1];
+        if v.is_nan() { 0.0 } else { (v - e.center) / e.scale }
+    }
+
+    // irow行目の目的変数
+    fn y(&self, irow: usize) -> f64 {
+        (self.y.get(irow, self.k) - self.norm[0].center) / self.norm[0].scale
+    }
+}
+
+// =================================================
+//  fitStreamの誤り
+// =================================================
+#[derive(Debug)]
+pub enum StreamError {
+    Csv(U::CSVError),       // 読み込みの誤り（列名の誤りを含む）
+    Shape(ShapeError),      // チャンクの列の長さが揃っていない
+    Scaling(Scaling),       // 1パスでは求められない正規化の方法(Robust)
+    Empty                   // データの行がない
+}
+
+impl std::fmt::Display for StreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            StreamError::Csv(e) => write!(f, "{}", e),
+            StreamError::Shape(e) => write!(f, "{}", e),
+            StreamError::Scaling(s) => write!(f, "{:?} scaling cannot be computed while streaming (use minmax or zscore)", s),
+            StreamError::Empty => write!(f, "no rows to train on")
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+impl From<U::CSVError> for StreamError {
+    fn from(e: U::CSVError) -> Self {
+        StreamError::Csv(e)
+    }
+}
+
+impl From<ShapeError> for StreamError {
+    fn from(e: ShapeError) -> Self {
+        StreamError::Shape(e)
+    }
+}
+
+// =================================================
+//  正規化（他のモデルからも使う）
+// =================================================
+
+// -----------------------------------------------
+// 1列分の正規化パラメータを値を少しずつ受け取りながら求める（fitStream用）
+// 平均、分散はWelford法で更新する．欠損値(NaN)は除く
+// -----------------------------------------------
+#[derive(Clone)]
+struct RunningScale {
+    n: f64,
+    min: f64,
+    max: f64,
+    mean: f64,
+    m2: f64     // 平均からの偏差の二乗和
+}
+
+impl RunningScale {
+    fn new() -> Self {
+        RunningScale { n: 0.0, min: f64::INFINITY, max: f64::NEG_INFINITY, mean: 0.0, m2: 0.0 }
+    }
+
+    fn push(&mut self, v: &[f64]) {
+        for e in v.iter().filter(|e| !e.is_nan()) {
+            self.n += 1.0;
+            self.min = self.min.min(*e);
+            self.max = self.max.max(*e);
+            let d = e - self.mean;
+            self.mean += d / self.n;
+            self.m2 += d * (e - self.mean);
+        }
+    }
+
+    // calcScaleと同じ規則の正規化パラメータ（MinMax, ZScoreのみ）
+    fn scale(&self, scaling: Scaling) -> Scale {
+        if self.n == 0.0 {
+            return Scale { center: 0.0, scale: 1.0 };
+        }
+        let (center, scale) = match scaling {
+            Scaling::ZScore => (self.mean, (self.m2 / self.n).sqrt()),
+            _ => (self.min, self.max - self.min)
+        };
+        Scale {
+            center,
+            scale: if scale == 0.0 { 1.0 } else { scale }
+        }
+    }
+}
+
+// -----------------------------------------------
+// 1列分の正規化パラメータを求める
+//
+// @param v 数値ベクトル
+// @param scaling 正規化の方法
+// -----------------------------------------------
+pub fn calcScale(v: &[f64], scaling: Scaling) -> Scale {
+    // 欠損値(NaN)は除いて求める
+    if v.iter().any(|e| e.is_nan()) {
+        let present: Vec<f64> = v.iter().cloned().filter(|e| !e.is_nan()).collect();
+        if present.is_empty() {
+            return Scale { center: 0.0, scale: 1.0 };
+        }
+        return calcScale(&present, scaling);
+    }
+    let (center, scale) = match scaling {
+        Scaling::MinMax => {
+            let e = U::calcMinMax(v);
+            (e.min, e.max - e.min)
+        },
+        Scaling::ZScore => (U::mean(v), U::stdev(v)),
+        Scaling::Robust => (U::median(v), U::quantile(v, 0.75) - U::quantile(v, 0.25))
+    };
+    Scale {
+        center,
+        scale: if scale == 0.0 { 1.0 } else { scale }  // 全て同じ値の列は1で割る(=ずらすだけ)
+    }
+}
+
+// -----------------------------------------------
+// 列ごとの正規化パラメータを求める
+//
+// @param x 説明変数(2次元配列. 変数(=列)ごとの値)
+// @param scaling 正規化の方法
+// -----------------------------------------------
+pub fn calcScales(x: &U::Matrix, scaling: Scaling) -> Vec<Scale> {
+    x.iter().map(|col| calcScale(col, scaling)).collect()
+}
+
+// -----------------------------------------------
+// 列ごとに(値 - center) / scale で正規化する
+// 欠損値(NaN)は0(=center)にする（係数に関係なく予測に寄与しない）
+//
+// @param x 説明変数
+// @param norm 列ごとの正規化パラメータ（calcScales()の結果）
+//
+// @return 正規化後の説明変数
+// -----------------------------------------------
+pub fn applyScales(x: &U::Matrix, norm: &[Scale]) -> U::Matrix {
+    x.iter().zip(norm.iter())
+        .map(|(col, e)| col.iter().map(|v| if v.is_nan() { 0.0 } else { (v - e.center) / e.scale }).collect())
+        .collect()
+}
+
+// irow行目の重み（重みの指定がなければ1）
+fn rowWeight(weights: Option<&[f64]>, irow: usize) -> f64 {
+    weights.map_or(1.0, |w| w[irow])
+}
+
+// 重みの合計（重みの指定がなければ行数）
+fn weightSum(weights: Option<&[f64]>, nrows: usize) -> f64 {
+    weights.map_or(nrows as f64, |w| w.iter().sum())
+}
+
+// ソフト閾値関数 sign(v) * max(|v| - t, 0)
+fn softThreshold(v: f64, t: f64) -> f64 {
+    if v > t {
+        v - t
+    } else if v < -t {
+        v + t
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // y = 1 + 2*x1 - 3*x2 （誤差なし）
+    fn exactData() -> (U::Matrix, U::Matrix) {
+        let x1: Vec<f64> = (0..20).map(|i| (i as f64) * 0.5).collect();
+        let x2: Vec<f64> = (0..20).map(|i| ((i * 7 % 11) as f64) - 5.0).collect();
+        let y: Vec<f64> = x1.iter().zip(x2.iter()).map(|(a, b)| 1.0 + 2.0 * a - 3.0 * b).collect();
+        (vec![x1, x2], vec![y])
+    }
+
+    #[test]
+    fn lrScheduleRates() {
+        assert_eq!(LrSchedule::Constant.rate(0.1, 5), 0.1);
+        assert_eq!(LrSchedule::TimeBased(1.0).rate(0.1, 4), 0.1 / 5.0);
+        assert_eq!(LrSchedule::Step(10, 0.5).rate(0.1, 25), 0.1 * 0.25);
+        assert_eq!(LrSchedule::Exponential(0.5).rate(0.1, 3), 0.1 * 0.125);
+        assert!(matches!("step:10:0.5".parse::<LrSchedule>(), Ok(LrSchedule::Step(10, f)) if f == 0.5));
+        assert!("step:10".parse::<LrSchedule>().is_err());
+    }
+
+    #[test]
+    fn sgdStopsEarlyWithTol() {
+        let (x, y) = exactData();
+        let mut l = Linear::new().epochs(500).lr(0.05).tol(1e-4);
+        l.fit(&x, &y).unwrap();
+        assert!(l.lossHistory.len() < 500);
+        let mut full = Linear::new().epochs(50).lr(0.05);
+        full.fit(&x, &y).unwrap();
+        assert_eq!(full.lossHistory.len(), 50);
+        assert!(full.lossHistory[49] < full.lossHistory[0]);
+    }
+
+    #[test]
+    fn sgdShuffleIsReproducible() {
+        let (x, y) = exactData();
+        let fit = |seed| {
+            let mut l = Linear::new().epochs(5).shuffle(seed).batchSize(4);
+            l.fit(&x, &y).unwrap();
+            l.beta
+        };
+        assert_eq!(fit(3), fit(3));
+        assert_ne!(fit(3), fit(4));
+    }
+
+    // x = (0, 1, 2, 3), y = (1, 3, 2, 5) の最小二乗解は 切片1.1, 傾き1.1
+    fn smallData() -> (U::Matrix, U::Matrix) {
+        (vec![vec![0.0, 1.0, 2.0, 3.0]], vec![vec![1.0, 3.0, 2.0, 5.0]])
+    }
+
+    fn near(a: f64, b: f64, tol: f64) -> bool {
+        (a - b).abs() < tol
+    }
+
+    #[test]
+    fn exactSolversMatchOls() {
+        let (x, y) = smallData();
+        for solver in [Solver::Cholesky, Solver::Qr] {
+            let mut l = Linear::new().solver(solver);
+            l.fit(&x, &y).unwrap();
+            let r = l.report();
+            assert!(near(r[0].coef, 1.1, 1e-9) && near(r[1].coef, 1.1, 1e-9), "{:?}", solver);
+            assert_eq!(l.lossHistory.len(), 1);
+        }
+
+        let (x, y) = exactData();
+        let mut l = Linear::new().solver(Solver::Qr);
+        l.fit(&x, &y).unwrap();
+        let pred = l.predict(&x, false);
+        assert!(pred.iter().zip(y[0].iter()).all(|(p, t)| near(*p, *t, 1e-9)));
+    }
+
+    #[test]
+    fn choleskyFallsBackToQrWhenSingular() {
+        // 同じ列が2つあるとAᵀAが特異になる
+        let (x, y) = smallData();
+        let x2 = vec![x[0].clone(), x[0].clone()];
+        let mut l = Linear::new().solver(Solver::Cholesky);
+        l.fit(&x2, &y).unwrap();
+        let pred = l.predict(&x2, false);
+        let expected: Vec<f64> = x[0].iter().map(|v| 1.1 + 1.1 * v).collect();
+        assert!(pred.iter().zip(expected.iter()).all(|(p, e)| near(*p, *e, 1e-9)));
+    }
+
+    #[test]
+    fn ridgeMatchesClosedForm() {
+        // 1変数のridgeの解（切片は正則化しない）: 傾き = Sxy / (Sxx + n*alpha), 切片 = ȳ - 傾き * x̄（正規化後の空間）
+        let (x, y) = smallData();
+        let alpha = 0.1;
+        let nx: Vec<f64> = x[0].iter().map(|v| v / 3.0).collect();
+        let ny: Vec<f64> = y[0].iter().map(|v| (v - 1.0) / 4.0).collect();
+        let (mx, my) = (U::mean(&nx), U::mean(&ny));
+        let sxy: f64 = nx.iter().zip(ny.iter()).map(|(a, b)| (a - mx) * (b - my)).sum();
+        let sxx: f64 = nx.iter().map(|a| (a - mx).powi(2)).sum();
+        let slope = sxy / (sxx + 4.0 * alpha);
+        let intercept = my - slope * mx;
+
+        for (solver, tol) in [(Solver::Cholesky, 1e-12), (Solver::Qr, 1e-12), (Solver::Cd, 1e-6)] {
+            let mut l = Linear::new().solver(solver).penalty(Penalty::Ridge(alpha));
+            l.fit(&x, &y).unwrap();
+            assert!(near(l.beta[0], intercept, tol) && near(l.beta[1], slope, tol), "{:?}: {:?}", solver, l.beta);
+        }
+    }
+
+    #[test]
+    fn lassoZeroesCoefficients() {
+        let (x, y) = exactData();
+        let mut l = Linear::new().solver(Solver::Cd).penalty(Penalty::Lasso(10.0));
+        l.fit(&x, &y).unwrap();
+        assert!(l.beta[1..].iter().all(|b| *b == 0.0));
+
+        // alphaが小さければ最小二乗解に近い
+        let mut l = Linear::new().solver(Solver::Cd).penalty(Penalty::ElasticNet(1e-6, 0.5));
+        l.fit(&x, &y).unwrap();
+        let r = l.report();
+        assert!(near(r[1].coef, 2.0, 1e-3) && near(r[2].coef, -3.0, 1e-3));
+    }
+
+    #[test]
+    fn regularizationPathStartsFromZero() {
+        let (x, y) = exactData();
+        let path = Linear::new().regularizationPath(&x, &y, None, 5).unwrap();
+        assert_eq!(path.len(), 5);
+        assert!(path[0].1[1..].iter().all(|b| b.abs() < 1e-12));
+        assert!(path[4].1[1..].iter().all(|b| *b != 0.0));
+        assert!(path.windows(2).all(|w| w[0].0 > w[1].0));
+    }
+
+    #[test]
+    fn scalingParameters() {
+        let v = [1.0, 2.0, 3.0, 4.0, 100.0, f64::NAN];
+        let s = calcScale(&v, Scaling::MinMax);
+        assert_eq!((s.center, s.scale), (1.0, 99.0));
+        let s = calcScale(&v, Scaling::ZScore);
+        assert_eq!(s.center, 22.0);
+        assert!(near(s.scale, (7610.0f64 / 5.0).sqrt(), 1e-12));     // 偏差の二乗和 = 21² + 20² + 19² + 18² + 78²
+        let s = calcScale(&v, Scaling::Robust);
+        assert_eq!((s.center, s.scale), (3.0, 2.0));
+
+        // 全て同じ値、全て欠損値の列は1で割る
+        let s = calcScale(&[5.0, 5.0], Scaling::ZScore);
+        assert_eq!((s.center, s.scale), (5.0, 1.0));
+        let s = calcScale(&[f64::NAN], Scaling::Robust);
+        assert_eq!((s.center, s.scale), (0.0, 1.0));
+    }
+
+    #[test]
+    fn predictionDoesNotDependOnScaling() {
+        let (x, y) = smallData();
+        for scaling in [Scaling::MinMax, Scaling::ZScore, Scaling::Robust] {
+            let mut l = Linear::new().solver(Solver::Qr).scaling(scaling);
+            l.fit(&x, &y).unwrap();
+            let pred = l.predict(&vec![vec![10.0]], false);
+            assert!(near(pred[0], 1.1 + 11.0, 1e-9), "{:?}", scaling);
+        }
+    }
+
+    #[test]
+    fn reportStandardErrors() {
+        // smallDataの単回帰: 残差平方和2.7, 自由度2 --> σ² = 1.35, Sxx = 5, x̄ = 1.5
+        // se(傾き) = sqrt(σ²/Sxx), se(切片) = sqrt(σ²(1/n + x̄²/Sxx))
+        let (x, y) = smallData();
+        for scaling in [Scaling::MinMax, Scaling::ZScore] {
+            let mut l = Linear::new().solver(Solver::Cholesky).scaling(scaling).columnNames(&[String::from("x")], &[String::from("y")]);
+            l.fit(&x, &y).unwrap();
+            let r = l.report();
+            assert_eq!(r[0].name, "(intercept)");
+            assert_eq!(r[1].name, "x");
+            assert!(near(r[1].stdErr.unwrap(), 0.27f64.sqrt(), 1e-9));
+            assert!(near(r[0].stdErr.unwrap(), 0.945f64.sqrt(), 1e-9));
+            assert!(near(r[1].tValue.unwrap(), 1.1 / 0.27f64.sqrt(), 1e-9));
+            assert!(near(r[1].pValue.unwrap(), stats::tTestPValue(1.1 / 0.27f64.sqrt(), 2.0), 1e-12));
+        }
+
+        // SGDでは標準誤差を求めない
+        let mut l = Linear::new();
+        l.fit(&x, &y).unwrap();
+        assert!(l.report().iter().all(|r| r.stdErr.is_none() && r.pValue.is_none()));
+    }
+
+    #[test]
+    fn lossValuesAndGradients() {
+        assert_eq!(Loss::Squared.value(-3.0), 9.0);
+        assert_eq!(Loss::Huber(1.0).value(0.5), 0.25);
+        assert_eq!(Loss::Huber(1.0).value(-3.0), 5.0);     // 境界で二乗誤差とつながる: delta(2|r| - delta)
+        assert_eq!(Loss::Huber(1.0).gradient(-3.0), -1.0);
+        assert_eq!(Loss::Absolute.gradient(0.0), 0.0);
+        assert_eq!(Loss::Quantile(0.9).value(-2.0), 1.8);
+        assert_eq!(Loss::Quantile(0.9).gradient(2.0), 1.0 - 0.9);
+        assert_eq!(Loss::EpsilonInsensitive(0.5).value(0.3), 0.0);
+        assert_eq!(Loss::EpsilonInsensitive(0.5).gradient(-0.7), -1.0);
+        assert!(matches!("huber:1.5".parse::<Loss>(), Ok(Loss::Huber(d)) if d == 1.5));
+        assert!("quantile".parse::<Loss>().is_err());
+    }
+
+    #[test]
+    fn quantileLossPredictsQuantile() {
+        // 説明変数が定数なら切片だけのモデルになり、予測値は目的変数の分位点に近づく
+        let x: U::Matrix = vec![vec![1.0; 101]];
+        let y: U::Matrix = vec![(0..=100).map(|i| i as f64).collect()];
+        for (loss, expected) in [(Loss::Quantile(0.9), 90.0), (Loss::Absolute, 50.0)] {
+            let mut l = Linear::new().loss(loss).solver(Solver::Qr).epochs(300).lr(0.01).shuffle(1);
+            l.fit(&x, &y).unwrap();
+            let pred = l.predict(&vec![vec![1.0]], false)[0];
+            assert!(near(pred, expected, 3.0), "{:?}: {}", loss, pred);
+        }
+    }
+
+    #[test]
+    fn multiOutputFitsEachTarget() {
+        let (x, y) = exactData();
+        let y2: Vec<f64> = x[0].iter().map(|v| 5.0 - v).collect();
+        let ys = vec![y[0].clone(), y2.clone()];
+        let mut l = Linear::new().solver(Solver::Qr);
+        l.fit(&x, &ys).unwrap();
+        assert_eq!(l.outputs.len(), 1);
+        assert_eq!(l.outputs[0].names[0], "y2");
+
+        let pred = l.predictMulti(&x);
+        assert_eq!(pred.len(), 2);
+        for (p, t) in pred.iter().zip(ys.iter()) {
+            assert!(p.iter().zip(t.iter()).all(|(a, b)| near(*a, *b, 1e-9)));
+        }
+        let r = l.outputs[0].report();
+        assert!(near(r[0].coef, 5.0, 1e-9) && near(r[1].coef, -1.0, 1e-9) && near(r[2].coef, 0.0, 1e-9));
+    }
+
+    #[test]
+    fn weightsActLikeRepeatedRows() {
+        // 重み2の行は同じ行を2回入れたのと同じ、重み0の行は除いたのと同じ
+        let (x, y) = smallData();
+        let xr: U::Matrix = vec![vec![0.0, 1.0, 1.0, 3.0]];
+        let yr: U::Matrix = vec![vec![1.0, 3.0, 3.0, 5.0]];
+        for solver in [Solver::Cholesky, Solver::Qr, Solver::Cd] {
+            let mut weighted = Linear::new().solver(solver);
+            weighted.fitWeighted(&x, &y, &[1.0, 2.0, 0.0, 1.0]).unwrap();
+            let mut repeated = Linear::new().solver(solver);
+            repeated.fit(&xr, &yr).unwrap();
+            let (a, b) = (weighted.report(), repeated.report());
+            assert!(near(a[0].coef, b[0].coef, 1e-6) && near(a[1].coef, b[1].coef, 1e-6), "{:?}", solver);
+            if let Solver::Cd = solver {
+                continue;
+            }
+            // 自由度は重みの合計 - 係数の数なので、標準誤差も同じになる
+            assert_eq!(weighted.covBeta.as_ref().map(|(_, dof)| *dof), Some(2.0));
+            for (ra, rb) in a.iter().zip(b.iter()) {
+                assert!(near(ra.stdErr.unwrap(), rb.stdErr.unwrap(), 1e-9), "{:?}", solver);
+            }
+        }
+
+        // 正則化パスも重み付きで求める
+        let pathW = Linear::new().regularizationPath(&x, &y, Some(&[1.0, 2.0, 0.0, 1.0]), 4).unwrap();
+        let pathR = Linear::new().regularizationPath(&xr, &yr, None, 4).unwrap();
+        for ((alphaW, betaW), (alphaR, betaR)) in pathW.iter().zip(pathR.iter()) {
+            assert!(near(*alphaW, *alphaR, 1e-9));
+            assert!(betaW.iter().zip(betaR.iter()).all(|(a, b)| near(*a, *b, 1e-6)));
+        }
+
+        // 重みが全て1ならfitと同じ
+        let mut ones = Linear::new();
+        ones.fitWeighted(&x, &y, &[1.0; 4]).unwrap();
+        let mut plain = Linear::new();
+        plain.fit(&x, &y).unwrap();
+        assert_eq!(ones.beta, plain.beta);
+    }
+
+    #[test]
+    fn fitViewMatchesFit() {
+        use crate::dataset::Dataset;
+
+        let (x, y) = exactData();
+        let names = vec![String::from("a"), String::from("b")];
+        let xd = Dataset::fromMatrix(&x).unwrap().names(&names).unwrap();
+        let yd = Dataset::fromMatrix(&y).unwrap().names(&[String::from("target")]).unwrap();
+
+        // 一部の行のビューで学習しても、その行を取り出した行列で学習したのと同じ
+        let rows: Vec<usize> = (0..20).filter(|i| i % 3 != 0).collect();
+        for solver in [Solver::Cholesky, Solver::Qr, Solver::Sgd] {
+            let mut byView = Linear::new().solver(solver);
+            byView.fitView(&xd.view().select(&rows), &yd.view().select(&rows), None).unwrap();
+            let mut byMatrix = Linear::new().solver(solver);
+            byMatrix.fit(&U::MatSelectRow(&x, &rows), &U::MatSelectRow(&y, &rows)).unwrap();
+            assert_eq!(byView.beta, byMatrix.beta, "{:?}", solver);
+            assert_eq!(byView.names, vec!["target", "a", "b"]);
+            assert_eq!(byView.predictView(&xd.view())[0], byMatrix.predict(&x, false));
+        }
+    }
+
+    #[test]
+    fn fitReturnsShapeErrors() {
+        let (x, y) = exactData();
+        let short: U::Matrix = vec![y[0][..10].to_vec()];
+        assert!(matches!(Linear::new().fit(&x, &short), Err(ShapeError::Rows { x: 20, y: 10 })));
+        assert!(matches!(Linear::new().fitWeighted(&x, &y, &[1.0; 3]), Err(ShapeError::Weights { expected: 20, found: 3 })));
+        assert!(Linear::new().regularizationPath(&vec![x[0].clone(), vec![1.0]], &y, None, 3).is_err());
+    }
+
+    #[test]
+    fn partialFitSkipsMissingTargets() {
+        let (x, y) = smallData();
+        let mut l = Linear::new().lr(0.1);
+        l.partialFit(&x, &y).unwrap();
+        let beta = l.beta.clone();
+
+        // 目的変数が欠損値の行だけのバッチは学習しない
+        l.partialFit(&vec![vec![1.0]], &vec![vec![f64::NAN]]).unwrap();
+        assert_eq!(l.beta, beta);
+        assert_eq!(l.lossHistory.len(), 1);
+
+        // 列数が最初のバッチと違えば学習しない
+        let wide: U::Matrix = vec![vec![1.0], vec![2.0]];
+        assert!(matches!(l.partialFit(&wide, &vec![vec![1.0]]), Err(ShapeError::Columns { expected: 1, found: 2 })));
+        assert_eq!(l.beta, beta);
+
+        // 欠損値の行は除いて学習する
+        let mut withNaN = Linear::new().lr(0.1);
+        withNaN.partialFit(&vec![vec![0.0, 1.0, 9.0, 2.0, 3.0]], &vec![vec![1.0, 3.0, f64::NAN, 2.0, 5.0]]).unwrap();
+        assert!(withNaN.beta.iter().all(|b| b.is_finite()));
+        assert!(withNaN.lossHistory[0].is_finite());
+    }
+
+    #[test]
+    fn fitStreamMatchesInMemorySgd() {
+        let (x, y) = exactData();
+        let mut text = String::from("x1,x2,y\n");
+        for irow in 0..20 {
+            text += &format!("{},{},{}\n", x[0][irow], x[1][irow], y[0][irow]);
+        }
+        text += "1,2,NA\n";    // 目的変数が欠損値の行は使わない（説明変数は範囲内なのでmin-maxの正規化は変わらない）
+        let path = std::env::temp_dir().join(format!("ex1-test-{}-stream.csv", std::process::id()));
+        std::fs::write(&path, text).unwrap();
+        let path = path.to_str().unwrap().to_string();
+        let target = [U::Col::Name(String::from("y"))];
+
+        // 1チャンクに全ての行が入れば、メモリ上のfitと同じ更新になる
+        let csv = U::CSV::new();
+        let mut streamed = Linear::new().epochs(5).lr(0.05);
+        streamed.fitStream(|| csv.chunks(&path, 100), &target, None, &[]).unwrap();
+        let mut inMemory = Linear::new().epochs(5).lr(0.05);
+        inMemory.fit(&x, &y).unwrap();
+        assert_eq!(streamed.names, vec!["y", "x1", "x2"]);
+        assert_eq!(streamed.lossHistory.len(), 5);
+        for (a, b) in streamed.beta.iter().zip(inMemory.beta.iter()) {
+            assert!(near(*a, *b, 1e-12));
+        }
+
+        // チャンクに分けても学習できる
+        let mut chunked = Linear::new().epochs(5).lr(0.05).scaling(Scaling::ZScore);
+        chunked.fitStream(|| csv.chunks(&path, 3), &target, None, &[]).unwrap();
+        assert!(chunked.beta.iter().all(|b| b.is_finite()));
+
+        let mut robust = Linear::new().scaling(Scaling::Robust);
+        assert!(matches!(robust.fitStream(|| csv.chunks(&path, 3), &target, None, &[]), Err(StreamError::Scaling(_))));
+        let unknown = [U::Col::Name(String::from("z"))];
+        assert!(matches!(Linear::new().fitStream(|| csv.chunks(&path, 3), &unknown, None, &[]), Err(StreamError::Csv(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+// ******************************************************************
+//  Logistic（ロジスティック回帰、多クラスはソフトマックス回帰）
+// ******************************************************************
+
+#![allow(non_snake_case)]
+
+use super::U;   // main.rsのコメントを参照
+use super::linear;
+use super::metrics;
+use super::dataset::View;
+
+// =================================================
+//  ロジスティック回帰モデル
+//  2クラスの場合はシグモイド関数、3クラス以上の場合はソフトマックス関数で
+//  クラスごとの確率を求める
+// =================================================
+pub struct Logistic {
+    epochs: u32,
+    lr: f64,
+
+    // クラスラベル（昇順）
+    labels: Vec<f64>,
+
+    // 係数．weights[k][0]=切片、weights[k][1..]=係数
+    // 2クラスの場合はweights.len()=1（labels[1]である確率の式）
+    // 3クラス以上の場合はweights.len()=クラス数
+    weights: Vec<Vec<f64>>,
+
+    // 説明変数の正規化（線形モデルと同じ方法．デフォルトはmin-max）
+    scaling: linear::Scaling,
+    norm: Vec<linear::Scale>
+}
+
+impl Logistic {
+    pub fn new() -> Self {
+        Logistic {
+            epochs: 100,
+            lr: 0.1,
+            labels: vec![],
+            weights: vec![],
+            scaling: linear::Scaling::MinMax,
+            norm: vec![]
+        }
+    }
+
+    // 学習の繰り返し回数を指定する
+    pub fn epochs(mut self, epochs: u32) -> Self {
+        self.epochs = epochs;
+        self
+    }
+
+    // 学習率を指定する
+    pub fn lr(mut self, lr: f64) -> Self {
+        self.lr = lr;
+        self
+    }
+
+    // 説明変数の正規化の方法を指定する
+    pub fn scaling(mut self, scaling: linear::Scaling) -> Self {
+        self.scaling = scaling;
+        self
+    }
+
+    pub fn print(&self) {
+        println!("epochs = {}", self.epochs);
+        println!("lr = {}", self.lr);
+        println!("labels = {:?}", self.labels);
+        for (k, w) in self.weights.iter().enumerate() {
+            println!("weights[{}] = {:?}", k, w);
+        }
+    }
+
+    // ============================================
+    //  モデル作成（確率的勾配降下法で対数尤度を最大化）
+    //
+    //  @param x: 説明変数(2次元配列. 変数(=列)ごとの値)
+    //  @param y: 目的変数(1列目の値をクラスラベルとして使う)
+    // ============================================
+    pub fn fit(&mut self, x: &U::Matrix, y: &U::Matrix) {
+        self.fitSub(x, y, None);
+    }
+
+    // ============================================
+    //  行ごとの重みを付けてモデルを作成（勾配を重み倍する）
+    //
+    //  @param weights: 行ごとの重み（長さ=行数）
+    // ============================================
+    pub fn fitWeighted(&mut self, x: &U::Matrix, y: &U::Matrix, weights: &[f64]) {
+        self.fitSub(x, y, Some(weights));
+    }
+
+    // fit, fitWeightedの本体
+    fn fitSub(&mut self, x: &U::Matrix, y: &U::Matrix, weights: Option<&[f64]>) {
+        self.labels = metrics::classLabels(&y[0]);
+        let target: U::Matrix = metrics::oneHot(&y[0], &self.labels);
+
+        // 説明変数を正規化
+        self.norm = linear::calcScales(x, self.scaling);
+        let nx = linear::applyScales(x, &self.norm);
+
+        let nExpVars = x.len();
+        let nrows = y[0].len();  // 説明変数の列がない（切片だけの）場合も目的変数の行数で数える
+        let binary = self.labels.len() == 2;
+        let nModels = if binary { 1 } else { self.labels.len() };
+        self.weights = vec![vec![0.0; nExpVars+1]; nModels];
+
+        for _ in 0..self.epochs {
+            for irow in 0..nrows {
+                let p = self.probaRow(&nx, irow);
+                for (k, w) in self.weights.iter_mut().enumerate() {
+                    // 2クラスの場合はlabels[1]の確率(p[1])と正解との差
+                    let kk = if binary { 1 } else { k };
+                    let rowWeight = weights.map_or(1.0, |ws| ws[irow]);
+                    let err: f64 = (p[kk] - target[kk][irow]) * self.lr * rowWeight;
+                    w[0] -= err;    // 切片
+                    for jcol in 0..nExpVars {
+                        w[jcol+1] -= nx[jcol][irow] * err; // 係数
+                    }
+                }
+            }
+        }
+    }
+
+    // -----------------------------------------------
+    //  正規化済みの説明変数nxのirow行目について、クラスごとの確率を計算する
+    // -----------------------------------------------
+    fn probaRow(&self, nx: &U::Matrix, irow: usize) -> Vec<f64> {
+        // 各モデルの線形結合の値
+        let zs: Vec<f64> = self.weights.iter().map(|w| {
+            let mut z: f64 = w[0];
+            for (jcol, col) in nx.iter().enumerate() {
+                z += col[irow] * w[jcol+1];
+            }
+            z
+        }).collect();
+
+        if self.labels.len() == 2 {
+            // シグモイド関数
+            let p = 1.0 / (1.0 + (-zs[0]).exp());
+            vec![1.0 - p, p]
+        } else {
+            // ソフトマックス関数（オーバーフローしないように最大値を引いておく）
+            let zmax = zs.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let es: Vec<f64> = zs.iter().map(|z| (z - zmax).exp()).collect();
+            let sum: f64 = es.iter().sum();
+            es.iter().map(|e| e / sum).collect()
+        }
+    }
+
+    // ============================================================
+    //  クラスごとの確率を計算
+    //
+    //  @param x 説明変数（正規化前）
+    //
+    //  @return クラスごとの確率（Matrix[クラス][行]．クラスの順は学習時のラベルの昇順）
+    // ============================================================
+    pub fn predictProba(&self, x: &U::Matrix) -> U::Matrix {
+        let nx = linear::applyScales(x, &self.norm);
+        self.probaOf(&nx, x[0].len())
+    }
+
+    // 正規化済みの説明変数nxのnrows行分のクラスごとの確率（Matrix[クラス][行]）
+    fn probaOf(&self, nx: &U::Matrix, nrows: usize) -> U::Matrix {
+        let mut proba: U::Matrix = vec![vec![]; self.labels.len()];
+        for irow in 0..nrows {
+            for (col, p) in proba.iter_mut().zip(self.probaRow(nx, irow)) {
+                col.push(p);
+            }
+        }
+        proba
+    }
+
+    // ============================================================
+    //  予測値（最も確率の高いクラスのラベル）を計算
+    //  線形モデル、決定木のpredictと同じく1列の予測値を返す
+    // ============================================================
+    pub fn predict(&self, x: &U::Matrix) -> Vec<f64> {
+        metrics::probaToLabels(&self.predictProba(x), &self.labels)
+    }
+
+    // 予測値を計算（データセットのビューから．列のないビューでも行数はビューで決まる）
+    pub fn predictView(&self, x: &View) -> Vec<f64> {
+        let nx = linear::applyScales(&x.toMatrix(), &self.norm);
+        metrics::probaToLabels(&self.probaOf(&nx, x.nrows()), &self.labels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binarySeparableData() {
+        // x < 5ならクラス0, それ以外はクラス1
+        let x: U::Matrix = vec![(0..10).map(|i| i as f64).collect()];
+        let y: U::Matrix = vec![(0..10).map(|i| if i < 5 { 0.0 } else { 1.0 }).collect()];
+        let mut m = Logistic::new().epochs(500).lr(0.5);
+        m.fit(&x, &y);
+        assert_eq!(m.weights.len(), 1);
+        assert_eq!(m.predict(&x), y[0]);
+
+        let proba = m.predictProba(&x);
+        assert!(proba[1][9] > 0.9 && proba[1][0] < 0.1);
+        assert!((0..10).all(|i| (proba[0][i] + proba[1][i] - 1.0).abs() < 1e-12));
+    }
+
+    #[test]
+    fn softmaxForThreeClasses() {
+        let x: U::Matrix = vec![(0..30).map(|i| i as f64).collect()];
+        let y: U::Matrix = vec![(0..30).map(|i| ((i / 10) * 10) as f64).collect()];
+        let mut m = Logistic::new().epochs(2000).lr(0.5);
+        m.fit(&x, &y);
+        assert_eq!(m.labels, vec![0.0, 10.0, 20.0]);
+        assert_eq!(m.weights.len(), 3);
+
+        let proba = m.predictProba(&x);
+        assert!((0..30).all(|i| (proba.iter().map(|col| col[i]).sum::<f64>() - 1.0).abs() < 1e-12));
+        let acc = metrics::accuracy(&y[0], &m.predict(&x));
+        assert!(acc >= 0.9, "accuracy = {}", acc);
+    }
+
+    #[test]
+    fn zeroWeightRowsAreIgnored() {
+        // 重み0の行(x=1.5)は正規化の範囲を変えないので、その行を除いて学習したものと同じになる
+        let x: U::Matrix = vec![vec![0.0, 1.0, 2.0, 3.0, 1.5]];
+        let y: U::Matrix = vec![vec![0.0, 0.0, 1.0, 1.0, 0.0]];
+        let mut weighted = Logistic::new().epochs(50);
+        weighted.fitWeighted(&x, &y, &[1.0, 1.0, 1.0, 1.0, 0.0]);
+        let mut subset = Logistic::new().epochs(50);
+        subset.fit(&U::MatSelectRow(&x, &[0, 1, 2, 3]), &U::MatSelectRow(&y, &[0, 1, 2, 3]));
+        assert_eq!(weighted.weights, subset.weights);
+
+        // 重みが全て1ならfitと同じ
+        let mut ones = Logistic::new().epochs(50);
+        ones.fitWeighted(&x, &y, &[1.0; 5]);
+        let mut plain = Logistic::new().epochs(50);
+        plain.fit(&x, &y);
+        assert_eq!(ones.weights, plain.weights);
+    }
+}
@@ -8,7 +8,7 @@
 
 use std::env;
 
-use std::time::{Duration, Instant}; // 経過時間測定用
+use std::time::Instant; // 経過時間測定用
 
 // 他のファイルで定義されている関数などを参照する方法：
 //
@@ -21,72 +21,952 @@ use std::time::{Duration, Instant}; // 経過時間測定用
 mod U;  // 「main.rsからU.rs内のpub要素を使う」の意味
 mod zeror;
 mod linear;
+mod linalg;
+mod logistic;
 mod dtree;
+mod ensemble;
+mod dataset;
+mod parallel;
+mod random;
+mod importance;
+mod features;
+mod metrics;
+mod search;
+mod stats;
+
+use dataset::ShapeError;
 
 fn zeroRuleTest(x: &U::Matrix, y: &U::Matrix) {
     let mut z = zeror::ZeroRule::new();
 
     // モデル作成
-    z.fit(&x, &y);
+    if let Err(e) = z.fit(x, y) {
+        println!("dataset error: {}", e);
+        return;
+    }
+    z.print();
 
     // 予測
-    let result = z.predict(&x);
+    let result = match z.predict(x) {
+        Ok(r) => r,
+        Err(e) => { println!("dataset error: {}", e); return; }
+    };
 
     // 結果表示
     println!("*** ZeroRule output ***");
     println!("{:?}", result);
 }
 
-fn linearTest(x: &U::Matrix, y: &U::Matrix) {
-    let mut l = linear::Linear::new();
+// ============================================================
+//  オプションで指定された学習設定の線形モデルを作る
+// ============================================================
+fn newLinear(opts: &Options) -> linear::Linear {
+    let mut l = linear::Linear::new()
+        .solver(opts.solver)
+        .penalty(opts.penalty)
+        .loss(opts.loss)
+        .scaling(opts.scaling)
+        .schedule(opts.schedule)
+        .batchSize(opts.batchSize);
+    if let Some(epochs) = opts.epochs {
+        l = l.epochs(epochs);
+    }
+    if let Some(lr) = opts.lr {
+        l = l.lr(lr);
+    }
+    if opts.shuffle {
+        l = l.shuffle(opts.seed);
+    }
+    if let Some(tol) = opts.tol {
+        l = l.tol(tol);
+    }
+    l
+}
+
+// names = 説明変数の列名, yNames = 目的変数の列名（係数や正規化パラメータの表示に使う）
+fn linearTest(x: &U::Matrix, y: &U::Matrix, names: &[String], yNames: &[String], opts: &Options) {
+    let mut l = newLinear(opts).columnNames(names, yNames);
 
     // モデル作成
-    l.fit(&x, &y);
+    if let Err(e) = l.fit(x, y) {
+        println!("dataset error: {}", e);
+        return;
+    }
 
-    // 予測
-    let result: Vec<f64> = l.predict(&x, false);
+    // 予測（目的変数ごと）
+    let result: U::Matrix = l.predictMulti(x);
 
     println!("*** Linear output ***");
+    for r in &result {
+        println!("{:?}", r);
+    }
+    l.print();
+    l.printReport();
+}
+
+// ============================================================
+//  線形モデルをCSVファイルから少しずつ読みながら学習する（--stream, --partial）
+//  ファイル全体をメモリに読み込まないので、予測値は表示せず係数だけを表示する
+//
+//  @param csv 読み込みの設定
+// ============================================================
+fn streamTest(csv: &U::CSV, filePath: &str, opts: &Options) {
+    let chunkRows = opts.stream.or(opts.partial).unwrap_or(1);
+
+    // 目的変数の列（指定がなければ末尾のnOutputs列）
+    let target: Vec<U::Col> = match opts.target {
+        Some(ref t) => t.clone(),
+        None => {
+            let names = match csv.chunks(filePath, chunkRows) {
+                Ok(c) => c.colNames(),
+                Err(e) => { println!("load error {}: {}", filePath, e); return; }
+            };
+            if opts.nOutputs == 0 || opts.nOutputs >= names.len() {
+                println!("invalid number of outputs {} (columns={})", opts.nOutputs, names.len());
+                return;
+            }
+            names[names.len()-opts.nOutputs..].iter().map(|n| U::Col::Name(n.clone())).collect()
+        }
+    };
+
+    let mut l = newLinear(opts);
+    if opts.stream.is_some() {
+        // 1パス目で正規化パラメータ、以降エポックごとにファイルを読み直す
+        if let Err(e) = l.fitStream(|| csv.chunks(filePath, chunkRows), &target, opts.features.as_deref(), &opts.drop) {
+            println!("stream error {}: {}", filePath, e);
+            return;
+        }
+        println!("*** Linear (streamed by {} rows) ***", chunkRows);
+    } else {
+        // バッチが順に届く場合（最初のバッチで正規化パラメータを決める）
+        let chunks = match csv.chunks(filePath, chunkRows) {
+            Ok(c) => c,
+            Err(e) => { println!("load error {}: {}", filePath, e); return; }
+        };
+        for (i, chunk) in chunks.enumerate() {
+            let xy = chunk.and_then(|c| c.splitXY(&target, opts.features.as_deref(), &opts.drop));
+            let (xc, yc) = match xy {
+                Ok(xy) => xy,
+                Err(e) => { println!("load error {}: {}", filePath, e); return; }
+            };
+            if i == 0 {
+                l = l.columnNames(&xc.colNames, &yc.colNames);     // 係数を列名で表示する
+            }
+            if let Err(e) = l.partialFit(&xc.cols, &yc.cols) {
+                println!("dataset error: {}", e);
+                return;
+            }
+        }
+        println!("*** Linear (partialFit by {} rows) ***", chunkRows);
+    }
+    l.print();
+    l.printReport();
+}
+
+// ============================================================
+//  オプションで指定された設定の決定木を作る
+// ============================================================
+fn newDecisionTree(opts: &Options, max_depth: u32) -> dtree::DecisionTree {
+    let mut d = dtree::DecisionTree::new(1, max_depth).leafSolver(opts.solver).leaf(opts.leaf).categorical(&opts.nativeColumns).threads(opts.threads).seed(opts.seed);
+    if let Some(k) = opts.maxFeatures {
+        d = d.maxFeatures(k);
+    }
+    d
+}
+
+// ============================================================
+//  オプションで指定された設定の決定木のアンサンブルを作る
+//
+//  @param modelType "b"（バギング）, "f"（ランダムフォレスト）, "e"（ExtraTrees）
+//  @param nTrees 木の数
+// ============================================================
+fn newForest(modelType: &str, opts: &Options, nTrees: usize, max_depth: u32) -> ensemble::Forest {
+    let mut f = match modelType {
+        "b" => ensemble::Forest::bagging(nTrees, max_depth),
+        "f" => ensemble::Forest::randomForest(nTrees, max_depth),
+        _ => ensemble::Forest::extraTrees(nTrees, max_depth).bootstrap(opts.bootstrap)
+    };
+    f = f.leafSolver(opts.solver)
+        .categorical(&opts.nativeColumns)
+        .seed(opts.seed)
+        .threads(opts.threads);
+    if let Some(k) = opts.maxFeatures {
+        f = f.maxFeatures(k);
+    }
+    f
+}
+
+// アンサンブルのモデル名
+fn forestName(modelType: &str) -> &'static str {
+    match modelType {
+        "b" => "Bagging",
+        "f" => "RandomForest",
+        _ => "ExtraTrees"
+    }
+}
+
+// 1列目の予測値（回帰のアンサンブルのOOBスコア、重要度用）
+fn firstOutput(p: U::Matrix) -> Vec<f64> {
+    p.into_iter().next().unwrap_or_default()
+}
+
+// -----------------------------------------------
+//  クラスごとの予測値(Matrix[クラス][行])を確率に補正し、確率が最も大きいクラスを予測値とする
+// -----------------------------------------------
+fn predictedLabels(proba: U::Matrix, labels: &[f64]) -> Vec<f64> {
+    let mut proba = proba;
+    metrics::normalizeProba(&mut proba);
+    metrics::probaToLabels(&proba, labels)
+}
+
+// ============================================================
+//  オプションで指定された学習設定のロジスティック回帰モデルを作る
+// ============================================================
+fn newLogistic(opts: &Options) -> logistic::Logistic {
+    let mut g = logistic::Logistic::new().scaling(opts.scaling);
+    if let Some(epochs) = opts.epochs {
+        g = g.epochs(epochs);
+    }
+    if let Some(lr) = opts.lr {
+        g = g.lr(lr);
+    }
+    g
+}
+
+fn logisticTest(x: &U::Matrix, y: &U::Matrix, opts: &Options) {
+    let mut g = newLogistic(opts);
+
+    // モデル作成
+    g.fit(x, y);
+
+    // 予測（最も確率の高いクラス）
+    let result: Vec<f64> = g.predict(x);
+
+    println!("*** Logistic output ***");
     println!("{:?}", result);
+    g.print();
 }
 
-fn decisionTreeTest(x: &U::Matrix, y: &U::Matrix, max_depth: u32) {
+fn decisionTreeTest(x: &U::Matrix, y: &U::Matrix, opts: &Options) {
+    let max_depth = opts.max_depth;
     println!("max_depth={}", max_depth);
 
     let start = Instant::now();
 
-    let mut d = dtree::DecisionTree::new(1, max_depth);
+    let mut d = newDecisionTree(opts, max_depth);
 
     // モデル作成
-    d.fit(&x, &y, max_depth);
+    if let Err(e) = d.fit(x, y, max_depth) {
+        println!("dataset error: {}", e);
+        return;
+    }
     d.print();
 
-    // 予測
-    let result = d.predict(&x);
+    // 予測（目的変数ごと）
+    let result = match d.predictMulti(x) {
+        Ok(r) => r,
+        Err(e) => { println!("dataset error: {}", e); return; }
+    };
 
     let elapsed = start.elapsed();
 
     println!("*** DecisionTree output ***");
-    println!("{:?}", result);
+    for r in &result {
+        println!("{:?}", r);
+    }
 
-    println!("{}.{:03}秒経過しました。", elapsed.as_secs(), elapsed.subsec_nanos() / 1_000_000);
+    println!("{}.{:03}秒経過しました。", elapsed.as_secs(), elapsed.subsec_millis());
     // U::stdev()のテスト
     // let v: &Vec<f64> = &x[0];
     // let sd = U::stdev(&v);
     // println!("sd={}", sd);
+}
+
+// ============================================================
+//  決定木のアンサンブル(b, f, e)
+//  ブートストラップ標本で学習した場合は、OOBでの決定係数と平均二乗誤差も表示する
+// ============================================================
+fn forestTest(modelType: &str, x: &U::Matrix, y: &U::Matrix, opts: &Options) {
+    println!("max_depth={}, trees={}", opts.max_depth, opts.nTrees);
+
+    let start = Instant::now();
+
+    let mut f = newForest(modelType, opts, opts.nTrees, opts.max_depth);
+
+    // モデル作成
+    if let Err(e) = f.fit(x, y) {
+        println!("dataset error: {}", e);
+        return;
+    }
+    f.print();
+
+    // 予測（目的変数ごと．各木の予測値の平均）
+    let result = match f.predictMulti(x) {
+        Ok(r) => r,
+        Err(e) => { println!("dataset error: {}", e); return; }
+    };
+
+    let elapsed = start.elapsed();
+
+    println!("*** {} output ***", forestName(modelType));
+    for r in &result {
+        println!("{:?}", r);
+    }
+
+    // OOB（各行を学習に使わなかった木だけの予測値）での評価
+    if let (Some((r2, n)), Some((negMse, _))) = (f.oobScore(&y[0], firstOutput, metrics::r2), f.oobScore(&y[0], firstOutput, metrics::negMse)) {
+        println!("OOB R2 = {:.4}, OOB MSE = {:.4} ({} of {} rows)", r2, -negMse, n, y[0].len());
+    }
+
+    println!("{}.{:03}秒経過しました。", elapsed.as_secs(), elapsed.subsec_millis());
+}
+
+// ============================================================
+//  クラスごと(one-vs-rest)に回帰モデルを作り、クラスごとの予測値を求める
+//
+//  @param xtrain, ytrain 学習用データ（ytrainの1列目はクラスラベル）
+//  @param xtest 予測する説明変数
+//  @param labels クラスラベル（classLabels()の結果）
+//  @param fitPredict (学習用x, 学習用y(1列), 予測するx) -> 予測値（モデルの誤りはErr）
+//
+//  @return クラスごとの予測値（Matrix[クラス][行]．確率としての補正前）．fitPredictがErrを返せばそのErr
+// ============================================================
+fn oneVsRest<F, E>(xtrain: &U::Matrix, ytrain: &U::Matrix, xtest: &U::Matrix, labels: &[f64], fitPredict: F) -> Result<U::Matrix, E>
+    where F: Fn(&U::Matrix, &U::Matrix, &U::Matrix) -> Result<Vec<f64>, E>
+{
+    metrics::oneHot(&ytrain[0], labels).into_iter()
+        .map(|col| fitPredict(xtrain, &vec![col], xtest))
+        .collect()
+}
+
+// クラスごとの確率（Matrix[クラス][行]）とOOBでの(正解率, 計算に使った行数)（predictProbaの結果）
+type ProbaWithOob = (U::Matrix, Option<(f64, usize)>);
+
+// ============================================================
+//  各クラスの確率を予測する
+//  ZeroRule、決定木のアンサンブルは目的変数が複数列でもそのまま扱えるのでone-hot表現のyを直接学習する．
+//  線形モデル、決定木はクラスごと(one-vs-rest)にモデルを作って回帰し、
+//  その予測値を確率とみなす．ロジスティック回帰はクラスの確率を直接求める．
+//  葉がロジスティック回帰の決定木は予測したクラスの確率を1とする
+//
+//  @param y 目的変数（クラスラベル）
+//  @param labels クラスラベル（classLabels()の結果）
+//
+//  @return (クラスごとの確率（Matrix[クラス][行]）, OOBでの(正解率, 計算に使った行数)（ブートストラップ標本で学習したアンサンブルのみ）)．
+//  未知のモデルならNone．列の長さが揃っていなければShapeError
+// ============================================================
+fn predictProba(modelType: &str, x: &U::Matrix, y: &U::Matrix, labels: &[f64], opts: &Options) -> Result<Option<ProbaWithOob>, ShapeError> {
+    let max_depth = opts.max_depth;
+    // --balancedなら少ないクラスの行ほど重くする
+    let weights: Option<Vec<f64>> = if opts.balanced { Some(metrics::balancedWeights(&y[0], labels)) } else { None };
+    let mut proba: U::Matrix;
+    let mut oob: Option<(f64, usize)> = None;
+    match modelType {
+        "z" => {
+            let mut z = zeror::ZeroRule::new();
+            let yOneHot = metrics::oneHot(&y[0], labels);
+            match weights {
+                Some(ref w) => z.fitWeighted(x, &yOneHot, w)?,
+                None => z.fit(x, &yOneHot)?
+            }
+            proba = z.predict(x)?;
+        },
+        "l" => {
+            proba = oneVsRest(x, y, x, labels, |xt, yt, xe| {
+                let mut l = newLinear(opts);
+                match weights {
+                    Some(ref w) => l.fitWeighted(xt, yt, w)?,
+                    None => l.fit(xt, yt)?
+                }
+                Ok(l.predict(xe, false))
+            })?;
+        },
+        "d" if opts.leaf == dtree::LeafKind::Logistic => {
+            let mut d = newDecisionTree(opts, max_depth);
+            match weights {
+                Some(ref w) => d.fitWeighted(x, y, w, max_depth)?,
+                None => d.fit(x, y, max_depth)?
+            }
+            proba = metrics::oneHot(&d.predict(x)?, labels);
+        },
+        "d" => {
+            proba = oneVsRest(x, y, x, labels, |xt, yt, xe| {
+                let mut d = newDecisionTree(opts, max_depth);
+                match weights {
+                    Some(ref w) => d.fitWeighted(xt, yt, w, max_depth)?,
+                    None => d.fit(xt, yt, max_depth)?
+                }
+                d.predict(xe)
+            })?;
+        },
+        "b" | "f" | "e" => {
+            // 1つのアンサンブルで全てのクラスの列を学習し、列ごとの予測値（各木の平均）を使う
+            // OOBの正解率もOOBでの列ごとの予測値が最も大きいクラスで求める
+            let mut f = newForest(modelType, opts, opts.nTrees, max_depth);
+            let yOneHot = metrics::oneHot(&y[0], labels);
+            match weights {
+                Some(ref w) => f.fitWeighted(x, &yOneHot, w)?,
+                None => f.fit(x, &yOneHot)?
+            }
+            proba = f.predictMulti(x)?;
+            oob = f.oobScore(&y[0], |p| predictedLabels(p, labels), metrics::accuracy);
+        },
+        "g" => {
+            let mut g = newLogistic(opts);
+            match weights {
+                Some(ref w) => { g.fitWeighted(x, y, w); },
+                None => { g.fit(x, y); }
+            }
+            proba = g.predictProba(x);
+        },
+        _ => { return Ok(None); }
+    }
+    metrics::normalizeProba(&mut proba);
+    Ok(Some((proba, oob)))
+}
+
+// ============================================================
+//  分類モデルとして評価する
+//  目的変数の値(wineのqualityなど)をクラスラベルとみなす
+//
+//  opts.curveFile ROC曲線、PR曲線の点を書き出すCSVファイル（Noneなら書き出さない）
+// ============================================================
+fn classificationTest(modelType: &str, x: &U::Matrix, y: &U::Matrix, opts: &Options) {
+    let labels = metrics::classLabels(&y[0]);
+
+    let (proba, oob) = match predictProba(modelType, x, y, &labels, opts) {
+        Ok(Some(p)) => p,
+        Ok(None) => { println!("unknown model"); return; }
+        Err(e) => { println!("dataset error: {}", e); return; }
+    };
+    let yPred = metrics::probaToLabels(&proba, &labels);
+
+    let cm = metrics::ConfusionMatrix::new(&y[0], &yPred);
+
+    println!("*** Confusion matrix ***");
+    cm.print();
+    println!();
+    println!("*** Classification report ***");
+    cm.printReport();
+    println!("log loss = {:.4}", metrics::logLoss(&y[0], &proba, &labels));
+    if let Some((acc, n)) = oob {
+        println!("OOB accuracy = {:.4} ({} of {} rows)", acc, n, y[0].len());
+    }
+
+    // ROC曲線、PR曲線
+    let curves = metrics::oneVsRestCurves(&y[0], &proba, &labels);
+    println!();
+    println!("*** ROC / PR ***");
+    metrics::printCurveSummary(&curves);
+
+    if let Some(ref fpath) = opts.curveFile {
+        match metrics::writeCurvesCsv(fpath, &curves) {
+            Ok(_) => { println!("curve points written to {}", fpath); },
+            Err(e) => { println!("write error {} ({})", fpath, e); }
+        }
+    }
+}
+
+// ============================================================
+//  ハイパーパラメータ探索
+//  d ... max_depth
+//  b, f, e ... max_depth, trees
+//  l ... epochs, lr
+//  g ... epochs, lr
+//
+//  opts.searchMethod "grid"（全組み合わせ）または"random"（opts.nIter個を無作為に選ぶ）
+//  opts.nFolds 交差検証の分割数
+//  opts.scoring "mse"（平均二乗誤差の符号反転）, "r2"（決定係数）, "accuracy"（正解率）
+//  accuracyの場合、クラスラベルを直接予測しないモデルはclassificationTestと同じく
+//  one-vs-restの予測値が最も大きいクラスを予測値とする
+// ============================================================
+fn searchTest(modelType: &str, x: &U::Matrix, y: &U::Matrix, opts: &Options) {
+    let method: &str = opts.searchMethod.as_deref().unwrap_or("grid");
+    let scoring: &str = &opts.scoring;
+    let k = opts.nFolds;
+
+    let space = match modelType {
+        "d" => search::ParamSpace::new()
+            .add("max_depth", &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+        "b" | "f" | "e" => search::ParamSpace::new()
+            .add("max_depth", &[2.0, 3.0, 4.0, 5.0])
+            .add("trees", &[5.0, 10.0, 20.0]),
+        "l" => {
+            let mut space = search::ParamSpace::new();
+            if let linear::Solver::Sgd = opts.solver {
+                space = space
+                    .add("epochs", &[10.0, 20.0, 50.0, 100.0])
+                    .add("lr", &[0.001, 0.005, 0.01, 0.05, 0.1]);
+            }
+            if let linear::Penalty::None = opts.penalty {
+            } else {
+                space = space.add("alpha", &[0.0001, 0.001, 0.01, 0.1, 1.0]);
+            }
+            space
+        },
+        "g" => search::ParamSpace::new()
+            .add("epochs", &[50.0, 100.0, 200.0])
+            .add("lr", &[0.01, 0.05, 0.1, 0.5]),
+        _ => search::ParamSpace::new()
+    };
+    if space.params.is_empty() {
+        println!("no parameters to search for model {}", modelType);
+        return;
+    }
+
+    let scorer = match scorerOf(scoring) {
+        Some(f) => f,
+        None => { println!("unknown scoring {}", scoring); return; }
+    };
 
-    // d.test1(&x, &y);
-    // d.test_make_split(&x, &y);
-    // d.test_split_tree(&x, &y);
+    let mut rng = random::Rng::new(opts.seed);
+    let candidates = match method {
+        "grid" => space.grid(),
+        "random" => space.sample(opts.nIter, &mut rng),
+        _ => { println!("unknown search method {}", method); return; }
+    };
+
+    let start = Instant::now();
+
+    let folds = search::kfold(x[0].len(), k, &mut rng);
+    if folds.is_empty() {
+        println!("cross validation needs at least 2 rows (rows={})", x[0].len());
+        return;
+    }
+    let fitPredict = |params: &search::Params, xtrain: &U::Matrix, ytrain: &U::Matrix, xtest: &U::Matrix| -> Result<Vec<f64>, ShapeError> {
+        match modelType {
+            "d" => {
+                let max_depth = search::param(params, "max_depth").map_or(opts.max_depth, |v| v as u32);
+                let mut d = newDecisionTree(opts, max_depth).threads(1);   // 候補ごとに並列化しているので木の中は1スレッド
+                d.fit(xtrain, ytrain, max_depth)?;
+                d.predict(xtest)
+            },
+            "b" | "f" | "e" => {
+                let max_depth = search::param(params, "max_depth").map_or(opts.max_depth, |v| v as u32);
+                let nTrees = search::param(params, "trees").map_or(opts.nTrees, |v| v as usize);
+                let mut f = newForest(modelType, opts, nTrees, max_depth).threads(1);   // 候補ごとに並列化しているので木は1スレッドで順に学習する
+                f.fit(xtrain, ytrain)?;
+                f.predict(xtest)
+            },
+            "g" => {
+                let mut g = newLogistic(opts);
+                if let Some(epochs) = search::param(params, "epochs") {
+                    g = g.epochs(epochs as u32);
+                }
+                if let Some(lr) = search::param(params, "lr") {
+                    g = g.lr(lr);
+                }
+                g.fit(xtrain, ytrain);
+                Ok(g.predict(xtest))
+            },
+            _ => {
+                let mut l = newLinear(opts);
+                if let Some(epochs) = search::param(params, "epochs") {
+                    l = l.epochs(epochs as u32);
+                }
+                if let Some(lr) = search::param(params, "lr") {
+                    l = l.lr(lr);
+                }
+                if let Some(alpha) = search::param(params, "alpha") {
+                    l = l.penalty(opts.penalty.withAlpha(alpha));
+                }
+                l.fit(xtrain, ytrain)?;
+                Ok(l.predict(xtest, false))
+            }
+        }
+    };
+
+    // 正解率はクラスラベルの予測値で計算する
+    let labels = metrics::classLabels(&y[0]);
+    let predictsLabels = modelType == "g" || (modelType == "d" && opts.leaf == dtree::LeafKind::Logistic);
+    let results = search::search(&candidates, x, y, &folds, opts.threads, |params, xtrain, ytrain, xtest| {
+        if scoring == "accuracy" && !predictsLabels {
+            let proba = oneVsRest(xtrain, ytrain, xtest, &labels, |xt, yt, xe| fitPredict(params, xt, yt, xe))?;
+            Ok(predictedLabels(proba, &labels))
+        } else {
+            fitPredict(params, xtrain, ytrain, xtest)
+        }
+    }, scorer);
+    let results = match results {
+        Ok(r) => r,
+        Err(e) => { println!("dataset error: {}", e); return; }
+    };
+
+    let elapsed = start.elapsed();
+
+    println!("*** {} search ({}-fold CV, scoring={}) ***", method, k, scoring);
+    search::printResults(&results);
+    println!("{}.{:03}秒経過しました。", elapsed.as_secs(), elapsed.subsec_millis());
+}
+
+// -----------------------------------------------
+//  スコアの名前(--scoring)からスコア関数を得る
+// -----------------------------------------------
+fn scorerOf(scoring: &str) -> Option<search::Scorer> {
+    match scoring {
+        "mse" => Some(metrics::negMse),
+        "r2" => Some(metrics::r2),
+        "accuracy" => Some(metrics::accuracy),
+        _ => None
+    }
+}
+
+// 学習済みのモデルで予測値を返す関数（importanceTest用）
+type PredictFn = Box<dyn Fn(&U::Matrix) -> Result<Vec<f64>, ShapeError> + Sync>;
+
+// -----------------------------------------------
+//  全ての行で学習したモデルの予測関数を作る（importanceTest用）
+// -----------------------------------------------
+fn fittedPredictor(modelType: &str, x: &U::Matrix, y: &U::Matrix, opts: &Options) -> Result<PredictFn, ShapeError> {
+    let predict: PredictFn = match modelType {
+        "z" => {
+            let mut z = zeror::ZeroRule::new();
+            z.fit(x, y)?;
+            Box::new(move |x| z.predict(x).map(|mut p| p.swap_remove(0)))
+        },
+        "d" => {
+            let mut d = newDecisionTree(opts, opts.max_depth);
+            d.fit(x, y, opts.max_depth)?;
+            Box::new(move |x| d.predict(x))
+        },
+        "g" => {
+            let mut g = newLogistic(opts);
+            g.fit(x, y);
+            Box::new(move |x| Ok(g.predict(x)))
+        },
+        _ => {
+            let mut l = newLinear(opts);
+            l.fit(x, y)?;
+            Box::new(move |x| Ok(l.predict(x, false)))
+        }
+    };
+    Ok(predict)
+}
+
+// ============================================================
+//  決定木のアンサンブル(b, f, e)の並べ替えによる重要度
+//  ブートストラップ標本で学習した場合は、木ごとに学習に使わなかった行(OOB)だけで並べ替える
+//  scoring=accuracyの場合はone-hot表現の全てのクラスの列を1つのアンサンブルで学習し、
+//  列ごとの予測値が最も大きいクラスを予測値として正解率を求める
+//
+//  @return (列ごとの重要度, OOBで求めたか)．列の長さが揃っていなければShapeError
+// ============================================================
+fn forestImportance(modelType: &str, x: &U::Matrix, y: &U::Matrix, opts: &Options, scorer: search::Scorer, repeats: usize) -> Result<(Vec<importance::Importance>, bool), ShapeError> {
+    let labels: Option<Vec<f64>> = if opts.scoring == "accuracy" { Some(metrics::classLabels(&y[0])) } else { None };
+    let toPred = |p: U::Matrix| match labels {
+        Some(ref l) => predictedLabels(p, l),
+        None => firstOutput(p)
+    };
+
+    let mut f = newForest(modelType, opts, opts.nTrees, opts.max_depth);
+    match labels {
+        Some(ref l) => f.fit(x, &metrics::oneHot(&y[0], l))?,
+        None => f.fit(x, y)?
+    }
+    if f.oobPrediction().is_some() {
+        return Ok((f.oobImportance(x, &y[0], toPred, scorer, repeats, opts.seed)?, true));
+    }
+    let importances = importance::permutationImportance(x, &y[0], |x| f.predictMulti(x).map(toPred), scorer, repeats, opts.seed, opts.threads)?;
+    Ok((importances, false))
+}
+
+// ============================================================
+//  並べ替えによる特徴量の重要度(permutation importance)
+//  全ての行で学習したモデルについて、列ごとに値を並べ替えたときのスコアの低下量を表示する
+//  ブートストラップ標本で学習したアンサンブルはOOBで求める(forestImportance)
+//
+//  opts.seed 並べ替えのシード．opts.scoring スコア（1列目の目的変数で計算する）
+// ============================================================
+fn importanceTest(modelType: &str, x: &U::Matrix, y: &U::Matrix, names: &[String], opts: &Options, repeats: usize) {
+    let scorer = match scorerOf(&opts.scoring) {
+        Some(f) => f,
+        None => { println!("unknown scoring {}", opts.scoring); return; }
+    };
+
+    let start = Instant::now();
+
+    let importances = match modelType {
+        "b" | "f" | "e" => forestImportance(modelType, x, y, opts, scorer, repeats),
+        _ => fittedPredictor(modelType, x, y, opts)
+            .and_then(|predict| importance::permutationImportance(x, &y[0], predict, scorer, repeats, opts.seed, opts.threads))
+            .map(|imps| (imps, false))
+    };
+    let (importances, oob) = match importances {
+        Ok(r) => r,
+        Err(e) => { println!("dataset error: {}", e); return; }
+    };
+
+    let elapsed = start.elapsed();
+
+    if oob {
+        println!("*** OOB permutation importance ({} repeats per tree, scoring={}, seed={}) ***", repeats, opts.scoring, opts.seed);
+    } else {
+        println!("*** permutation importance ({} repeats, scoring={}, seed={}) ***", repeats, opts.scoring, opts.seed);
+    }
+    importance::printImportances(&importances, names);
+    println!("{}.{:03}秒経過しました。", elapsed.as_secs(), elapsed.subsec_millis());
+}
+
+// =================================================
+//  コマンドラインオプション
+// =================================================
+struct Options {
+    max_depth: u32,     // 決定木の最大深度
+    classify: bool,     // 分類として評価するか
+    balanced: bool,     // 分類の時、クラスの偏りを補正する重みを付けて学習するか
+    curveFile: Option<String>,      // ROC/PR曲線の出力先
+    searchMethod: Option<String>,   // ハイパーパラメータ探索の方法
+    nIter: usize,       // ランダムサーチで試す候補数
+    nFolds: usize,      // 交差検証の分割数
+    seed: u64,          // 乱数のシード
+    scoring: String,    // 探索のスコア
+    epochs: Option<u32>,    // 線形モデル、ロジスティック回帰の学習の繰り返し回数（Noneなら各モデルのデフォルト）
+    lr: Option<f64>,        // 線形モデル、ロジスティック回帰の学習率（Noneなら各モデルのデフォルト）
+    schedule: linear::LrSchedule,   // 学習率のスケジュール
+    batchSize: usize,   // ミニバッチの大きさ
+    shuffle: bool,      // エポックごとに行をシャッフルするか（シードはseed）
+    tol: Option<f64>,   // 早期終了の閾値
+    solver: linear::Solver, // 線形モデル（決定木の葉も含む）の回帰係数の求め方
+    penalty: linear::Penalty,   // 線形モデルの正則化
+    loss: linear::Loss,         // 線形モデルの損失関数
+    pathAlphas: Option<usize>,  // Someなら正則化パスを表示する（alphaの個数）
+    leaf: dtree::LeafKind,      // 決定木の葉のモデルの種類
+    scaling: linear::Scaling,   // 線形モデル、ロジスティック回帰の正規化の方法
+    polyDegree: u32,    // 多項式特徴量の最大次数（1なら元の列のみ）
+    interactions: bool, // 2列ずつの積(交互作用項)を説明変数に加えるか
+    nOutputs: usize,    // 目的変数の列数（--targetがなければ末尾のnOutputs列を目的変数にする）
+    target: Option<Vec<U::Col>>,    // 目的変数の列（Noneなら末尾のnOutputs列）
+    features: Option<Vec<U::Col>>,  // 説明変数の列（Noneなら目的変数以外の全ての列）
+    drop: Vec<U::Col>,              // 説明変数から除く列
+    delimiter: Option<char>,    // CSVの区切り文字（Noneなら推定する）
+    header: bool,               // CSVの1行目が列名か
+    comment: Option<char>,      // CSVのコメント行の先頭文字
+    columns: Option<Vec<U::Col>>,   // CSVから読み込む列（Noneなら全て）
+    naValues: Option<String>,       // 欠損値とみなすセルの内容（カンマ区切り．Noneならデフォルト）
+    impute: Option<features::ImputeStrategy>,   // 説明変数の欠損値の補完方法（Noneなら補完しない）
+    categorical: Vec<U::Col>,   // カテゴリ列として読む列（他の列は内容から推定する）
+    text: Vec<U::Col>,          // テキスト列として読む列（説明変数には使わない）
+    encoding: features::Encoding,   // カテゴリ列の数値化の方法
+    threads: usize,     // 並列に学習するスレッド数（決定木の分割探索、アンサンブルの木、ハイパーパラメータ探索の候補、重要度の列）
+    nTrees: usize,      // アンサンブル(b, f, e)の木の数
+    bootstrap: bool,    // ExtraTrees(e)の各木をブートストラップ標本で学習するか（b, fは常にブートストラップ）
+    maxFeatures: Option<usize>, // 決定木がノードごとに分割を探す列の数（Noneなら全ての列．列の選び方はseedで決まる）
+    importance: Option<usize>,  // Someなら並べ替えによる特徴量の重要度を表示する（1列あたりの並べ替えの回数）
+    stream: Option<usize>,      // Someなら線形モデルをファイルからN行ずつ読みながら学習する(fitStream)
+    partial: Option<usize>,     // Someなら線形モデルをN行ずつのバッチでpartialFitする（1パス）
+    nativeColumns: Vec<usize>   // 決定木が水準の集合で分割する説明変数（の列番号．--encoding nativeの場合に読み込み後に決まる）
+}
+
+// -----------------------------------------------
+//  args[i]のオプションの値(args[i+1])を型Tとして取り出す
+// -----------------------------------------------
+fn optValue<T: std::str::FromStr>(args: &[String], i: usize) -> Result<T, String> {
+    match args.get(i+1) {
+        Some(v) => v.parse::<T>().map_err(|_| format!("invalid value for {}: {}", args[i], v)),
+        None => Err(format!("missing value for {}", args[i]))
+    }
+}
+
+// -----------------------------------------------
+//  args[i]のオプションの値(args[i+1])をカンマ区切りの列の指定（列名か列番号）として取り出す
+// -----------------------------------------------
+fn optColumns(args: &[String], i: usize) -> Result<Vec<U::Col>, String> {
+    let cols: String = optValue(args, i)?;
+    Ok(cols.split(',').map(|c| c.parse::<U::Col>().unwrap()).collect())
+}
+
+// ============================================================
+//  コマンドラインオプションを解析する
+//
+//  @param args モデル種別、ファイル名より後ろの引数
+//
+//  @return 解析結果．不明なオプションや値の誤りがあればエラーメッセージ
+// ============================================================
+fn parseOptions(args: &[String]) -> Result<Options, String> {
+    let mut opts = Options {
+        max_depth: 3,   // default depth
+        classify: false,
+        balanced: false,
+        curveFile: None,
+        searchMethod: None,
+        nIter: 10,
+        nFolds: 5,
+        seed: 0,
+        scoring: String::from("mse"),
+        epochs: None,
+        lr: None,
+        schedule: linear::LrSchedule::Constant,
+        batchSize: 1,
+        shuffle: false,
+        tol: None,
+        solver: linear::Solver::Sgd,
+        penalty: linear::Penalty::None,
+        loss: linear::Loss::Squared,
+        pathAlphas: None,
+        leaf: dtree::LeafKind::Linear,
+        scaling: linear::Scaling::MinMax,
+        polyDegree: 1,
+        interactions: false,
+        nOutputs: 1,
+        target: None,
+        features: None,
+        drop: vec![],
+        delimiter: None,
+        header: true,
+        comment: None,
+        columns: None,
+        naValues: None,
+        impute: None,
+        categorical: vec![],
+        text: vec![],
+        encoding: features::Encoding::OneHot,
+        threads: parallel::availableThreads(),
+        nTrees: 10,
+        bootstrap: false,
+        maxFeatures: None,
+        importance: None,
+        stream: None,
+        partial: None,
+        nativeColumns: vec![]
+    };
+
+    let mut i = 0;
+    while i < args.len() {
+        let opt: &str = &args[i];
+        // 値を取らないオプション
+        match opt {
+            "-c" => { opts.classify = true; i += 1; continue; },
+            "--balanced" => { opts.balanced = true; i += 1; continue; },
+            "--shuffle" => { opts.shuffle = true; i += 1; continue; },
+            "--interactions" => { opts.interactions = true; i += 1; continue; },
+            "--bootstrap" => { opts.bootstrap = true; i += 1; continue; },
+            "--no-header" => { opts.header = false; i += 1; continue; },
+            _ => {}
+        }
+
+        // 以下は値を取るオプション
+        match opt {
+            "-d" => { opts.max_depth = optValue(args, i)?; },
+            "--curve" => { opts.curveFile = Some(optValue(args, i)?); },
+            "--search" => { opts.searchMethod = Some(optValue(args, i)?); },
+            "--iter" => { opts.nIter = optValue(args, i)?; },
+            "--cv" => { opts.nFolds = optValue(args, i)?; },
+            "--seed" => { opts.seed = optValue(args, i)?; },
+            "--scoring" => { opts.scoring = optValue(args, i)?; },
+            "--epochs" => { opts.epochs = Some(optValue(args, i)?); },
+            "--lr" => { opts.lr = Some(optValue(args, i)?); },
+            "--schedule" => { opts.schedule = optValue(args, i)?; },
+            "--batch" => { opts.batchSize = optValue(args, i)?; },
+            "--tol" => { opts.tol = Some(optValue(args, i)?); },
+            "--solver" => { opts.solver = optValue(args, i)?; },
+            "--penalty" => { opts.penalty = optValue(args, i)?; },
+            "--loss" => { opts.loss = optValue(args, i)?; },
+            "--path" => { opts.pathAlphas = Some(optValue(args, i)?); },
+            "--leaf" => { opts.leaf = optValue(args, i)?; },
+            "--scaling" => { opts.scaling = optValue(args, i)?; },
+            "--poly" => { opts.polyDegree = optValue(args, i)?; },
+            "--outputs" => { opts.nOutputs = optValue(args, i)?; },
+            "--delim" => {
+                // タブは"tab"または"\t"でも指定できる
+                let d: String = optValue(args, i)?;
+                opts.delimiter = match d.as_str() {
+                    "tab" | "\\t" => Some('\t'),
+                    _ => Some(optValue(args, i)?)
+                };
+            },
+            "--comment" => { opts.comment = Some(optValue(args, i)?); },
+            "--impute" => { opts.impute = Some(optValue(args, i)?); },
+            "--na" => { opts.naValues = Some(optValue(args, i)?); },
+            "--columns" => { opts.columns = Some(optColumns(args, i)?); },
+            "--target" => { opts.target = Some(optColumns(args, i)?); },
+            "--features" => { opts.features = Some(optColumns(args, i)?); },
+            "--drop" => { opts.drop = optColumns(args, i)?; },
+            "--categorical" => { opts.categorical = optColumns(args, i)?; },
+            "--text" => { opts.text = optColumns(args, i)?; },
+            "--encoding" => { opts.encoding = optValue(args, i)?; },
+            "--threads" => { opts.threads = optValue(args, i)?; },
+            "--trees" => { opts.nTrees = optValue(args, i)?; },
+            "--max-features" => { opts.maxFeatures = Some(optValue(args, i)?); },
+            "--importance" => { opts.importance = Some(optValue(args, i)?); },
+            "--stream" => { opts.stream = Some(optValue(args, i)?); },
+            "--partial" => { opts.partial = Some(optValue(args, i)?); },
+            _ => { return Err(format!("unknown option {}", opt)); }
+        }
+        i += 2; // オプション名と値
+    }
+
+    Ok(opts)
+}
+
+// ============================================================
+//  線形モデルの正則化パスを表示する
+//  alphaを大きい値から小さい値に変えたときの各係数（正規化後の空間での値）
+//
+//  @param names 説明変数の列名
+//  @param nAlphas alphaの個数
+// ============================================================
+fn pathTest(x: &U::Matrix, y: &U::Matrix, names: &[String], opts: &Options, nAlphas: usize) {
+    let mut l = newLinear(opts);
+    let path = match l.regularizationPath(x, y, None, nAlphas) {
+        Ok(p) => p,
+        Err(e) => { println!("dataset error: {}", e); return; }
+    };
+
+    // 正則化の指定がなければLasso
+    let l1Ratio = match opts.penalty {
+        linear::Penalty::None => 1.0,
+        p => p.l1Ratio()
+    };
+    println!("*** Regularization path (l1_ratio={}) ***", l1Ratio);
+    print!("{:>12}{:>12}", "alpha", "intercept");
+    for name in names {
+        print!(" {:>12.12}", name);
+    }
+    println!();
+    for (alpha, beta) in &path {
+        print!("{:>12.6}", alpha);
+        for b in beta {
+            print!(" {:>11.5}", b);
+        }
+        println!();
+    }
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len()<3 {
-        println!("usage {} [z|l|d] csvFile [-d max_depth]", args[0]);
+        println!("usage {} [z|l|d|b|f|e|g] csvFile [-d max_depth] [-c [--curve out.csv]]", args[0]);
         println!("z ... ZeroRule model");
         println!("l ... Linear model");
         println!("d ... DecisionTree model");
+        println!("b ... Bagging (DecisionTrees on bootstrap samples)");
+        println!("f ... RandomForest (Bagging + random columns per node)");
+        println!("e ... ExtraTrees (ensemble of randomized DecisionTrees)");
+        println!("g ... Logistic (softmax) regression model");
+        println!("-c ... classification (目的変数の値をクラスとして評価)");
+        println!("--curve ... ROC/PR曲線の点をCSVに出力 (-cと共に指定)");
+        println!("--balanced ... クラスの行数の逆数に比例する重みを付けて学習 (-cと共に指定)");
+        println!("--search [grid|random] ... ハイパーパラメータ探索 (--iter N, --cv K, --seed S, --scoring [mse|r2|accuracy])");
+        println!("--epochs N, --lr V, --schedule [const|time:d|step:n:f|exp:g], --batch N, --shuffle, --tol T ... 線形モデルの学習設定");
+        println!("--solver [sgd|cholesky|qr|cd] ... 線形モデル（決定木の葉も含む）の回帰係数の求め方");
+        println!("--penalty [none|ridge:a|lasso:a|enet:a:r] ... 線形モデルの正則化, --path N ... 正則化パスを表示");
+        println!("--leaf [linear|logistic] ... 決定木の葉のモデル（logisticなら分類木）");
+        println!("--loss [squared|huber:d|absolute|quantile:t|epsilon:e] ... 線形モデルの損失関数（squared以外はSGDで学習）");
+        println!("--scaling [minmax|zscore|robust] ... 線形モデル、ロジスティック回帰の正規化の方法");
+        println!("--outputs N ... 末尾N列を目的変数にする（多出力回帰．z, l, dのみ）");
+        println!("--target a,..., --features a,b,..., --drop a,... ... 目的変数、説明変数、説明変数から除く列（列名か0からの列番号）");
+        println!("--delim C, --no-header, --comment C, --columns a,b,... ... CSVの区切り文字(省略時は推定)、列名なし、コメント行、読み込む列(列名か0からの列番号)");
+        println!("--na a,b,... ... 欠損値とみなすセルの内容（デフォルトは 空, NA, N/A, NaN, null, ?）");
+        println!("--categorical a,b,..., --text a,b,... ... カテゴリ列、テキスト列として読む列（指定のない列は数値として読めなければカテゴリ列）");
+        println!("--encoding [onehot|ordinal|native|target[:m]] ... カテゴリ列の数値化の方法（nativeは決定木が水準の集合で分割．mはtargetの平滑化の行数．テキスト列は使わない）");
+        println!("--threads N ... 並列に学習するスレッド数（デフォルトはCPU数．結果はスレッド数によらない）");
+        println!("--max-features K ... 決定木がノードごとに無作為に選んだK列の中で分割を探す（列の選び方は--seedで決まる）");
+        println!("--trees N ... アンサンブル(b, f, e)の木の数（デフォルト=10．木は--threadsで並列に学習する）");
+        println!("--bootstrap ... ExtraTrees(e)の各木もブートストラップ標本で学習する（OOBで評価できる）");
+        println!("--importance R ... 並べ替えによる特徴量の重要度（列ごとにR回並べ替える．--seed, --scoring）");
+        println!("--stream N, --partial N ... 線形モデルをファイルからN行ずつ読みながら学習（stream: エポックごとに読み直す, partial: 1パスでpartialFit）");
+        println!("--impute [mean|median|mostfrequent|constant:v] ... 説明変数の欠損値(空, NA, ?など)の補完方法");
+        println!("--poly D, --interactions ... 説明変数にD次までのべき乗、次数の合計がD（1なら2）以下の2列以上の積を加える");
         println!("(ex)");
         println!("> cargo run d winequality-red-mid.csv");
         return;
@@ -94,37 +974,160 @@ fn main() {
     let modelType = &args[1];
     let filePath = &args[2];
 
-    let mut max_depth: u32 = 3; // default depth
-    if args.len()>4 {
-        if &args[3] == "-d" {
-            max_depth = args[4].parse::<u32>().unwrap();
+    let mut opts = match parseOptions(&args[3..]) {
+        Ok(o) => o,
+        Err(e) => { println!("{}", e); return; }
+    };
+
+    let mut csv = U::CSV::new().header(opts.header);
+    if let Some(d) = opts.delimiter {
+        csv = csv.delimiter(d);
+    }
+    if let Some(c) = opts.comment {
+        csv = csv.comment(c);
+    }
+    if let Some(ref cols) = opts.columns {
+        csv = csv.columns(cols);
+    }
+    if let Some(ref na) = opts.naValues {
+        let tokens: Vec<&str> = na.split(',').collect();
+        csv = csv.naValues(&tokens);
+    }
+    for c in &opts.categorical {
+        csv = csv.columnType(c.clone(), U::ColKind::Categorical);
+    }
+    for c in &opts.text {
+        csv = csv.columnType(c.clone(), U::ColKind::Text);
+    }
+
+    // 線形モデルをファイルから少しずつ読みながら学習する
+    if opts.stream.is_some() || opts.partial.is_some() {
+        if modelType != "l" {
+            println!("--stream and --partial are only for the linear model (l)");
+            return;
         }
+        streamTest(&csv, filePath, &opts);
+        return;
     }
 
-    let mut csv = U::CSV::new();
-    
     let result = csv.read(filePath); // 読み込み
-    if result.is_err() {
-        println!("load error {}", filePath);
+    if let Err(e) = result {
+        println!("load error {}: {}", filePath, e);
         return;
     }
 
+    // 目的変数の列（指定がなければ末尾のnOutputs列）
     let ncol = csv.cols.len();
+    let target: Vec<U::Col> = match opts.target {
+        Some(ref t) => t.clone(),
+        None => {
+            let ny = opts.nOutputs;  // 目的変数の列数
+            if ny == 0 || ny >= ncol {
+                println!("invalid number of outputs {} (columns={})", ny, ncol);
+                return;
+            }
+            (ncol-ny..ncol).map(U::Col::Index).collect()
+        }
+    };
+
+    // 説明変数、目的変数の列を選ぶ
+    let (xcsv, ycsv) = match csv.splitXY(&target, opts.features.as_deref(), &opts.drop) {
+        Ok(xy) => xy,
+        Err(e) => {
+            println!("{} (columns: {})", e, csv.colNames.join(", "));
+            return;
+        }
+    };
+    if xcsv.cols.is_empty() || ycsv.cols.is_empty() {
+        println!("no explanatory or target columns (explanatory={}, target={})", xcsv.cols.len(), ycsv.cols.len());
+        return;
+    }
 
     // 説明変数を取り出す
-    let x = csv.clonePartial(0, ncol-1).cols;
-    // println!("**** x ****");
-    // U::printMat(&x);
+    let mut x = xcsv.cols.clone();
+    let mut names = xcsv.colNames.clone();
 
-    // 目的変数を取り出す
-    let y = csv.clonePartial(ncol-1, ncol).cols;
-    // println!("**** y ****");
-    // U::printMat(&y);
+    // 目的変数を取り出す（カテゴリ列、テキスト列の値は水準の番号やNaNなので、目的変数には使えない）
+    if let Some(j) = ycsv.kinds.iter().position(|k| *k != U::ColKind::Numeric) {
+        println!("target column {} is {:?}, but the target must be numeric", ycsv.colNames[j], ycsv.kinds[j]);
+        return;
+    }
+    let mut y = ycsv.cols.clone();
+
+    // カテゴリ列を数値にし、テキスト列を除く（Targetは1列目の目的変数を使う）
+    if xcsv.kinds.iter().any(|k| *k != U::ColKind::Numeric) {
+        // Targetは全ての行の目的変数で水準の値を決めるので、交差検証の検証用の行の目的変数が
+        // 学習用の説明変数に漏れる．--searchとは一緒に使えない
+        if matches!(opts.encoding, features::Encoding::Target(_)) && opts.searchMethod.is_some() {
+            println!("--encoding target cannot be used with --search (the encoding is fitted on all rows, so the target leaks into the validation folds); use onehot or ordinal");
+            return;
+        }
+        let mut encoder = features::CategoricalEncoder::new(opts.encoding);
+        x = match encoder.fitTransform(&xcsv, &y[0]) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        };
+        names = encoder.names().to_vec();
+        opts.nativeColumns = encoder.nativeColumns();
+        println!("encoded columns ({:?}) = {:?}", opts.encoding, names);
+    }
+
+    // 目的変数が欠損値の行は学習に使えないので除く
+    let rows: Vec<usize> = (0..y[0].len()).filter(|irow| y.iter().all(|col| !col[*irow].is_nan())).collect();
+    if rows.len() < y[0].len() {
+        println!("dropped {} rows with missing target values", y[0].len() - rows.len());
+        x = U::MatSelectRow(&x, &rows);
+        y = U::MatSelectRow(&y, &rows);
+    }
+
+    // 説明変数の欠損値を補完する（補完しない場合、決定木は分割ごとに欠損値を入れる側を学習し、
+    // 線形モデル、ロジスティック回帰は欠損値を正規化後0として扱う）
+    if let Some(strategy) = opts.impute {
+        let mut imputer = features::Imputer::new(strategy);
+        x = imputer.fitTransform(&x);
+        println!("imputed values ({:?}) = {:?}", strategy, imputer.fillValues());
+    }
+
+    // 目的変数の列名
+    let yNames = ycsv.colNames.clone();
+
+    // 多項式、交互作用の列を加える
+    if opts.polyDegree > 1 || opts.interactions {
+        // 元の列は先頭に同じ順で残るので、カテゴリ列の列番号(opts.nativeColumns)は変わらない
+        let mut poly = features::Polynomial::new(opts.polyDegree.max(1)).interactions(opts.interactions).keepColumns(&opts.nativeColumns);
+        x = poly.fitTransform(&x, &names);
+        names = poly.names().to_vec();
+    }
+
+    if let Some(nAlphas) = opts.pathAlphas {
+        pathTest(&x, &y, &names, &opts, nAlphas);
+        return;
+    }
+
+    if opts.searchMethod.is_some() {
+        searchTest(modelType, &x, &y, &opts);
+        return;
+    }
+
+    if let Some(repeats) = opts.importance {
+        importanceTest(modelType, &x, &y, &names, &opts, repeats);
+        return;
+    }
+
+    if opts.classify {
+        classificationTest(modelType, &x, &y, &opts);
+        return;
+    }
 
     match modelType as &str {
         "z" => { zeroRuleTest(&x, &y); },
-        "l" => { linearTest(&x, &y); },
-        "d" => { decisionTreeTest(&x, &y, max_depth); }
+        "l" => { linearTest(&x, &y, &names, &yNames, &opts); },
+        "d" => { decisionTreeTest(&x, &y, &opts); },
+        "b" | "f" | "e" => { forestTest(modelType, &x, &y, &opts); },
+        "g" => { logisticTest(&x, &y, &opts); }
         _ => { println!("unknown model"); }
     }
 }
@@ -133,4 +1136,34 @@ fn main() {
 > cargo run l winequality-red-mid.csv   # Linear Model
 > cargo run d winequality-red-mid.csv   # Decision Tree (max_depth=default(3))
 > cargo run d winequality-red-mid.csv -d 4  # Decision Tree (max_depth=4)
+> cargo run z winequality-red-mid.csv -c    # ZeroRule as classifier (confusion matrix etc.)
+> cargo run l winequality-red-mid.csv -c --curve roc.csv    # + ROC/PR curve points
+> cargo run d winequality-red-mid.csv -c --balanced   # class-balanced sample weights
+> cargo run d winequality-red-mid.csv --search grid --cv 5   # grid search over max_depth
+> cargo run l winequality-red-mid.csv --search random --iter 8 --seed 1    # random search over epochs/lr
+> cargo run l winequality-red-mid.csv --epochs 200 --schedule exp:0.98 --batch 16 --shuffle --tol 1e-6
+> cargo run d winequality-red-mid.csv --solver qr   # leaves solved exactly by QR
+> cargo run l winequality-red-mid.csv --solver cd --penalty enet:0.01:0.5   # elastic net
+> cargo run l winequality-red-mid.csv --penalty lasso:0 --path 10   # lasso regularization path
+> cargo run l winequality-red-mid.csv --loss quantile:0.9 --epochs 100   # P90 prediction
+> cargo run l winequality-red-mid.csv --scaling robust  # median/IQR scaling
+> cargo run l winequality-red-mid.csv --solver qr --poly 2 --interactions  # quadratic + pairwise terms
+> cargo run d winequality-red-mid.csv --outputs 2  # predict alcohol and quality at once
+> cargo run l winequality-red-mid.csv --target alcohol --drop density  # alcohol from the other columns except density
+> cargo run l data.csv --delim , --comment '#' --columns alcohol,pH,quality   # comma separated, selected columns
+> cargo run l data-with-na.csv --impute median    # fill missing cells with column medians
+> cargo run d wine-with-color.csv --encoding native   # tree splits color by subsets of levels
+> cargo run d wine-with-color.csv --categorical color --encoding target:5   # color encoded as mean quality per level
+> cargo run d winequality-red-mid.csv -d 5 --threads 4   # split search on 4 threads (same tree as 1 thread)
+> cargo run d winequality-red-mid.csv -d 5 --max-features 4 --seed 7   # random 4 columns per node, reproducible
+> cargo run e winequality-red-mid.csv -d 5 --trees 50 --solver qr   # ExtraTrees, 50 trees in parallel
+> cargo run f winequality-red-mid.csv --trees 50 --solver qr   # random forest with OOB R2/MSE
+> cargo run b winequality-red-mid.csv -c --trees 20    # bagging, OOB accuracy
+> cargo run f winequality-red-mid.csv --importance 3 --solver qr   # OOB permutation importance
+> cargo run l winequality-red-mid.csv --importance 5 --seed 1   # permutation importance, 5 shuffles per column
+> cargo run l winequality-red-mid.csv --stream 100 --epochs 50   # out-of-core SGD, 100 rows at a time
+> cargo run l winequality-red-mid.csv --partial 20   # incremental updates as 20-row batches arrive
+> cargo run g winequality-red-mid.csv -c    # softmax regression (quality as class)
+> cargo run g winequality-red-mid.csv --search grid --scoring accuracy
+> cargo run d winequality-red-mid.csv -c --leaf logistic   # classification tree with logistic leaves
 */
\ No newline at end of file
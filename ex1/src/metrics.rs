@@ -0,0 +1,665 @@
+// ******************************************************************
+//  評価指標
+//  回帰: 平均二乗誤差、決定係数
+//  分類: 混同行列、ROC曲線・PR曲線
+// ******************************************************************
+
+#![allow(non_snake_case)]
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use super::U;   // main.rsのコメントを参照
+
+// log lossの計算で確率をクリップする際の下限
+const PROBA_EPS: f64 = 1e-15;
+
+// =================================================
+//  回帰の評価指標
+// =================================================
+
+// -----------------------------------------------
+// 平均二乗誤差(MSE)を求める
+//
+// @param yTrue 正解値
+// @param yPred 予測値
+// -----------------------------------------------
+pub fn mse(yTrue: &[f64], yPred: &[f64]) -> f64 {
+    let sum: f64 = yTrue.iter().zip(yPred.iter()).map(|(t, p)| (t - p).powf(2.0)).sum();
+    sum / (yTrue.len() as f64)
+}
+
+// -----------------------------------------------
+// 決定係数(R^2)を求める
+// 正解値が全て同じ値のときは、予測が完全に一致すれば1、そうでなければ0とする
+//
+// @param yTrue 正解値
+// @param yPred 予測値
+// -----------------------------------------------
+pub fn r2(yTrue: &[f64], yPred: &[f64]) -> f64 {
+    let m = yTrue.iter().sum::<f64>() / (yTrue.len() as f64);
+    let ssRes: f64 = yTrue.iter().zip(yPred.iter()).map(|(t, p)| (t - p).powf(2.0)).sum();
+    let ssTot: f64 = yTrue.iter().map(|t| (t - m).powf(2.0)).sum();
+    if ssTot == 0.0 {
+        return if ssRes == 0.0 { 1.0 } else { 0.0 };
+    }
+    1.0 - ssRes / ssTot
+}
+
+// -----------------------------------------------
+// 平均二乗誤差の符号を反転したもの
+// （ハイパーパラメータ探索で「大きいほど良い」スコアとして使う）
+// -----------------------------------------------
+pub fn negMse(yTrue: &[f64], yPred: &[f64]) -> f64 {
+    -mse(yTrue, yPred)
+}
+
+// =================================================
+//  クラスラベル関連のヘルパー
+// =================================================
+
+// -----------------------------------------------
+// クラスラベル（値の種類）を昇順で返す
+//
+// @param y 目的変数（クラスラベルの列）
+//
+// @return yに現れる値を重複なしで昇順に並べたもの（欠損値(NaN)はクラスに含めない）
+// -----------------------------------------------
+pub fn classLabels(y: &[f64]) -> Vec<f64> {
+    let mut labels: Vec<f64> = y.iter().cloned().filter(|v| !v.is_nan()).collect();
+    labels.sort_by(|a, b| a.total_cmp(b));
+    labels.dedup();
+    labels
+}
+
+// -----------------------------------------------
+// ラベルの位置（インデックス）を返す
+// -----------------------------------------------
+fn labelIndex(labels: &[f64], v: f64) -> Option<usize> {
+    labels.iter().position(|&l| l == v)
+}
+
+// -----------------------------------------------
+// クラスの偏りを補正する行ごとの重みを求める
+// 重み = 行数 / (クラス数 * そのクラスの行数)．どのクラスも重みの合計が同じになる
+//
+// @param y 目的変数（クラスラベルの列）
+// @param labels クラスラベル（classLabels()の結果）
+// -----------------------------------------------
+pub fn balancedWeights(y: &[f64], labels: &[f64]) -> Vec<f64> {
+    let mut counts = vec![0usize; labels.len()];
+    for v in y {
+        if let Some(k) = labelIndex(labels, *v) {
+            counts[k] += 1;
+        }
+    }
+    let scale = (y.len() as f64) / (labels.len() as f64);
+    y.iter().map(|v| labelIndex(labels, *v).map_or(0.0, |k| scale / (counts[k] as f64))).collect()
+}
+
+// -----------------------------------------------
+// クラスラベルの列をone-hot表現の行列に変換する
+//
+// @param y 目的変数（クラスラベルの列）
+// @param labels クラスラベル（classLabels()の結果）
+//
+// @return labels.len()列の行列．yの値に対応する列だけ1.0, それ以外は0.0
+// -----------------------------------------------
+pub fn oneHot(y: &[f64], labels: &[f64]) -> U::Matrix {
+    let mut result: U::Matrix = vec![vec![0.0; y.len()]; labels.len()];
+    for (irow, v) in y.iter().enumerate() {
+        if let Some(k) = labelIndex(labels, *v) {
+            result[k][irow] = 1.0;
+        }
+    }
+    result
+}
+
+// -----------------------------------------------
+// 予測値を確率として扱えるように補正する
+// 各値を[0, 1]にクリップし、行ごとの合計が1になるように割る
+// （線形モデルなどでクラスごとに回帰した結果を確率とみなすために使う）
+//
+// @param proba クラスごとの予測値（Matrix[クラス][行]）
+// -----------------------------------------------
+pub fn normalizeProba(proba: &mut U::Matrix) {
+    if proba.is_empty() {
+        return;
+    }
+    let nclass = proba.len();
+    let nrow = proba[0].len();
+    for irow in 0..nrow {
+        let mut sum = 0.0;
+        for col in proba.iter_mut() {
+            col[irow] = col[irow].clamp(0.0, 1.0);
+            sum += col[irow];
+        }
+        for col in proba.iter_mut() {
+            if sum > 0.0 {
+                col[irow] /= sum;
+            } else {
+                col[irow] = 1.0 / (nclass as f64);  // 全て0なら一様分布とみなす
+            }
+        }
+    }
+}
+
+// -----------------------------------------------
+// クラスごとの確率から、最も確率の高いクラスのラベルを選ぶ
+//
+// @param proba クラスごとの確率（Matrix[クラス][行]）
+// @param labels クラスラベル
+//
+// @return 行ごとの予測ラベル
+// -----------------------------------------------
+pub fn probaToLabels(proba: &U::Matrix, labels: &[f64]) -> Vec<f64> {
+    let nrow = proba[0].len();
+    let mut result = Vec::<f64>::new();
+    for irow in 0..nrow {
+        let mut best = 0;
+        for k in 1..proba.len() {
+            if proba[k][irow] > proba[best][irow] {
+                best = k;
+            }
+        }
+        result.push(labels[best]);
+    }
+    result
+}
+
+// =================================================
+//  評価指標
+// =================================================
+
+// -----------------------------------------------
+// 正解率（予測ラベルが正解ラベルと一致した割合）
+// ハイパーパラメータ探索のスコアとしても使う
+// -----------------------------------------------
+pub fn accuracy(yTrue: &[f64], yPred: &[f64]) -> f64 {
+    let hit = yTrue.iter().zip(yPred.iter()).filter(|(t, p)| t == p).count();
+    (hit as f64) / (yTrue.len() as f64)
+}
+
+// -----------------------------------------------
+// log lossを求める
+//
+// @param yTrue 正解ラベル
+// @param proba クラスごとの予測確率（Matrix[クラス][行]）
+// @param labels probaの列に対応するクラスラベル
+//
+// @return 正解クラスの予測確率の対数の平均(の符号を反転したもの)
+// -----------------------------------------------
+pub fn logLoss(yTrue: &[f64], proba: &U::Matrix, labels: &[f64]) -> f64 {
+    let mut total = 0.0;
+    for (irow, v) in yTrue.iter().enumerate() {
+        let p = match labelIndex(labels, *v) {
+            Some(k) => proba[k][irow],
+            None => 0.0     // 未知のクラスは確率0とみなす
+        };
+        total -= p.clamp(PROBA_EPS, 1.0 - PROBA_EPS).ln();
+    }
+    total / (yTrue.len() as f64)
+}
+
+// 適合率・再現率・F1値の平均の取り方
+#[derive(Clone, Copy)]
+pub enum Average {
+    Macro,      // クラスごとの値の単純平均
+    Micro,      // 全クラスのTP, FP, FNを合計してから計算
+    Weighted    // クラスごとの値をサポート数(正解の個数)で重み付け平均
+}
+
+// =================================================
+//  混同行列
+// =================================================
+pub struct ConfusionMatrix {
+    pub labels: Vec<f64>,           // クラスラベル（昇順）
+    pub counts: Vec<Vec<usize>>     // counts[正解][予測] = 件数
+}
+
+impl ConfusionMatrix {
+    // ===============================================================
+    //  正解ラベルと予測ラベルから混同行列を作る
+    //
+    // @param yTrue 正解ラベル
+    // @param yPred 予測ラベル
+    // どちらかが欠損値(NaN)の行は数えない
+    // ===============================================================
+    pub fn new(yTrue: &[f64], yPred: &[f64]) -> Self {
+        let mut all: Vec<f64> = yTrue.to_vec();
+        all.extend_from_slice(yPred);
+        let labels = classLabels(&all);
+
+        let n = labels.len();
+        let mut counts = vec![vec![0; n]; n];
+        for (t, p) in yTrue.iter().zip(yPred.iter()) {
+            // NaNはclassLabelsでクラスに含めないので見つからない
+            if let (Some(i), Some(j)) = (labelIndex(&labels, *t), labelIndex(&labels, *p)) {
+                counts[i][j] += 1;
+            }
+        }
+
+        ConfusionMatrix {
+            labels,
+            counts
+        }
+    }
+
+    // 全件数
+    pub fn total(&self) -> usize {
+        self.counts.iter().map(|row| row.iter().sum::<usize>()).sum()
+    }
+
+    // k番目のクラスのサポート数（正解がそのクラスである件数）
+    pub fn support(&self, k: usize) -> usize {
+        self.counts[k].iter().sum()
+    }
+
+    // k番目のクラスを予測した件数
+    fn predicted(&self, k: usize) -> usize {
+        self.counts.iter().map(|row| row[k]).sum()
+    }
+
+    // 正解率．件数が0なら0
+    pub fn accuracy(&self) -> f64 {
+        let hit: usize = (0..self.labels.len()).map(|k| self.counts[k][k]).sum();
+        ratio(hit, self.total())
+    }
+
+    // -----------------------------------------------
+    // balanced accuracy（正解に現れるクラスの再現率の平均）
+    // 正解が1件もなければ0
+    // -----------------------------------------------
+    pub fn balancedAccuracy(&self) -> f64 {
+        let present: Vec<usize> = (0..self.labels.len()).filter(|k| self.support(*k) > 0).collect();
+        if present.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = present.iter().map(|k| self.recall(*k)).sum();
+        sum / (present.len() as f64)
+    }
+
+    // k番目のクラスの適合率（precision）．予測件数が0なら0
+    pub fn precision(&self, k: usize) -> f64 {
+        ratio(self.counts[k][k], self.predicted(k))
+    }
+
+    // k番目のクラスの再現率（recall）．サポート数が0なら0
+    pub fn recall(&self, k: usize) -> f64 {
+        ratio(self.counts[k][k], self.support(k))
+    }
+
+    // k番目のクラスのF1値
+    pub fn f1(&self, k: usize) -> f64 {
+        f1(self.precision(k), self.recall(k))
+    }
+
+    // -----------------------------------------------
+    // 適合率・再現率・F1値の平均
+    //
+    // @param avg 平均の取り方
+    //
+    // @return (適合率, 再現率, F1値)
+    // -----------------------------------------------
+    pub fn average(&self, avg: Average) -> (f64, f64, f64) {
+        let n = self.labels.len();
+        match avg {
+            Average::Macro => {
+                if n == 0 {
+                    return (0.0, 0.0, 0.0);    // クラスがなければ0
+                }
+                let p = (0..n).map(|k| self.precision(k)).sum::<f64>() / (n as f64);
+                let r = (0..n).map(|k| self.recall(k)).sum::<f64>() / (n as f64);
+                let f = (0..n).map(|k| self.f1(k)).sum::<f64>() / (n as f64);
+                (p, r, f)
+            },
+            Average::Micro => {
+                // 単一ラベルの多クラス分類では、TPの合計 / 全件数 が適合率・再現率の両方になる
+                let tp: usize = (0..n).map(|k| self.counts[k][k]).sum();
+                let p = ratio(tp, self.total());
+                let r = ratio(tp, self.total());
+                (p, r, f1(p, r))
+            },
+            Average::Weighted => {
+                // 件数が0なら重みは全て0（平均は0）
+                let w = |k: usize| ratio(self.support(k), self.total());
+                let p = (0..n).map(|k| self.precision(k) * w(k)).sum::<f64>();
+                let r = (0..n).map(|k| self.recall(k) * w(k)).sum::<f64>();
+                let f = (0..n).map(|k| self.f1(k) * w(k)).sum::<f64>();
+                (p, r, f)
+            }
+        }
+    }
+
+    // -----------------------------------------------
+    // 混同行列を表示（行=正解、列=予測）
+    // -----------------------------------------------
+    pub fn print(&self) {
+        print!("{:>10}", "true\\pred");
+        for l in &self.labels {
+            print!("{:>8}", l);
+        }
+        println!();
+
+        for (i, l) in self.labels.iter().enumerate() {
+            print!("{:>10}", l);
+            for c in &self.counts[i] {
+                print!("{:>8}", c);
+            }
+            println!();
+        }
+    }
+
+    // -----------------------------------------------
+    // クラスごとの適合率・再現率・F1値と、各種平均を表示
+    // -----------------------------------------------
+    pub fn printReport(&self) {
+        println!("{:>10}{:>11}{:>11}{:>11}{:>9}", "class", "precision", "recall", "f1", "support");
+        for (k, l) in self.labels.iter().enumerate() {
+            println!("{:>10}{:>11.4}{:>11.4}{:>11.4}{:>9}", l, self.precision(k), self.recall(k), self.f1(k), self.support(k));
+        }
+        println!();
+
+        let avgs = [("macro", Average::Macro), ("micro", Average::Micro), ("weighted", Average::Weighted)];
+        for (name, avg) in avgs.iter() {
+            let (p, r, f) = self.average(*avg);
+            println!("{:>10}{:>11.4}{:>11.4}{:>11.4}{:>9}", name, p, r, f, self.total());
+        }
+        println!();
+
+        println!("accuracy = {:.4}", self.accuracy());
+        println!("balanced accuracy = {:.4}", self.balancedAccuracy());
+    }
+}
+
+// 割り算（分母が0なら0を返す）
+fn ratio(num: usize, den: usize) -> f64 {
+    if den == 0 {
+        0.0
+    } else {
+        (num as f64) / (den as f64)
+    }
+}
+
+// 適合率と再現率からF1値を求める
+fn f1(p: f64, r: f64) -> f64 {
+    if p + r == 0.0 {
+        0.0
+    } else {
+        2.0 * p * r / (p + r)
+    }
+}
+
+// =================================================
+//  ROC曲線、PR曲線
+// =================================================
+pub struct Curve {
+    pub x: Vec<f64>,            // ROC: 偽陽性率(FPR), PR: 再現率(recall)
+    pub y: Vec<f64>,            // ROC: 真陽性率(TPR), PR: 適合率(precision)
+    pub thresholds: Vec<f64>    // 各点のしきい値（スコアがこの値以上なら陽性と判定）
+}
+
+impl Curve {
+    // -----------------------------------------------
+    // 台形近似で曲線の下側の面積(AUC)を求める
+    // -----------------------------------------------
+    pub fn auc(&self) -> f64 {
+        let mut area = 0.0;
+        for i in 1..self.x.len() {
+            area += (self.x[i] - self.x[i-1]) * (self.y[i] + self.y[i-1]) / 2.0;
+        }
+        area
+    }
+}
+
+// 同じしきい値とみなすスコアか（NaN同士も同じとする）
+fn sameScore(a: f64, b: f64) -> bool {
+    a == b || (a.is_nan() && b.is_nan())
+}
+
+// -----------------------------------------------
+// スコアの大きい順に、しきい値ごとのTP, FPの累積数を求める
+//
+// @param yTrue 陽性ならtrue
+// @param scores 陽性らしさのスコア（クラスの確率など）
+//
+// @return (しきい値, TP累積数, FP累積数) のリスト
+// スコアが欠損値(NaN)の行は最も陽性らしくないものとして最後に数える
+// -----------------------------------------------
+fn cumulativeCounts(yTrue: &[bool], scores: &[f64]) -> Vec<(f64, usize, usize)> {
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|a, b| match (scores[*a].is_nan(), scores[*b].is_nan()) {
+        (false, false) => scores[*b].total_cmp(&scores[*a]),
+        (nanA, nanB) => nanA.cmp(&nanB)
+    });
+
+    let mut result = Vec::<(f64, usize, usize)>::new();
+    let mut tp = 0;
+    let mut fp = 0;
+    for (i, irow) in order.iter().enumerate() {
+        if yTrue[*irow] {
+            tp += 1;
+        } else {
+            fp += 1;
+        }
+        // 同じスコアが続く間は点を作らない
+        let last = i + 1 == order.len();
+        if last || !sameScore(scores[order[i+1]], scores[*irow]) {
+            result.push((scores[*irow], tp, fp));
+        }
+    }
+    result
+}
+
+// -----------------------------------------------
+// ROC曲線を求める
+//
+// @param yTrue 陽性ならtrue
+// @param scores 陽性らしさのスコア
+//
+// @return ROC曲線．(0, 0)から始まり(1, 1)で終わる
+// 陽性または陰性が1件もなければ率が計算できないのでNaNになる
+// -----------------------------------------------
+pub fn rocCurve(yTrue: &[bool], scores: &[f64]) -> Curve {
+    let npos = yTrue.iter().filter(|t| **t).count() as f64;
+    let nneg = (yTrue.len() as f64) - npos;
+
+    let mut curve = Curve {
+        x: vec![0.0],
+        y: vec![0.0],
+        thresholds: vec![f64::INFINITY]
+    };
+    for (thr, tp, fp) in cumulativeCounts(yTrue, scores) {
+        curve.x.push((fp as f64) / nneg);
+        curve.y.push((tp as f64) / npos);
+        curve.thresholds.push(thr);
+    }
+    curve
+}
+
+// -----------------------------------------------
+// PR曲線（precision-recall曲線）を求める
+//
+// @param yTrue 陽性ならtrue
+// @param scores 陽性らしさのスコア
+//
+// @return PR曲線．再現率0、適合率1の点から始まる
+// -----------------------------------------------
+pub fn prCurve(yTrue: &[bool], scores: &[f64]) -> Curve {
+    let npos = yTrue.iter().filter(|t| **t).count() as f64;
+
+    let mut curve = Curve {
+        x: vec![0.0],
+        y: vec![1.0],
+        thresholds: vec![f64::INFINITY]
+    };
+    for (thr, tp, fp) in cumulativeCounts(yTrue, scores) {
+        curve.x.push((tp as f64) / npos);
+        curve.y.push((tp as f64) / ((tp + fp) as f64));
+        curve.thresholds.push(thr);
+    }
+    curve
+}
+
+// -----------------------------------------------
+// 平均適合率（average precision）を求める
+// 再現率の増分で適合率を重み付けした和（台形近似はしない）
+//
+// @param pr PR曲線（prCurve()の結果）
+// -----------------------------------------------
+pub fn averagePrecision(pr: &Curve) -> f64 {
+    let mut ap = 0.0;
+    for i in 1..pr.x.len() {
+        ap += (pr.x[i] - pr.x[i-1]) * pr.y[i];
+    }
+    ap
+}
+
+// -----------------------------------------------
+// 1クラス分のROC曲線、PR曲線（one-vs-rest）
+// -----------------------------------------------
+pub struct ClassCurves {
+    pub label: f64,     // 陽性とみなすクラス
+    pub roc: Curve,
+    pub pr: Curve
+}
+
+// -----------------------------------------------
+// クラスごとに、そのクラスを陽性・それ以外を陰性として
+// ROC曲線、PR曲線を求める(one-vs-rest)
+// 2クラスの場合は、大きい方のラベルを陽性とした曲線だけを返す
+//
+// @param yTrue 正解ラベル
+// @param proba クラスごとの予測確率（Matrix[クラス][行]）
+// @param labels probaの列に対応するクラスラベル
+// -----------------------------------------------
+pub fn oneVsRestCurves(yTrue: &[f64], proba: &U::Matrix, labels: &[f64]) -> Vec<ClassCurves> {
+    let first = if labels.len() == 2 { 1 } else { 0 };
+
+    let mut result = Vec::<ClassCurves>::new();
+    for k in first..labels.len() {
+        let positive: Vec<bool> = yTrue.iter().map(|v| *v == labels[k]).collect();
+        result.push(ClassCurves {
+            label: labels[k],
+            roc: rocCurve(&positive, &proba[k]),
+            pr: prCurve(&positive, &proba[k])
+        });
+    }
+    result
+}
+
+// -----------------------------------------------
+// クラスごとのAUC、平均適合率を表示する
+// -----------------------------------------------
+pub fn printCurveSummary(curves: &[ClassCurves]) {
+    println!("{:>10}{:>11}{:>11}", "class", "ROC AUC", "AP");
+    for c in curves {
+        println!("{:>10}{:>11.4}{:>11.4}", c.label, c.roc.auc(), averagePrecision(&c.pr));
+    }
+    let n = curves.len() as f64;
+    let rocAuc = curves.iter().map(|c| c.roc.auc()).sum::<f64>() / n;
+    let ap = curves.iter().map(|c| averagePrecision(&c.pr)).sum::<f64>() / n;
+    println!("{:>10}{:>11.4}{:>11.4}", "macro", rocAuc, ap);
+}
+
+// -----------------------------------------------
+// 曲線の点をCSVファイルに書き出す（外部ツールでのプロット用）
+// 列: class,curve,x,y,threshold
+// curveは"roc"（x=FPR, y=TPR）または"pr"（x=recall, y=precision）
+//
+// @param fpath 出力ファイルパス
+// @param curves oneVsRestCurves()の結果
+// -----------------------------------------------
+pub fn writeCurvesCsv(fpath: &str, curves: &[ClassCurves]) -> std::io::Result<()> {
+    let mut w = BufWriter::new(File::create(fpath)?);
+    writeln!(w, "class,curve,x,y,threshold")?;
+    for c in curves {
+        for (name, curve) in [("roc", &c.roc), ("pr", &c.pr)].iter() {
+            for i in 0..curve.x.len() {
+                writeln!(w, "{},{},{},{},{}", c.label, name, curve.x[i], curve.y[i], curve.thresholds[i])?;
+            }
+        }
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn near(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn confusionMatrixCounts() {
+        let yTrue = [0.0, 0.0, 0.0, 1.0, 1.0, 2.0];
+        let yPred = [0.0, 0.0, 1.0, 1.0, 0.0, 2.0];
+        let cm = ConfusionMatrix::new(&yTrue, &yPred);
+        assert_eq!(cm.labels, vec![0.0, 1.0, 2.0]);
+        assert_eq!(cm.counts, vec![vec![2, 1, 0], vec![1, 1, 0], vec![0, 0, 1]]);
+        assert_eq!(cm.total(), 6);
+        assert!(near(cm.accuracy(), 4.0 / 6.0));
+        assert!(near(cm.precision(0), 2.0 / 3.0));
+        assert!(near(cm.recall(1), 0.5));
+        assert!(near(cm.balancedAccuracy(), (2.0 / 3.0 + 0.5 + 1.0) / 3.0));
+    }
+
+    #[test]
+    fn balancedAccuracyIgnoresAbsentClasses() {
+        // クラス2は予測にしか現れないので平均に含めない
+        let cm = ConfusionMatrix::new(&[0.0, 1.0], &[0.0, 2.0]);
+        assert!(near(cm.balancedAccuracy(), 0.5));
+        // 正解が1件もなければ0
+        let empty = ConfusionMatrix::new(&[], &[]);
+        assert_eq!(empty.balancedAccuracy(), 0.0);
+    }
+
+    #[test]
+    fn classLabelsSkipNaN() {
+        assert_eq!(classLabels(&[2.0, f64::NAN, 1.0, 2.0]), vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn confusionMatrixSkipsNaN() {
+        let cm = ConfusionMatrix::new(&[0.0, f64::NAN, 1.0, 1.0], &[0.0, 1.0, f64::NAN, 1.0]);
+        assert_eq!(cm.labels, vec![0.0, 1.0]);
+        assert_eq!(cm.counts, vec![vec![1, 0], vec![0, 1]]);
+        assert_eq!(cm.total(), 2);
+    }
+
+    #[test]
+    fn averagesOfEmptyMatrixAreZero() {
+        let empty = ConfusionMatrix::new(&[], &[]);
+        assert_eq!(empty.accuracy(), 0.0);
+        for avg in [Average::Macro, Average::Micro, Average::Weighted] {
+            assert_eq!(empty.average(avg), (0.0, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn rocAucOfSeparatedScores() {
+        let yTrue = [true, true, false, false];
+        assert!(near(rocCurve(&yTrue, &[0.9, 0.8, 0.3, 0.1]).auc(), 1.0));
+        assert!(near(rocCurve(&yTrue, &[0.1, 0.3, 0.8, 0.9]).auc(), 0.0));
+        // 全て同じスコアなら対角線になる
+        let tie = rocCurve(&yTrue, &[0.5; 4]);
+        assert_eq!(tie.x, vec![0.0, 1.0]);
+        assert!(near(tie.auc(), 0.5));
+    }
+
+    #[test]
+    fn rocTreatsNaNScoresAsLeastPositive() {
+        let curve = rocCurve(&[true, false, true], &[f64::NAN, 0.2, 0.7]);
+        assert_eq!(curve.x, vec![0.0, 0.0, 1.0, 1.0]);
+        assert_eq!(curve.y, vec![0.0, 0.5, 0.5, 1.0]);
+        assert!(curve.thresholds[3].is_nan());
+    }
+
+    #[test]
+    fn averagePrecisionOfPerfectRanking() {
+        let pr = prCurve(&[true, false, true, false], &[0.9, 0.2, 0.8, 0.1]);
+        assert!(near(averagePrecision(&pr), 1.0));
+        assert_eq!(*pr.x.last().unwrap(), 1.0);
+    }
+}
@@ -0,0 +1,67 @@
+// ******************************************************************
+//  並列処理
+//  独立な計算（決定木の列ごとの分割探索、ハイパーパラメータの候補ごとの交差検証など）を複数のスレッドで分担する
+//  結果は常に入力の順に並べるので、スレッド数によって結果が変わらない
+// ******************************************************************
+
+#![allow(non_snake_case)]
+
+// -----------------------------------------------
+// 使えるスレッド数（分からなければ1）
+// -----------------------------------------------
+pub fn availableThreads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// -----------------------------------------------
+// 0..nの各iについてf(i)を求める
+// iを連続した区間に分けてthreads個のスレッドで分担する（threadsが1以下なら呼び出し元のスレッドで順に）
+// スレッドの中でパニックした場合は、呼び出し元のスレッドで同じパニックを起こす
+//
+// @param n 個数
+// @param threads スレッド数
+// @param f 計算する関数（どのスレッドから呼ばれても同じ結果になること）
+//
+// @return [f(0), f(1), ..., f(n-1)]
+// -----------------------------------------------
+pub fn map<T, F>(n: usize, threads: usize, f: F) -> Vec<T>
+    where T: Send, F: Fn(usize) -> T + Sync
+{
+    let threads = threads.min(n);
+    if threads <= 1 {
+        return (0..n).map(f).collect();
+    }
+
+    let f = &f;
+    std::thread::scope(|s| {
+        let handles: Vec<_> = (0..threads).map(|t| {
+            // t番目のスレッドの区間
+            let start = t * n / threads;
+            let end = (t + 1) * n / threads;
+            s.spawn(move || (start..end).map(f).collect::<Vec<T>>())
+        }).collect();
+
+        let mut result = Vec::with_capacity(n);
+        for h in handles {
+            match h.join() {
+                Ok(part) => result.extend(part),
+                Err(e) => std::panic::resume_unwind(e)
+            }
+        }
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resultsKeepIndexOrder() {
+        let expected: Vec<usize> = (0..37).map(|i| i * i).collect();
+        for threads in [0, 1, 2, 3, 8, 100] {
+            assert_eq!(map(37, threads, |i| i * i), expected, "threads = {}", threads);
+        }
+        assert!(map(0, 4, |i| i).is_empty());
+    }
+}
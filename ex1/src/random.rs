@@ -0,0 +1,181 @@
+// ******************************************************************
+//  乱数
+//  外部crateを使わずに、シードを指定すれば同じ乱数列が得られるようにする
+//  乱数を使う処理（行のシャッフル、交差検証の分割、ブートストラップ、列のサンプリング、並べ替えによる重要度）は
+//  全てシードを受け取り、同じシードなら実行するたびに同じ結果になる
+//
+//  並列に実行する処理（アンサンブルの各木、列ごとの計算など）は、1つの乱数列を共有せずに
+//  deriveSeed(seed, 番号)で番号ごとに別のシードを作る．こうするとスレッド数や実行順によらない
+// ******************************************************************
+
+#![allow(non_snake_case)]
+
+#[cfg(test)]
+use super::U;   // main.rsのコメントを参照
+
+// -----------------------------------------------
+// 64bitの値を攪拌する(splitmix64)
+// -----------------------------------------------
+fn mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// -----------------------------------------------
+// シードと番号から、番号ごとに異なるシードを作る
+// (ex) let mut r = Rng::new(deriveSeed(seed, j as u64));  // j列目用
+//
+// @param seed 元のシード
+// @param stream 番号
+//
+// @return 派生したシード（同じ(seed, stream)なら常に同じ値）
+// -----------------------------------------------
+pub fn deriveSeed(seed: u64, stream: u64) -> u64 {
+    mix(mix(seed) ^ stream)
+}
+
+// =================================================
+//  乱数生成器（xorshift64*）
+// =================================================
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // 状態が0だとxorshiftは0しか返さなくなるので、シードを攪拌してから使う(splitmix64)
+        let mut z = mix(seed);
+        if z == 0 {
+            z = 0x9E37_79B9_7F4A_7C15;
+        }
+        Rng {
+            state: z
+        }
+    }
+
+    // 64bitの乱数
+    pub fn nextU64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // [0, 1)の一様乱数
+    pub fn nextF64(&mut self) -> f64 {
+        ((self.nextU64() >> 11) as f64) / ((1u64 << 53) as f64)
+    }
+
+    // 0以上n未満の整数
+    pub fn range(&mut self, n: usize) -> usize {
+        ((self.nextF64() * (n as f64)) as usize).min(n - 1)
+    }
+
+    // -----------------------------------------------
+    // 配列をシャッフルする(Fisher-Yates)
+    // -----------------------------------------------
+    pub fn shuffle<T>(&mut self, v: &mut [T]) {
+        for i in (1..v.len()).rev() {
+            let j = self.range(i + 1);
+            v.swap(i, j);
+        }
+    }
+
+    // 0..nを並べ替えたもの
+    pub fn permutation(&mut self, n: usize) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..n).collect();
+        self.shuffle(&mut order);
+        order
+    }
+
+    // -----------------------------------------------
+    // ブートストラップ標本（0..nから重複を許してn個選ぶ）
+    //
+    // @return 選んだ番号（昇順）
+    // -----------------------------------------------
+    pub fn bootstrap(&mut self, n: usize) -> Vec<usize> {
+        let mut rows: Vec<usize> = (0..n).map(|_| self.range(n)).collect();
+        rows.sort_unstable();
+        rows
+    }
+
+    // -----------------------------------------------
+    // 0..nから重複なしでk個選ぶ（kがn以上なら全て）
+    //
+    // @return 選んだ番号（昇順）
+    // -----------------------------------------------
+    pub fn choose(&mut self, n: usize, k: usize) -> Vec<usize> {
+        let k = k.min(n);
+        let mut all: Vec<usize> = (0..n).collect();
+        // Fisher-Yatesを前からk個分だけ行う
+        for i in 0..k {
+            let j = i + self.range(n - i);
+            all.swap(i, j);
+        }
+        let mut chosen = all[..k].to_vec();
+        chosen.sort_unstable();
+        chosen
+    }
+}
+
+// -----------------------------------------------
+// テスト用の乱数データ（0以上1未満の説明変数ncol列と、1列目、2列目から決まる目的変数に小さな雑音を加えたもの）
+// 決定木の分割探索を並列に行う大きさのデータを作るのに使う
+// -----------------------------------------------
+#[cfg(test)]
+pub(crate) fn noisyData(nrow: usize, ncol: usize, seed: u64) -> (U::Matrix, U::Matrix) {
+    let mut rng = Rng::new(seed);
+    let x: U::Matrix = (0..ncol).map(|_| (0..nrow).map(|_| rng.nextF64()).collect()).collect();
+    let y: Vec<f64> = (0..nrow).map(|i| x[0][i] * 3.0 + if x[1][i] > 0.5 { 1.0 } else { 0.0 } + rng.nextF64() * 0.1).collect();
+    (x, vec![y])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sameSeedSameSequence() {
+        let draw = |seed| {
+            let mut r = Rng::new(seed);
+            (0..5).map(|_| r.nextU64()).collect::<Vec<u64>>()
+        };
+        assert_eq!(draw(42), draw(42));
+        assert_ne!(draw(42), draw(43));
+        assert!(draw(0).iter().all(|v| *v != 0));   // シード0でも0だけにならない
+        assert_ne!(deriveSeed(1, 0), deriveSeed(1, 1));
+        assert_ne!(deriveSeed(1, 0), deriveSeed(2, 0));
+    }
+
+    #[test]
+    fn valuesStayInRange() {
+        let mut r = Rng::new(7);
+        for _ in 0..1000 {
+            let f = r.nextF64();
+            assert!((0.0..1.0).contains(&f));
+            assert!(r.range(3) < 3);
+        }
+    }
+
+    #[test]
+    fn samplingHelpers() {
+        let mut r = Rng::new(1);
+        let mut p = r.permutation(10);
+        p.sort_unstable();
+        assert_eq!(p, (0..10).collect::<Vec<usize>>());
+
+        let c = r.choose(20, 5);
+        assert_eq!(c.len(), 5);
+        assert!(c.windows(2).all(|w| w[0] < w[1]) && c.iter().all(|i| *i < 20));
+        assert_eq!(r.choose(3, 10), vec![0, 1, 2]);
+
+        let b = r.bootstrap(50);
+        assert_eq!(b.len(), 50);
+        assert!(b.windows(2).all(|w| w[0] <= w[1]) && b.iter().all(|i| *i < 50));
+        assert!(b.windows(2).any(|w| w[0] == w[1]));    // 重複を許す
+    }
+}
@@ -0,0 +1,281 @@
+// ******************************************************************
+//  ハイパーパラメータ探索（グリッドサーチ、ランダムサーチ）
+//  交差検証(k-fold cross validation)のスコアで候補を順位付けする
+// ******************************************************************
+
+#![allow(non_snake_case)]
+
+use super::U;   // main.rsのコメントを参照
+use super::random;
+use super::parallel;
+
+// パラメータの組み合わせ1つ分．(パラメータ名, 値)のリスト
+pub type Params = Vec<(String, f64)>;
+
+// スコア関数の型定義（正解値, 予測値） -> スコア．大きいほど良い
+pub type Scorer = fn(&[f64], &[f64]) -> f64;
+
+// =================================================
+//  パラメータ空間
+//  パラメータごとに候補値のリストを持つ
+// =================================================
+pub struct ParamSpace {
+    pub params: Vec<(String, Vec<f64>)>
+}
+
+impl ParamSpace {
+    pub fn new() -> Self {
+        ParamSpace {
+            params: vec![]
+        }
+    }
+
+    // パラメータと候補値を追加する
+    // (ex) let space = ParamSpace::new().add("max_depth", &[2.0, 3.0, 4.0]);
+    pub fn add(mut self, name: &str, values: &[f64]) -> Self {
+        self.params.push((name.to_string(), values.to_vec()));
+        self
+    }
+
+    // -----------------------------------------------
+    // 全ての組み合わせを列挙する（グリッドサーチ用）
+    // -----------------------------------------------
+    pub fn grid(&self) -> Vec<Params> {
+        let mut result: Vec<Params> = vec![vec![]];
+        for (name, values) in &self.params {
+            let mut next = Vec::<Params>::new();
+            for p in &result {
+                for v in values {
+                    let mut q = p.clone();
+                    q.push((name.clone(), *v));
+                    next.push(q);
+                }
+            }
+            result = next;
+        }
+        result
+    }
+
+    // -----------------------------------------------
+    // 組み合わせを無作為にn個選ぶ（ランダムサーチ用）
+    // 同じ組み合わせは2度選ばない．nが組み合わせの総数以上なら全部返す
+    //
+    // @param n 選ぶ個数
+    // @param rng 乱数生成器
+    // -----------------------------------------------
+    pub fn sample(&self, n: usize, rng: &mut random::Rng) -> Vec<Params> {
+        let mut all = self.grid();
+        rng.shuffle(&mut all);
+        all.truncate(n);
+        all
+    }
+}
+
+// -----------------------------------------------
+// パラメータの値を名前で取り出す
+//
+// @param params パラメータの組み合わせ
+// @param name パラメータ名
+//
+// @return 値．paramsに含まれていなければNone
+// -----------------------------------------------
+pub fn param(params: &Params, name: &str) -> Option<f64> {
+    params.iter().find(|(n, _)| n == name).map(|(_, v)| *v)
+}
+
+// -----------------------------------------------
+// パラメータの組み合わせを"name=value, ..."の形の文字列にする
+// -----------------------------------------------
+pub fn paramsToString(params: &Params) -> String {
+    let s: Vec<String> = params.iter().map(|(n, v)| format!("{}={}", n, v)).collect();
+    s.join(", ")
+}
+
+// =================================================
+//  交差検証
+// =================================================
+
+// -----------------------------------------------
+// 行番号をk個のグループに分け、(学習用, 検証用)の行番号の組をk個作る
+//
+// @param nrow 行数
+// @param k 分割数（nrowより大きければnrowにする）
+// @param rng 乱数生成器．行をシャッフルしてから分ける
+//
+// @return (学習用の行番号, 検証用の行番号)のリスト．nrowが2未満なら分けられないので空
+// -----------------------------------------------
+pub fn kfold(nrow: usize, k: usize, rng: &mut random::Rng) -> Vec<(Vec<usize>, Vec<usize>)> {
+    if nrow < 2 {
+        return vec![];
+    }
+    let k = k.min(nrow).max(2);
+    let mut order: Vec<usize> = (0..nrow).collect();
+    rng.shuffle(&mut order);
+
+    let mut result = Vec::<(Vec<usize>, Vec<usize>)>::new();
+    for ifold in 0..k {
+        // ifold番目のグループ = order[start..end]
+        let start = ifold * nrow / k;
+        let end = (ifold + 1) * nrow / k;
+        let test: Vec<usize> = order[start..end].to_vec();
+        let mut train: Vec<usize> = order[..start].to_vec();
+        train.extend_from_slice(&order[end..]);
+        result.push((train, test));
+    }
+    result
+}
+
+// -----------------------------------------------
+// 交差検証でスコアを計算する
+//
+// @param x 説明変数
+// @param y 目的変数（1列目をスコアの計算に使う）
+// @param folds kfold()の結果
+// @param fitPredict 学習用データ(x, y)でモデルを作り、検証用のxの予測値を返す関数（モデルの誤りはErr）
+// @param scorer スコア関数
+//
+// @return foldごとのスコア．fitPredictがErrを返せばそのErr
+// -----------------------------------------------
+pub fn crossValidate<F, E>(x: &U::Matrix, y: &U::Matrix, folds: &[(Vec<usize>, Vec<usize>)], fitPredict: F, scorer: Scorer) -> Result<Vec<f64>, E>
+    where F: Fn(&U::Matrix, &U::Matrix, &U::Matrix) -> Result<Vec<f64>, E>
+{
+    let mut scores = Vec::<f64>::new();
+    for (train, test) in folds {
+        let xtrain = U::MatSelectRow(x, train);
+        let ytrain = U::MatSelectRow(y, train);
+        let xtest = U::MatSelectRow(x, test);
+        let ytest = U::MatSelectRow(y, test);
+
+        let pred = fitPredict(&xtrain, &ytrain, &xtest)?;
+        scores.push(scorer(&ytest[0], &pred));
+    }
+    Ok(scores)
+}
+
+// =================================================
+//  探索結果（1候補分）
+// =================================================
+pub struct SearchResult {
+    pub params: Params,
+    pub meanScore: f64,     // foldごとのスコアの平均
+    pub stdScore: f64       // foldごとのスコアの標準偏差
+}
+
+// ============================================================
+//  候補のパラメータそれぞれについて交差検証を行い、スコアの良い順に並べて返す
+//  全ての候補で同じ分割(folds)を使う．候補ごとの交差検証は互いに独立なのでthreads個のスレッドで分担する
+//
+//  @param candidates パラメータの候補（ParamSpace::grid()またはsample()の結果）
+//  @param x 説明変数
+//  @param y 目的変数
+//  @param folds 交差検証の分割（kfold()の結果）
+//  @param threads スレッド数（結果はスレッド数によらない）
+//  @param fitPredict (パラメータ, 学習用x, 学習用y, 検証用x) -> 検証用xの予測値（モデルの誤りはErr）
+//  @param scorer スコア関数（大きいほど良い）
+//
+//  @return スコアの平均が大きい順に並べた結果．fitPredictがErrを返せば（候補の順で）最初のErr
+// ============================================================
+pub fn search<F, E>(candidates: &[Params], x: &U::Matrix, y: &U::Matrix, folds: &[(Vec<usize>, Vec<usize>)], threads: usize, fitPredict: F, scorer: Scorer) -> Result<Vec<SearchResult>, E>
+    where F: Fn(&Params, &U::Matrix, &U::Matrix, &U::Matrix) -> Result<Vec<f64>, E> + Sync, E: Send
+{
+    let results: Result<Vec<SearchResult>, E> = parallel::map(candidates.len(), threads, |i| {
+        let params = &candidates[i];
+        let scores = crossValidate(x, y, folds, |xtr, ytr, xte| fitPredict(params, xtr, ytr, xte), scorer)?;
+        Ok(SearchResult {
+            params: params.clone(),
+            meanScore: U::mean(&scores),
+            stdScore: U::stdev(&scores)
+        })
+    }).into_iter().collect();
+    let mut results = results?;
+
+    // スコアの平均の大きい順（NaNは最後）
+    results.sort_by(|a, b| {
+        b.meanScore.partial_cmp(&a.meanScore).unwrap_or_else(|| a.meanScore.is_nan().cmp(&b.meanScore.is_nan()))
+    });
+    Ok(results)
+}
+
+// -----------------------------------------------
+// 探索結果を順位表として表示する
+// -----------------------------------------------
+pub fn printResults(results: &[SearchResult]) {
+    println!("{:>5}{:>14}{:>12}  params", "rank", "mean score", "std");
+    for (i, r) in results.iter().enumerate() {
+        println!("{:>5}{:>14.6}{:>12.6}  {}", i+1, r.meanScore, r.stdScore, paramsToString(&r.params));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kfoldPartitionsRows() {
+        let nrow = 23;
+        let folds = kfold(nrow, 5, &mut random::Rng::new(1));
+        assert_eq!(folds.len(), 5);
+
+        // 検証用の行を全foldで合わせると、どの行もちょうど1回ずつ現れる
+        let mut tested: Vec<usize> = folds.iter().flat_map(|(_, test)| test.clone()).collect();
+        tested.sort_unstable();
+        assert_eq!(tested, (0..nrow).collect::<Vec<usize>>());
+
+        // 各foldで学習用と検証用は重ならず、合わせると全ての行になる
+        for (train, test) in &folds {
+            assert!(test.len() == 4 || test.len() == 5);
+            assert!(train.iter().all(|i| !test.contains(i)));
+            let mut all = train.clone();
+            all.extend_from_slice(test);
+            all.sort_unstable();
+            assert_eq!(all, (0..nrow).collect::<Vec<usize>>());
+        }
+    }
+
+    #[test]
+    fn kfoldClampsFoldCount() {
+        assert!(kfold(0, 5, &mut random::Rng::new(1)).is_empty());
+        assert!(kfold(1, 5, &mut random::Rng::new(1)).is_empty());
+        let folds = kfold(3, 10, &mut random::Rng::new(1));
+        assert_eq!(folds.len(), 3);
+        assert!(folds.iter().all(|(train, test)| train.len() == 2 && test.len() == 1));
+    }
+
+    #[test]
+    fn kfoldIsReproducible() {
+        assert_eq!(kfold(50, 4, &mut random::Rng::new(7)), kfold(50, 4, &mut random::Rng::new(7)));
+    }
+
+    #[test]
+    fn gridAndSample() {
+        let space = ParamSpace::new().add("a", &[1.0, 2.0]).add("b", &[10.0, 20.0, 30.0]);
+        let grid = space.grid();
+        assert_eq!(grid.len(), 6);
+        assert_eq!(paramsToString(&grid[1]), "a=1, b=20");
+        assert_eq!(param(&grid[5], "a"), Some(2.0));
+        assert_eq!(param(&grid[5], "c"), None);
+
+        // 重複なしで選び、総数を超えれば全部
+        let mut picked: Vec<String> = space.sample(4, &mut random::Rng::new(3)).iter().map(paramsToString).collect();
+        picked.sort();
+        picked.dedup();
+        assert_eq!(picked.len(), 4);
+        assert_eq!(space.sample(100, &mut random::Rng::new(3)).len(), 6);
+    }
+
+    #[test]
+    fn searchRanksByMeanScore() {
+        let x: U::Matrix = vec![(0..10).map(|i| i as f64).collect()];
+        let y: U::Matrix = vec![(0..10).map(|i| (2 * i) as f64).collect()];
+        let folds = kfold(10, 5, &mut random::Rng::new(0));
+        let candidates = ParamSpace::new().add("slope", &[1.0, 2.0, 3.0]).grid();
+        let fitPredict = |p: &Params, _: &U::Matrix, _: &U::Matrix, xte: &U::Matrix| -> Result<Vec<f64>, ()> {
+            Ok(xte[0].iter().map(|v| v * param(p, "slope").unwrap()).collect())
+        };
+        for threads in [1, 3] {
+            let ranked = search(&candidates, &x, &y, &folds, threads, fitPredict, crate::metrics::negMse).unwrap();
+            assert_eq!(param(&ranked[0].params, "slope"), Some(2.0));
+            assert_eq!(ranked[0].meanScore, 0.0);
+        }
+    }
+}
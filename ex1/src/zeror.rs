@@ -7,6 +7,8 @@
 #![allow(non_snake_case)]
 
 use super::U;   // main.rsのコメントを参照
+use super::dataset;
+use super::dataset::{Dataset, ShapeError, View};
 
 // =================================================
 //  ZeroRuleモデル
@@ -17,7 +19,7 @@ pub struct ZeroRule {
 
 impl ZeroRule {
     pub fn new() -> Self {
-        return ZeroRule {
+        ZeroRule {
             r: Vec::<f64>::new()
         }
     }
@@ -27,18 +29,48 @@ impl ZeroRule {
     //
     // @param x: 説明変数(2次元配列. 変数(=列)ごとの値)
     // @param y: 目的変数(2次元配列．分類の時は列数>1, 回帰の時は列数=1）
+    //
+    // @return x, yの列の長さが揃っていなければShapeError
+    // ===============================================================
+    pub fn fit(&mut self, x: &U::Matrix, y: &U::Matrix) -> Result<(), ShapeError> {
+        let (xd, yd) = dataset::fromMatrices(x, y)?;
+        self.fitView(&xd.view(), &yd.view(), None)
+    }
+
+    // ===============================================================
+    //  行ごとの重みを付けてモデルを作成（目的変数列の重み付き平均）
+    //
+    // @param x: 説明変数
+    // @param y: 目的変数
+    // @param weights: 行ごとの重み（長さ=行数）
+    // ===============================================================
+    pub fn fitWeighted(&mut self, x: &U::Matrix, y: &U::Matrix, weights: &[f64]) -> Result<(), ShapeError> {
+        let (xd, yd) = dataset::fromMatrices(x, y)?;
+        self.fitView(&xd.view(), &yd.view(), Some(weights))
+    }
+
+    // ===============================================================
+    //  モデル作成（データセットのビューから）
+    //
+    // @param x: 説明変数（使わない）
+    // @param y: 目的変数
+    // @param weights: 行ごとの重み（Noneなら重みなし）
     // ===============================================================
-    pub fn fit(&mut self, x: &Vec<Vec<f64>>, y: &Vec<Vec<f64>>) {
-        // let ncols = x.len();
-        // println!("ncols = {}", ncols);
+    pub fn fitView(&mut self, x: &View, y: &View, weights: Option<&[f64]>) -> Result<(), ShapeError> {
+        dataset::checkRows(x, y)?;
+        dataset::checkWeights(y, weights)?;
 
         // 内容クリア
         self.r.clear();
 
-        // 目的変数列の平均を計算してrにストア
-        for z in y.iter() {
-            self.r.push(U::mean(z));
+        // 目的変数列の平均を計算してrにストア（重みがあれば重み付き平均．計算はU::meanと同じ）
+        for j in 0..y.ncols() {
+            self.r.push(match weights {
+                Some(w) => y.columnIter(j).zip(w.iter()).fold(0.0, |total, (e, we)| total + e * we) / w.iter().sum::<f64>(),
+                None => y.columnIter(j).fold(0.0, |total, e| total + e) / (y.nrows() as f64)
+            });
         }
+        Ok(())
     }
 
     // ============================================================
@@ -46,15 +78,21 @@ impl ZeroRule {
     //
     // @param x : 説明変数(2次元配列(mxn). 変数(=列)ごとの値)
     //
-    // @return 予測値．m行x1列の行列（mはxの行数）
+    // @return 予測値．m行x1列の行列（mはxの行数）．xの列の長さが揃っていなければShapeError
     // ZeroRuleは予測値の計算にxの値は使わない．
     // fitの際に計算した目的変数の平均値をxの値に無関係に返すだけ．
     // すなわち全要素がself.rのmx1の行列を返す
     // ============================================================
-    pub fn predict(&self, x: &Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    pub fn predict(&self, x: &U::Matrix) -> Result<U::Matrix, ShapeError> {
+        let xd = Dataset::fromMatrix(x)?;
+        Ok(self.predictView(&xd.view()))
+    }
+
+    // 予測値を計算（データセットのビューから）
+    pub fn predictView(&self, x: &View) -> U::Matrix {
         let mut result = vec![];
         let ncols = self.r.len();
-        let nrows = x[0].len(); // xの行数
+        let nrows = x.nrows(); // xの行数
         for icol in 0..ncols {
             let mut resultCol = Vec::<f64>::new();
             for _ in 0..nrows {
@@ -68,4 +106,28 @@ impl ZeroRule {
     pub fn print(&self) {
         println!("r={:?}", self.r);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn predictsColumnMeans() {
+        let x: U::Matrix = vec![vec![0.0; 4]];
+        let y: U::Matrix = vec![vec![1.0, 2.0, 3.0, 6.0], vec![0.0, 0.0, 1.0, 1.0]];
+        let mut z = ZeroRule::new();
+        z.fit(&x, &y).unwrap();
+        assert_eq!(z.predict(&vec![vec![0.0; 2]]).unwrap(), vec![vec![3.0, 3.0], vec![0.5, 0.5]]);
+        assert!(matches!(z.fit(&vec![vec![0.0; 3]], &y), Err(ShapeError::Rows { x: 3, y: 4 })));
+    }
+
+    #[test]
+    fn weightedMean() {
+        let x: U::Matrix = vec![vec![0.0; 4]];
+        let y: U::Matrix = vec![vec![1.0, 2.0, 3.0, 6.0]];
+        let mut z = ZeroRule::new();
+        z.fitWeighted(&x, &y, &[1.0, 0.0, 0.0, 3.0]).unwrap();
+        assert_eq!(z.r, vec![(1.0 + 18.0) / 4.0]);
+    }
+}